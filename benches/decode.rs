@@ -0,0 +1,61 @@
+//! Throughput of decoding a representative mix of instructions, word by word with `decode` and
+//! in bulk with `decode_all_into`. Both work on words in memory, see `benches/load.rs` for the
+//! cost of loading a binary.
+//!
+//! Run with `cargo bench --bench decode`. The words cycle through
+//! [`common::INSTRUCTION_MIX`], which covers every RISC-U format, plus the RV64IMC instructions
+//! emitted by GCC for selfie.
+
+#[path = "../tests/common/mod.rs"]
+mod common;
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use riscu::{decode, decode_all_into};
+
+const WORDS: usize = 1 << 16;
+
+const EXTRA: [u32; 8] = [
+    0x00c5a52f, // amoadd.w a0,a2,(a1)
+    0x0ff0000f, // fence
+    0x00008067, // ret
+    0x4045d51b, // sraiw a0,a1,4
+    0x00001097, // auipc ra,0x1
+    0x17e1,     // c.addi a5,-8
+    0x02b55533, // divu a0,a0,a1
+    0x00b51463, // bne a0,a1,8
+];
+
+fn decoding(c: &mut Criterion) {
+    let words = common::INSTRUCTION_MIX
+        .iter()
+        .chain(EXTRA.iter())
+        .copied()
+        .cycle()
+        .take(WORDS)
+        .collect::<Vec<_>>();
+    assert!(words.iter().all(|w| decode(*w).is_ok()));
+
+    let mut group = c.benchmark_group("decode");
+    group.throughput(Throughput::Elements(WORDS as u64));
+
+    // the re-encoded instructions are folded into a checksum, such that none of them is optimized
+    // away
+    group.bench_function("decode", |b| {
+        b.iter(|| {
+            black_box(&words)
+                .iter()
+                .map(|w| u32::from(decode(*w).unwrap()))
+                .fold(0, |sum, w| sum ^ w)
+        })
+    });
+
+    let mut out = Vec::with_capacity(WORDS);
+    group.bench_function("decode_all", |b| {
+        b.iter(|| decode_all_into(black_box(&words), &mut out))
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, decoding);
+criterion_main!(benches);
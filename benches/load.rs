@@ -0,0 +1,40 @@
+//! Throughput of loading and decoding a large RISC-U binary from disk, in bytes of the file and
+//! in instructions including the decoding of every instruction. See `benches/decode.rs` for the
+//! cost of decoding alone.
+//!
+//! Run with `cargo bench --bench load`. The fixture is synthesized by [`common::ElfBuilder::large_riscu`],
+//! so the numbers are reproducible across machines up to their speed.
+
+#[path = "../tests/common/mod.rs"]
+mod common;
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use riscu::load_object_file;
+use tempfile::tempdir;
+
+const INSTRUCTIONS: usize = 1 << 20;
+
+fn loading(c: &mut Criterion) {
+    let dir = tempdir().unwrap();
+    let path = common::ElfBuilder::large_riscu(INSTRUCTIONS).write(&dir);
+    let size = std::fs::metadata(&path).unwrap().len();
+
+    let load = || load_object_file(&path).unwrap().decode().unwrap();
+    assert_eq!(load().iter_instructions().count(), INSTRUCTIONS);
+
+    let mut group = c.benchmark_group("load");
+    group.throughput(Throughput::Bytes(size));
+    group.sample_size(20);
+
+    group.bench_function("load_and_decode", |b| b.iter(load));
+
+    group.throughput(Throughput::Elements(INSTRUCTIONS as u64));
+    group.bench_function("load_and_decode_instructions", |b| {
+        b.iter(|| load().try_instructions().unwrap())
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, loading);
+criterion_main!(benches);
@@ -0,0 +1,17 @@
+//! Decoding arbitrary words never panics, neither one word at a time nor as a mixed stream of
+//! compressed and 32-bit instructions.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use riscu::{decode, decode_mixed};
+
+fuzz_target!(|data: &[u8]| {
+    for word in data.chunks_exact(4) {
+        let _ = decode(u32::from_le_bytes([word[0], word[1], word[2], word[3]]));
+    }
+
+    for (offset, _) in decode_mixed(data) {
+        assert!(offset < data.len() as u64);
+    }
+});
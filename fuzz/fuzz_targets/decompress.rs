@@ -0,0 +1,17 @@
+//! Decompressing arbitrary half-words never panics, and both ways of decompressing them agree.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use riscu::{
+    decode,
+    decompress::{decompress, decompress_to_instruction},
+};
+
+fuzz_target!(|data: &[u8]| {
+    for halfword in data.chunks_exact(2) {
+        let i = u16::from_le_bytes([halfword[0], halfword[1]]);
+
+        assert_eq!(decompress(i).and_then(decode), decompress_to_instruction(i));
+    }
+});
@@ -0,0 +1,956 @@
+//! # Static analyses over decoded programs
+//!
+//! The analyses stop at the first encoding in the code segment which can not be decoded, such as
+//! trailing padding.
+
+use crate::{
+    decode, is_valid_riscu_word, iterators::EncodingIter, DecodedProgram, Instruction, Register,
+    INSTRUCTION_SIZE,
+};
+use byteorder::{ByteOrder, LittleEndian};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    mem::size_of,
+    ops::Range,
+};
+
+impl DecodedProgram {
+    /// Index ranges of instructions which follow an unconditional jump and can not be reached
+    /// by any direct branch or jump.
+    pub fn unreachable_spans(&self) -> Vec<Range<usize>> {
+        let instructions = self.addressed_instructions();
+
+        let targets = instructions
+            .iter()
+            .filter_map(|(pc, instruction)| instruction.branch_target(*pc))
+            .collect::<HashSet<_>>();
+
+        let mut spans = Vec::new();
+        let mut start = None;
+
+        for (idx, (pc, _)) in instructions.iter().enumerate() {
+            if targets.contains(pc) {
+                if let Some(start) = start.take() {
+                    spans.push(start..idx);
+                }
+            } else if start.is_none() && idx > 0 && instructions[idx - 1].1.is_unconditional_jump()
+            {
+                start = Some(idx);
+            }
+        }
+
+        if let Some(start) = start {
+            spans.push(start..instructions.len());
+        }
+
+        spans
+    }
+
+    /// Index of the first instruction of the trailing run of zero or otherwise illegal words in
+    /// the code segment, i.e. the number of actual instructions if the segment is padded.
+    pub fn code_end(&self) -> usize {
+        self.scan_code_end().0
+    }
+
+    /// Byte offset into the code segment where the trailing padding of [`code_end`] begins.
+    ///
+    /// [`code_end`]: Self::code_end
+    pub(crate) fn code_end_offset(&self) -> usize {
+        self.scan_code_end().1
+    }
+
+    fn scan_code_end(&self) -> (usize, usize) {
+        let mut end = (0, 0);
+
+        for (idx, (address, raw, length)) in
+            EncodingIter::new(&self.code.content, self.code.address).enumerate()
+        {
+            if decode(raw).is_ok() {
+                end = (idx + 1, (address - self.code.address) as usize + length);
+            }
+        }
+
+        end
+    }
+
+    /// Addresses and instructions of all control transfers, see
+    /// [`Instruction::is_control_transfer`].
+    pub fn control_flow_instructions(&self) -> impl Iterator<Item = (u64, Instruction)> + '_ {
+        self.decoded_encodings()
+            .map(|(pc, instruction, _)| (pc, instruction))
+            .filter(|(_, instruction)| instruction.is_control_transfer())
+    }
+
+    /// Addresses of all `ecall` instructions, e.g. to trace syscalls with breakpoints.
+    pub fn ecall_sites(&self) -> Vec<u64> {
+        self.decoded_encodings()
+            .filter(|(_, instruction, _)| matches!(instruction, Instruction::Ecall(_)))
+            .map(|(pc, _, _)| pc)
+            .collect()
+    }
+
+    /// Number of times each register (indexed by its number) is read or written by an
+    /// instruction of the program.
+    pub fn register_usage(&self) -> [usize; 32] {
+        let mut usage = [0; 32];
+
+        for instruction in self.decoded_instructions() {
+            for register in instruction.reads().into_iter().chain(instruction.writes()) {
+                usage[u32::from(register) as usize] += 1;
+            }
+        }
+
+        usage
+    }
+
+    /// Whether any instruction of the M extension (multiplication and division) is used.
+    pub fn uses_m_extension(&self) -> bool {
+        self.decoded_instructions()
+            .any(|instruction| instruction.is_m_extension())
+    }
+
+    /// Number of occurrences of each mnemonic in the code segment, e.g. to see which
+    /// instructions a program exercises.
+    pub fn opcode_histogram(&self) -> BTreeMap<&'static str, usize> {
+        let mut histogram = BTreeMap::new();
+
+        for instruction in self.decoded_instructions() {
+            *histogram.entry(instruction.mnemonic()).or_insert(0) += 1;
+        }
+
+        histogram
+    }
+
+    /// A human-readable overview of the program: number of instructions, size and address of
+    /// the segments, the five most frequent mnemonics, the extensions in use and the number of
+    /// syscalls, one line each.
+    pub fn summary(&self) -> String {
+        let mnemonics = self.opcode_histogram();
+
+        let total = mnemonics.values().sum::<usize>();
+        let compressed = self
+            .decoded_encodings()
+            .filter(|(_, _, length)| *length == 2)
+            .count();
+
+        let mut top = mnemonics.into_iter().collect::<Vec<_>>();
+        top.sort_by(|(a, a_count), (b, b_count)| b_count.cmp(a_count).then(a.cmp(b)));
+
+        let top = top
+            .iter()
+            .take(5)
+            .map(|(mnemonic, count)| format!("{} {}", mnemonic, count))
+            .collect::<Vec<_>>();
+
+        let yes_no = |used| if used { "yes" } else { "no" };
+
+        format!(
+            "instructions: {} ({} compressed)\n\
+             code: {} bytes at {:#x}\n\
+             data: {} bytes at {:#x}\n\
+             top opcodes: {}\n\
+             M extension: {}\n\
+             C extension: {}\n\
+             syscalls: {}\n",
+            total,
+            compressed,
+            self.code.content.len(),
+            self.code.address,
+            self.data.content.len() * size_of::<u64>(),
+            self.data.address,
+            top.join(", "),
+            yes_no(self.uses_m_extension()),
+            yes_no(compressed > 0),
+            self.ecall_sites().len(),
+        )
+    }
+
+    /// Indices of the instructions from which control can flow into each instruction, i.e. the
+    /// preceding instruction unless it is an unconditional jump, and all direct branches and
+    /// jumps targeting it.
+    pub fn predecessors(&self) -> Vec<Vec<usize>> {
+        let instructions = self.addressed_instructions();
+
+        let indices = instructions
+            .iter()
+            .enumerate()
+            .map(|(idx, (pc, _))| (*pc, idx))
+            .collect::<HashMap<_, _>>();
+
+        let mut predecessors = vec![Vec::new(); instructions.len()];
+
+        for (idx, (pc, instruction)) in instructions.iter().enumerate() {
+            if idx + 1 < instructions.len() && !instruction.is_unconditional_jump() {
+                predecessors[idx + 1].push(idx);
+            }
+
+            if let Some(target) = instruction.branch_target(*pc).and_then(|t| indices.get(&t)) {
+                predecessors[*target].push(idx);
+            }
+        }
+
+        predecessors
+    }
+
+    /// Address ranges of the functions in the code segment, delimited by the symbols in
+    /// `symbols` (address to name). Every function extends to the next symbol or the end of
+    /// the code segment.
+    pub fn functions(&self, symbols: &BTreeMap<u64, String>) -> Vec<(String, Range<u64>)> {
+        let code = self.code_range();
+        let starts = symbols.range(code.clone()).collect::<Vec<_>>();
+
+        starts
+            .iter()
+            .enumerate()
+            .map(|(idx, (start, name))| {
+                let end = starts.get(idx + 1).map_or(code.end, |(next, _)| **next);
+
+                ((*name).clone(), **start..end)
+            })
+            .collect()
+    }
+
+    /// Number of instructions of each function, see [`DecodedProgram::functions`].
+    pub fn function_sizes(&self, symbols: &BTreeMap<u64, String>) -> Vec<(String, usize)> {
+        let locations = self
+            .decoded_encodings()
+            .map(|(pc, _, _)| pc)
+            .collect::<Vec<_>>();
+
+        self.functions(symbols)
+            .into_iter()
+            .map(|(name, range)| {
+                let size = locations.iter().filter(|pc| range.contains(pc)).count();
+
+                (name, size)
+            })
+            .collect()
+    }
+
+    /// Instruction indices and targets of calls, i.e. linking `jal`s and linking `jalr`s which
+    /// jump relative to a directly preceding `auipc` (`auipc ra, hi; jalr ra, lo(ra)`).
+    pub fn resolved_calls(&self) -> Vec<(usize, u64)> {
+        let instructions = self.addressed_instructions();
+        let mut calls = Vec::new();
+
+        for (idx, (pc, instruction)) in instructions.iter().enumerate() {
+            match instruction {
+                Instruction::Jal(j) if j.rd() != Register::Zero => {
+                    calls.extend(instruction.branch_target(*pc).map(|target| (idx, target)));
+                }
+                Instruction::Jalr(i) if i.rd() != Register::Zero && idx > 0 => {
+                    if let (auipc_pc, Instruction::Auipc(u)) = instructions[idx - 1] {
+                        if u.rd() == i.rs1() {
+                            let offset = instructions[idx - 1].1.effective_immediate().unwrap()
+                                + i64::from(i.imm());
+
+                            calls.push((idx, auipc_pc.wrapping_add(offset as u64)));
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        calls
+    }
+
+    /// Instruction indices of indirect jumps and calls (`jalr`, `c.jr` and `c.jalr`) whose
+    /// target is not statically known, e.g. to attempt jump table recovery at. Returns and calls
+    /// resolved by [`resolved_calls`](Self::resolved_calls) are left out.
+    pub fn indirect_jump_sites(&self) -> Vec<usize> {
+        let resolved = self
+            .resolved_calls()
+            .into_iter()
+            .map(|(idx, _)| idx)
+            .collect::<HashSet<_>>();
+
+        self.decoded_instructions()
+            .enumerate()
+            .filter(|(idx, instruction)| {
+                matches!(instruction, Instruction::Jalr(_))
+                    && !instruction.is_return()
+                    && !resolved.contains(idx)
+            })
+            .map(|(idx, _)| idx)
+            .collect()
+    }
+
+    /// Instruction indices and targets of tail calls, i.e. jumps which do not link (`j` and
+    /// `c.j`) to the entry of a function in `symbols` (address to name).
+    pub fn tail_calls(&self, symbols: &BTreeMap<u64, String>) -> Vec<(usize, u64)> {
+        self.addressed_instructions()
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, (pc, instruction))| match instruction {
+                Instruction::Jal(j) if j.rd() == Register::Zero => {
+                    Some((idx, instruction.branch_target(*pc)?))
+                }
+                _ => None,
+            })
+            .filter(|(_, target)| symbols.contains_key(target))
+            .collect()
+    }
+
+    /// Instruction indices of loads which read a doubleword just stored to the same address, i.e.
+    /// `sd rs2, off(base); ..; ld rd, off(base)`. Between the two, `base` and the stored register
+    /// must not be written and there must be neither another store nor a control transfer, so
+    /// the load can be replaced by a move from the stored register.
+    pub fn redundant_loads(&self) -> Vec<usize> {
+        let instructions = self.addressed_instructions();
+        let mut redundant = Vec::new();
+
+        for (idx, (_, instruction)) in instructions.iter().enumerate() {
+            let store = match instruction {
+                Instruction::Sd(s) => s,
+                _ => continue,
+            };
+
+            for (offset, (_, later)) in instructions[idx + 1..].iter().enumerate() {
+                if let Instruction::Ld(l) = later {
+                    if l.rs1() == store.rs1() && l.imm() == store.imm() {
+                        redundant.push(idx + 1 + offset);
+                        break;
+                    }
+                }
+
+                let clobbered = later
+                    .writes()
+                    .map_or(false, |r| r == store.rs1() || r == store.rs2());
+                let stores = matches!(
+                    later,
+                    Instruction::Sb(_)
+                        | Instruction::Sh(_)
+                        | Instruction::Sw(_)
+                        | Instruction::Sd(_)
+                );
+
+                if clobbered || stores || later.is_control_transfer() {
+                    break;
+                }
+            }
+        }
+
+        redundant
+    }
+
+    /// Whether the code can be relocated without rewriting it, i.e. it contains no `jalr` to an
+    /// absolute address built with `lui` (and optionally `addi`) in the same basic block. Jumps
+    /// relative to `auipc` and all branches and `jal`s are PC-relative.
+    ///
+    /// This is a heuristic: absolute addresses of data or loaded from memory are not detected.
+    pub fn is_position_independent(&self) -> bool {
+        // registers (indexed by their number) holding an absolute address
+        let mut absolute = [false; 32];
+        let idx = |register: Register| u32::from(register) as usize;
+
+        for instruction in self.decoded_instructions() {
+            match instruction {
+                Instruction::Jalr(i) if absolute[idx(i.rs1())] => return false,
+                Instruction::Lui(u) => absolute[idx(u.rd())] = true,
+                Instruction::Addi(i) | Instruction::Addiw(i) if absolute[idx(i.rs1())] => {
+                    absolute[idx(i.rd())] = true
+                }
+                _ if instruction.is_control_transfer() => absolute = [false; 32],
+                _ => {
+                    if let Some(rd) = instruction.writes() {
+                        absolute[idx(rd)] = false;
+                    }
+                }
+            }
+        }
+
+        true
+    }
+
+    /// Deepest stack frame of the entry function, i.e. the most negative sum of the
+    /// `addi sp, sp, imm` adjustments from the entry address up to the first `ret` or the end of
+    /// the code segment. `None` if the stack pointer is never decreased.
+    ///
+    /// This is a heuristic: control flow and dynamic allocations are not taken into account.
+    pub fn max_stack_frame(&self) -> Option<i64> {
+        let mut depth = 0;
+        let mut deepest = 0;
+
+        let entry_function = self
+            .addressed_instructions()
+            .into_iter()
+            .skip_while(|(pc, _)| *pc < self.entry_address);
+
+        for (_, instruction) in entry_function {
+            match instruction {
+                Instruction::Addi(i) if i.rd() == Register::Sp && i.rs1() == Register::Sp => {
+                    depth += i64::from(i.imm());
+                    deepest = deepest.min(depth);
+                }
+                _ if instruction.is_return() => break,
+                _ => {}
+            }
+        }
+
+        if deepest < 0 {
+            Some(deepest)
+        } else {
+            None
+        }
+    }
+
+    /// Syscall number of the `ecall` at instruction index `idx`, i.e. `N` of the preceding
+    /// `addi a7, zero, N` in the same basic block. `None` if the instruction is no `ecall` or
+    /// the number is not set by such an instruction.
+    pub fn syscall_number_before(&self, idx: usize) -> Option<u64> {
+        let instructions = self
+            .decoded_instructions()
+            .take(idx + 1)
+            .collect::<Vec<_>>();
+
+        if !matches!(instructions.get(idx), Some(Instruction::Ecall(_))) {
+            return None;
+        }
+
+        for instruction in instructions[..idx].iter().rev() {
+            match instruction {
+                Instruction::Addi(i) if i.rd() == Register::A7 && i.rs1() == Register::Zero => {
+                    return Some(i.imm() as u64);
+                }
+                _ if instruction.writes() == Some(Register::A7) => return None,
+                _ if instruction.is_control_transfer() => return None,
+                _ => {}
+            }
+        }
+
+        None
+    }
+
+    /// Whether every function called with `jal ra` returns, i.e. contains a `ret` when following
+    /// its instructions in order from the call target, starting with the calls of the entry
+    /// function. Calls to addresses outside of the code segment are unbalanced.
+    ///
+    /// This is a heuristic: branches within a function are not taken into account.
+    pub fn check_call_return_balance(&self) -> bool {
+        let instructions = self.addressed_instructions();
+
+        let indices = instructions
+            .iter()
+            .enumerate()
+            .map(|(idx, (pc, _))| (*pc, idx))
+            .collect::<HashMap<_, _>>();
+
+        let mut checked = HashSet::new();
+        let mut pending = vec![(self.entry_address, false)];
+
+        while let Some((start, must_return)) = pending.pop() {
+            let start = match indices.get(&start) {
+                Some(start) => *start,
+                None => return false,
+            };
+
+            let mut returns = false;
+
+            for (pc, instruction) in &instructions[start..] {
+                match instruction {
+                    Instruction::Jal(j) if j.rd() == Register::Ra => {
+                        let target = instruction.branch_target(*pc).unwrap();
+
+                        if checked.insert(target) {
+                            pending.push((target, true));
+                        }
+                    }
+                    _ if instruction.is_return() => {
+                        returns = true;
+                        break;
+                    }
+                    _ => {}
+                }
+            }
+
+            if must_return && !returns {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    /// Fraction of the instructions whose address is in `executed`, e.g. as traced by an
+    /// emulator. Addresses which are not the start of an instruction are ignored, and a program
+    /// without instructions has a coverage of 0.
+    pub fn coverage(&self, executed: &HashSet<u64>) -> f64 {
+        let (total, covered) =
+            self.decoded_encodings()
+                .fold((0, 0), |(total, covered), (pc, _, _)| {
+                    (total + 1, covered + usize::from(executed.contains(&pc)))
+                });
+
+        if total == 0 {
+            0.0
+        } else {
+            covered as f64 / total as f64
+        }
+    }
+
+    fn addressed_instructions(&self) -> Vec<(u64, Instruction)> {
+        self.decoded_encodings()
+            .map(|(pc, instruction, _)| (pc, instruction))
+            .collect()
+    }
+
+    fn decoded_instructions(&self) -> impl Iterator<Item = Instruction> + '_ {
+        self.decoded_encodings()
+            .map(|(_, instruction, _)| instruction)
+    }
+
+    /// Address, instruction and length of the encodings in the code segment up to the first one
+    /// which can not be decoded, such as the trailing padding accepted by a lenient load, where
+    /// the analyses stop instead of failing.
+    fn decoded_encodings(&self) -> impl Iterator<Item = (u64, Instruction, usize)> + '_ {
+        EncodingIter::new(&self.code.content, self.code.address).map_while(
+            |(address, raw, length)| {
+                decode(raw)
+                    .ok()
+                    .map(|instruction| (address, instruction, length))
+            },
+        )
+    }
+}
+
+/// Guess where code ends and data begins in a raw blob without ELF structure, i.e. the offset
+/// of the first word which is not a valid RISC-U instruction.
+pub fn guess_code_data_split(bytes: &[u8]) -> usize {
+    bytes
+        .chunks_exact(INSTRUCTION_SIZE)
+        .position(|word| !is_valid_riscu_word(LittleEndian::read_u32(word)))
+        .unwrap_or(bytes.len() / INSTRUCTION_SIZE)
+        * INSTRUCTION_SIZE
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use crate::{DecodedProgram, ProgramSegment, Register};
+    use std::collections::{BTreeMap, HashSet};
+
+    /// Build a program with the code segment at `0x10000` from 32-bit instruction words.
+    pub(crate) fn program(words: &[u32]) -> DecodedProgram {
+        DecodedProgram {
+            code: ProgramSegment {
+                address: 0x10000,
+                content: words.iter().flat_map(|w| w.to_le_bytes()).collect(),
+            },
+            data: ProgramSegment {
+                address: 0x20000,
+                content: vec![],
+            },
+            data_file_size: 0,
+            entry_address: 0x10000,
+        }
+    }
+
+    #[test]
+    fn unreachable_spans() {
+        let program = program(&[
+            0x00100513, // addi a0,zero,1
+            0x0080006f, // j 0x1000c
+            0x00200513, // addi a0,zero,2
+            0x00000073, // ecall
+        ]);
+
+        assert_eq!(program.unreachable_spans(), vec![2..3]);
+    }
+
+    #[test]
+    fn code_end() {
+        let padded = program(&[
+            0x00100513, // addi a0,zero,1
+            0x00000073, // ecall
+            0x00000000, 0x00000000,
+        ]);
+
+        assert_eq!(padded.code_end(), 2);
+        assert_eq!(padded.code_end_offset(), 8);
+        assert_eq!(program(&[0x00000073]).code_end(), 1);
+        assert_eq!(program(&[0x00000073]).code_end_offset(), 4);
+        assert_eq!(program(&[0, 0]).code_end(), 0);
+        assert_eq!(program(&[0, 0]).code_end_offset(), 0);
+
+        // c.addi a5,-8; ecall; a zero half-word
+        let mixed = program(&[0x007317e1, 0x00000000]);
+        assert_eq!(mixed.code_end(), 2);
+        assert_eq!(mixed.code_end_offset(), 6);
+    }
+
+    #[test]
+    fn uses_m_extension() {
+        assert!(program(&[
+            0x00100513, // addi a0,zero,1
+            0x02b55533, // divu a0,a0,a1
+            0x00000073, // ecall
+        ])
+        .uses_m_extension());
+
+        assert!(!program(&[
+            0x00100513, // addi a0,zero,1
+            0x00b50533, // add a0,a0,a1
+            0x00000073, // ecall
+        ])
+        .uses_m_extension());
+    }
+
+    #[test]
+    fn opcode_histogram() {
+        let histogram = program(&[
+            0x00100513, // addi a0,zero,1
+            0x00b50533, // add a0,a0,a1
+            0x00150513, // addi a0,a0,1
+            0x00000073, // ecall
+        ])
+        .opcode_histogram();
+
+        assert_eq!(
+            histogram.into_iter().collect::<Vec<_>>(),
+            vec![("add", 1), ("addi", 2), ("ecall", 1)]
+        );
+        assert!(program(&[]).opcode_histogram().is_empty());
+    }
+
+    #[test]
+    fn predecessors() {
+        let program = program(&[
+            0x00050663, // beq a0,zero,0x1000c
+            0x00100513, // addi a0,zero,1
+            0x0080006f, // j 0x10010
+            0x00200513, // addi a0,zero,2
+            0x00000073, // ecall
+        ]);
+
+        assert_eq!(
+            program.predecessors(),
+            vec![vec![], vec![0], vec![1], vec![0], vec![2, 3]]
+        );
+    }
+
+    #[test]
+    fn function_sizes() {
+        let program = program(&[
+            0x008000ef, // main: jal ra,0x10008 <f>
+            0x00000073, // ecall
+            0x00100513, // f: addi a0,zero,1
+            0x00b50533, // add a0,a0,a1
+            0x00008067, // ret
+        ]);
+
+        let mut symbols = BTreeMap::new();
+        symbols.insert(0x10000, String::from("main"));
+        symbols.insert(0x10008, String::from("f"));
+        symbols.insert(0x30000, String::from("data"));
+
+        assert_eq!(
+            program.functions(&symbols),
+            vec![
+                (String::from("main"), 0x10000..0x10008),
+                (String::from("f"), 0x10008..0x10014)
+            ]
+        );
+        assert_eq!(
+            program.function_sizes(&symbols),
+            vec![(String::from("main"), 2), (String::from("f"), 3)]
+        );
+    }
+
+    #[test]
+    fn resolved_calls() {
+        let program = program(&[
+            0x00001097, // auipc ra,0x1
+            0xff8080e7, // jalr ra,-8(ra)
+            0x008000ef, // jal ra,0x10010
+            0x00000073, // ecall
+            0x00008067, // ret
+        ]);
+
+        assert_eq!(program.resolved_calls(), vec![(1, 0x10ff8), (2, 0x10010)]);
+    }
+
+    #[test]
+    fn indirect_jump_sites() {
+        let program = program(&[
+            0x00001097, // auipc ra,0x1
+            0xff8080e7, // jalr ra,-8(ra)
+            0x00351513, // slli a0,a0,0x3
+            0x00a787b3, // add a5,a5,a0
+            0x0007b783, // ld a5,0(a5)
+            0x00078067, // jr a5
+            0x00008067, // ret
+        ]);
+
+        assert_eq!(program.indirect_jump_sites(), vec![5]);
+    }
+
+    #[test]
+    fn is_position_independent() {
+        let relative = program(&[
+            0x00000517, // auipc a0,0x0
+            0x01050513, // addi a0,a0,16
+            0x000500e7, // jalr a0
+            0x00000073, // ecall
+        ]);
+        assert!(relative.is_position_independent());
+
+        let absolute = program(&[
+            0x00010537, // lui a0,0x10
+            0x01050513, // addi a0,a0,16
+            0x000500e7, // jalr a0
+            0x00000073, // ecall
+        ]);
+        assert!(!absolute.is_position_independent());
+
+        // the absolute address is overwritten before the jump
+        let overwritten = program(&[
+            0x00010537, // lui a0,0x10
+            0x00000517, // auipc a0,0x0
+            0x000500e7, // jalr a0
+        ]);
+        assert!(overwritten.is_position_independent());
+    }
+
+    #[test]
+    fn redundant_loads() {
+        let program = program(&[
+            0xfea13c23, // sd a0,-8(sp)
+            0x00158593, // addi a1,a1,1
+            0xff813603, // ld a2,-8(sp)
+            0x00b13023, // sd a1,0(sp)
+            0x00110113, // addi sp,sp,1
+            0x00013683, // ld a3,0(sp)
+            0x00b13423, // sd a1,8(sp)
+            0x00c13423, // sd a2,8(sp)
+            0x00813703, // ld a4,8(sp)
+        ]);
+
+        assert_eq!(program.redundant_loads(), vec![2, 8]);
+    }
+
+    #[test]
+    fn max_stack_frame() {
+        let program = program(&[
+            0xff010113, // addi sp,sp,-16
+            0x00113423, // sd ra,8(sp)
+            0xfe010113, // addi sp,sp,-32
+            0x02010113, // addi sp,sp,32
+            0x00813083, // ld ra,8(sp)
+            0x01010113, // addi sp,sp,16
+            0x00008067, // ret
+            0xfc010113, // addi sp,sp,-64
+        ]);
+
+        assert_eq!(program.max_stack_frame(), Some(-48));
+
+        let mut leaf = program;
+        leaf.entry_address = 0x10018;
+        assert_eq!(leaf.max_stack_frame(), None);
+    }
+
+    #[test]
+    fn syscall_number_before() {
+        let program = program(&[
+            0x04000893, // addi a7,zero,64
+            0x00100513, // addi a0,zero,1
+            0x00400613, // addi a2,zero,4
+            0x00000073, // ecall
+            0x00050463, // beq a0,zero,0x10014
+            0x00000073, // ecall
+        ]);
+
+        assert_eq!(program.syscall_number_before(3), Some(64));
+        assert_eq!(program.syscall_number_before(2), None);
+        assert_eq!(program.syscall_number_before(5), None);
+    }
+
+    #[test]
+    fn check_call_return_balance() {
+        let mut words = vec![
+            0x00c000ef, // jal ra,0x1000c
+            0x05d00893, // addi a7,zero,93
+            0x00000073, // ecall
+            0x00150513, // addi a0,a0,1
+            0x00008067, // ret
+        ];
+
+        assert!(program(&words).check_call_return_balance());
+
+        words[4] = 0x00150513; // addi a0,a0,1
+        assert!(!program(&words).check_call_return_balance());
+    }
+
+    #[test]
+    fn coverage() {
+        let empty = program(&[]);
+        let program = program(&[
+            0x00100513, // addi a0,zero,1
+            0x00a00593, // addi a1,zero,10
+            0x00b50533, // add a0,a0,a1
+            0x00000073, // ecall
+        ]);
+
+        let executed = [0x10000, 0x10004, 0x10006, 0x20000]
+            .iter()
+            .copied()
+            .collect::<HashSet<_>>();
+
+        assert_eq!(program.coverage(&executed), 0.5);
+        assert_eq!(program.coverage(&HashSet::new()), 0.0);
+        assert_eq!(empty.coverage(&executed), 0.0);
+    }
+
+    #[test]
+    fn summary() {
+        let mut program = program(&[
+            0x00100513, // addi a0,zero,1
+            0x00a00593, // addi a1,zero,10
+            0x02b50533, // mul a0,a0,a1
+            0x05d00893, // addi a7,zero,93
+            0x00000073, // ecall
+        ]);
+        program.data.content = vec![0; 3];
+
+        assert_eq!(
+            program.summary(),
+            "instructions: 5 (0 compressed)\n\
+             code: 20 bytes at 0x10000\n\
+             data: 24 bytes at 0x20000\n\
+             top opcodes: addi 3, ecall 1, mul 1\n\
+             M extension: yes\n\
+             C extension: no\n\
+             syscalls: 1\n"
+        );
+    }
+
+    #[test]
+    fn analyses_stop_at_padding() {
+        let padded = program(&[
+            0x05d00893, // addi a7,zero,93
+            0x00000073, // ecall
+            0x00000000, 0x00000000,
+        ]);
+
+        assert_eq!(
+            padded.summary(),
+            "instructions: 2 (0 compressed)\n\
+             code: 16 bytes at 0x10000\n\
+             data: 0 bytes at 0x20000\n\
+             top opcodes: addi 1, ecall 1\n\
+             M extension: no\n\
+             C extension: no\n\
+             syscalls: 1\n"
+        );
+        assert_eq!(padded.ecall_sites(), vec![0x10004]);
+        assert_eq!(padded.predecessors(), vec![vec![], vec![0]]);
+        assert!(padded.unreachable_spans().is_empty());
+        assert_eq!(padded.syscall_number_before(1), Some(93));
+
+        // the first half of a 32-bit instruction at the end
+        let mut truncated = program(&[0x00000073]); // ecall
+        truncated.code.content.extend(&[0x13, 0x05]);
+
+        assert_eq!(truncated.ecall_sites(), vec![0x10000]);
+        assert_eq!(
+            truncated.coverage(&[0x10000].iter().copied().collect()),
+            1.0
+        );
+    }
+
+    #[test]
+    fn ecall_sites() {
+        let empty = program(&[0x00008067]); // ret
+        let program = program(&[
+            0x05d00893, // addi a7,zero,93
+            0x00000073, // ecall
+            0x00100513, // addi a0,zero,1
+            0x00014501, // c.li a0,0 and c.nop
+            0x00000073, // ecall
+        ]);
+
+        assert_eq!(program.ecall_sites(), vec![0x10004, 0x10010]);
+        assert!(empty.ecall_sites().is_empty());
+    }
+
+    #[test]
+    fn control_flow_instructions() {
+        let program = program(&[
+            0x00050663, // beq a0,zero,0x1000c
+            0x00100513, // addi a0,zero,1
+            0x0080006f, // j 0x10010
+            0x00200513, // addi a0,zero,2
+            0x00000073, // ecall
+            0x00008067, // ret
+        ]);
+
+        let addresses = program
+            .control_flow_instructions()
+            .map(|(pc, _)| pc)
+            .collect::<Vec<_>>();
+
+        assert_eq!(addresses, vec![0x10000, 0x10008, 0x10010, 0x10014]);
+    }
+
+    #[test]
+    fn guess_code_data_split() {
+        let mut blob = program(&[
+            0x00100513, // addi a0,zero,1
+            0x00b50533, // add a0,a0,a1
+            0x00000073, // ecall
+        ])
+        .code
+        .content;
+        blob.extend(&42u64.to_le_bytes());
+        blob.extend(&7u64.to_le_bytes());
+
+        assert_eq!(super::guess_code_data_split(&blob), 12);
+        assert_eq!(super::guess_code_data_split(&blob[..8]), 8);
+    }
+
+    #[test]
+    fn register_usage() {
+        let program = program(&[
+            0xff010113, // addi sp,sp,-16
+            0x00113423, // sd ra,8(sp)
+            0x00813083, // ld ra,8(sp)
+            0x01010113, // addi sp,sp,16
+            0x00008067, // ret
+        ]);
+
+        let usage = program.register_usage();
+
+        assert_eq!(usage[u32::from(Register::Sp) as usize], 6);
+        assert_eq!(usage[u32::from(Register::Ra) as usize], 3);
+        assert_eq!(usage[u32::from(Register::Zero) as usize], 1);
+        assert_eq!(usage.iter().sum::<usize>(), 10);
+    }
+
+    #[test]
+    fn tail_calls() {
+        let program = program(&[
+            0x00c000ef, // jal ra,0x1000c <f>
+            0x00000073, // ecall
+            0x00000013, // nop
+            0xffdff06f, // f: j 0x10008 (local)
+            0x0040006f, // j 0x10014 <g>
+            0x00008067, // g: ret
+        ]);
+
+        let mut symbols = BTreeMap::new();
+        symbols.insert(0x1000c, String::from("f"));
+        symbols.insert(0x10014, String::from("g"));
+
+        assert_eq!(program.tail_calls(&symbols), vec![(4, 0x10014)]);
+    }
+
+    #[test]
+    fn jump_targets_are_reachable() {
+        let program = program(&[
+            0x0080006f, // j 0x10008
+            0x00200513, // addi a0,zero,2
+            0xffdff06f, // j 0x10004
+        ]);
+
+        assert_eq!(program.unreachable_spans(), vec![]);
+    }
+}
@@ -0,0 +1,193 @@
+//! # Bit permutations of immediates
+//!
+//! RISC-V splits immediates into fields which are placed at fixed positions of an instruction,
+//! in an order that differs from the binary representation of the immediate. A permutation
+//! lists the bits of the immediate in the order they appear in the instruction, e.g. the 12
+//! bits of a B-type immediate are `[12, 10, 9, 8, 7, 6, 5, 4, 3, 2, 1, 11]`: `imm[12|10:5]` in
+//! bits 31 to 25 and `imm[4:1|11]` in bits 11 to 7.
+//!
+//! ```
+//! use riscu::bits::Permutable;
+//!
+//! // bit 0 moves to the top, bits 2 and 1 follow
+//! assert_eq!(0b110_u16.permute(&[0, 2, 1]), 0b011);
+//! assert_eq!(0b011_u16.inv_permute(&[0, 2, 1]), 0b110);
+//!
+//! // beq with an offset of -4: imm[12|10:5] is 0b1111111 and imm[4:1|11] is 0b11101
+//! let packed = ((-4_i32 as u32) & 0x1ffe).permute(&[12, 10, 9, 8, 7, 6, 5, 4, 3, 2, 1, 11]);
+//! assert_eq!(packed, 0b1111111_11101);
+//! ```
+
+pub trait Permutable {
+    /// Gather the bits listed in `perm` into a packed value, as done when scattering an
+    /// immediate into the fields of an instruction. Bit `perm[k]` of `self` becomes bit
+    /// `perm.len() - 1 - k` of the result, i.e. `perm` lists the source bits from the most
+    /// significant bit of the result down.
+    fn permute(self, perm: &[usize]) -> Self;
+
+    /// Scatter a packed value back to the bits listed in `perm`, as done when extracting an
+    /// immediate from an instruction. Bit `perm.len() - 1 - k` of `self` becomes bit `perm[k]`
+    /// of the result, undoing [`permute`](Self::permute) for the bits in `perm`.
+    fn inv_permute(self, perm: &[usize]) -> Self;
+}
+
+macro_rules! impl_permutable {
+    ($($t:ty => $bits:expr),*) => {$(
+        impl Permutable for $t {
+            fn inv_permute(self, perm: &[usize]) -> Self {
+                debug_assert!(
+                    perm.len() <= $bits,
+                    "Permutation of {} cannot exceed {} entries.",
+                    stringify!($t),
+                    $bits
+                );
+                debug_assert!(
+                    perm.iter().all(|x| x < &$bits),
+                    "Permutation indices for {} cannot exceed {}.",
+                    stringify!($t),
+                    $bits - 1
+                );
+
+                perm.iter()
+                    .rev()
+                    .enumerate()
+                    .map(|(bit, offset)| ((self >> bit) & 0b1) << offset)
+                    .sum()
+            }
+
+            fn permute(self, perm: &[usize]) -> Self {
+                debug_assert!(
+                    perm.len() <= $bits,
+                    "Permutation of {} cannot exceed {} entries.",
+                    stringify!($t),
+                    $bits
+                );
+                debug_assert!(
+                    perm.iter().all(|x| x < &$bits),
+                    "Permutation indices for {} cannot exceed {}.",
+                    stringify!($t),
+                    $bits - 1
+                );
+
+                perm.iter()
+                    .rev()
+                    .enumerate()
+                    .map(|(bit, offset)| ((self >> offset) & 0b1) << bit)
+                    .sum()
+            }
+        }
+    )*};
+}
+
+impl_permutable!(u16 => 16, u32 => 32, u64 => 64);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_rng::Rng;
+
+    /// Random permutation of `0..n` (Fisher-Yates).
+    fn permutation(rng: &mut Rng, n: usize) -> Vec<usize> {
+        let mut perm = (0..n).collect::<Vec<_>>();
+
+        for i in (1..n).rev() {
+            perm.swap(i, rng.next_u32() as usize % (i + 1));
+        }
+
+        perm
+    }
+
+    /// Random selection of 1 to `width` distinct bit indices below `width`, in random order.
+    fn partial_permutation(rng: &mut Rng, width: usize) -> Vec<usize> {
+        let mut perm = permutation(rng, width);
+        perm.truncate(1 + rng.next_u32() as usize % width);
+
+        perm
+    }
+
+    /// Pseudo-random permutations of `0..n`.
+    fn permutations(n: usize) -> impl Iterator<Item = Vec<usize>> {
+        let mut rng = Rng::new();
+
+        (0..32).map(move |_| permutation(&mut rng, n))
+    }
+
+    #[test]
+    fn round_trip() {
+        for perm in permutations(16) {
+            for x in [0, 1, 0x8000, 0xa5c3, u16::MAX].iter() {
+                assert_eq!(x.permute(&perm).inv_permute(&perm), *x);
+            }
+        }
+
+        for perm in permutations(32) {
+            for x in [0, 1, 0x8000_0000, 0xdead_beef, u32::MAX].iter() {
+                assert_eq!(x.permute(&perm).inv_permute(&perm), *x);
+            }
+        }
+
+        for perm in permutations(64) {
+            for x in [0, 1, 1 << 63, 0x0123_4567_89ab_cdef, u64::MAX].iter() {
+                assert_eq!(x.permute(&perm).inv_permute(&perm), *x);
+                assert_eq!(x.inv_permute(&perm).permute(&perm), *x);
+            }
+        }
+    }
+
+    #[test]
+    fn inverses() {
+        let mut rng = Rng::new();
+
+        for _ in 0..1000 {
+            let perm = partial_permutation(&mut rng, 16);
+            let covered = perm.iter().fold(0u16, |mask, bit| mask | 1 << bit);
+            let packed = !(u16::MAX.checked_shl(perm.len() as u32).unwrap_or(0));
+            let x = rng.next_u32() as u16;
+
+            assert_eq!(
+                x.inv_permute(&perm).permute(&perm),
+                x & packed,
+                "{:?}",
+                perm
+            );
+            assert_eq!(
+                x.permute(&perm).inv_permute(&perm),
+                x & covered,
+                "{:?}",
+                perm
+            );
+        }
+
+        for _ in 0..1000 {
+            let perm = partial_permutation(&mut rng, 32);
+            let covered = perm.iter().fold(0u32, |mask, bit| mask | 1 << bit);
+            let packed = !(u32::MAX.checked_shl(perm.len() as u32).unwrap_or(0));
+            let x = rng.next_u32();
+
+            assert_eq!(
+                x.inv_permute(&perm).permute(&perm),
+                x & packed,
+                "{:?}",
+                perm
+            );
+            assert_eq!(
+                x.permute(&perm).inv_permute(&perm),
+                x & covered,
+                "{:?}",
+                perm
+            );
+        }
+    }
+
+    #[test]
+    fn widths_agree() {
+        let perm = [12, 10, 9, 8, 7, 6, 5, 4, 3, 2, 1, 11];
+
+        for x in (0..0x2000_u16).step_by(7) {
+            let packed = x.permute(&perm);
+
+            assert_eq!(u32::from(x).permute(&perm), u32::from(packed));
+            assert_eq!(u64::from(x).permute(&perm), u64::from(packed));
+        }
+    }
+}
@@ -0,0 +1,133 @@
+//! # Construct RISC-U instructions
+//!
+//! Only the 14 instructions of RISC-U can be built with this module, so tools targeting selfie
+//! can not emit instructions outside of the subset by accident. Like the `Instruction::new_*`
+//! constructors, these functions panic if an immediate does not fit into its encoding.
+
+use crate::{Instruction, Register};
+
+/// `lui rd, imm`, where `imm` is the 20-bit upper immediate.
+pub fn lui(rd: Register, imm: i32) -> Instruction {
+    Instruction::new_lui(rd, imm)
+}
+
+/// `addi rd, rs1, imm`
+pub fn addi(rd: Register, rs1: Register, imm: i32) -> Instruction {
+    Instruction::new_addi(rd, rs1, imm)
+}
+
+/// `ld rd, imm(rs1)`
+pub fn ld(rd: Register, rs1: Register, imm: i32) -> Instruction {
+    Instruction::new_ld(rd, rs1, imm)
+}
+
+/// `sd rs2, imm(rs1)`
+pub fn sd(rs1: Register, rs2: Register, imm: i32) -> Instruction {
+    Instruction::new_sd(rs1, rs2, imm)
+}
+
+/// `add rd, rs1, rs2`
+pub fn add(rd: Register, rs1: Register, rs2: Register) -> Instruction {
+    Instruction::new_add(rd, rs1, rs2)
+}
+
+/// `sub rd, rs1, rs2`
+pub fn sub(rd: Register, rs1: Register, rs2: Register) -> Instruction {
+    Instruction::new_sub(rd, rs1, rs2)
+}
+
+/// `mul rd, rs1, rs2`
+pub fn mul(rd: Register, rs1: Register, rs2: Register) -> Instruction {
+    Instruction::new_mul(rd, rs1, rs2)
+}
+
+/// `divu rd, rs1, rs2`
+pub fn divu(rd: Register, rs1: Register, rs2: Register) -> Instruction {
+    Instruction::new_divu(rd, rs1, rs2)
+}
+
+/// `remu rd, rs1, rs2`
+pub fn remu(rd: Register, rs1: Register, rs2: Register) -> Instruction {
+    Instruction::new_remu(rd, rs1, rs2)
+}
+
+/// `sltu rd, rs1, rs2`
+pub fn sltu(rd: Register, rs1: Register, rs2: Register) -> Instruction {
+    Instruction::new_sltu(rd, rs1, rs2)
+}
+
+/// `beq rs1, rs2, imm`, where `imm` is the offset to the branch target.
+pub fn beq(rs1: Register, rs2: Register, imm: i32) -> Instruction {
+    Instruction::new_beq(rs1, rs2, imm)
+}
+
+/// `jal rd, imm`, where `imm` is the offset to the jump target.
+pub fn jal(rd: Register, imm: i32) -> Instruction {
+    Instruction::new_jal(rd, imm)
+}
+
+/// `jalr rd, imm(rs1)`
+pub fn jalr(rd: Register, rs1: Register, imm: i32) -> Instruction {
+    Instruction::new_jalr(rd, rs1, imm)
+}
+
+/// `ecall`
+pub fn ecall() -> Instruction {
+    Instruction::new_ecall()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decode;
+    use Register::*;
+
+    #[test]
+    fn riscu_subset() {
+        let instructions = [
+            lui(A0, 0x1),
+            addi(Sp, Sp, -8),
+            ld(A0, Sp, 8),
+            sd(Sp, A0, -8),
+            add(A0, A1, A2),
+            sub(A0, A1, A2),
+            mul(A0, A1, A2),
+            divu(A0, A1, A2),
+            remu(A0, A1, A2),
+            sltu(A0, A1, A2),
+            beq(A0, Zero, -12),
+            jal(Ra, 8),
+            jalr(Zero, Ra, 0),
+            ecall(),
+        ];
+
+        let mnemonics = instructions
+            .iter()
+            .map(|i| i.mnemonic())
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            mnemonics,
+            [
+                "lui", "addi", "ld", "sd", "add", "sub", "mul", "divu", "remu", "sltu", "beq",
+                "jal", "jalr", "ecall"
+            ]
+        );
+        assert!(instructions.iter().all(|i| i.is_riscu()));
+        assert!(instructions
+            .iter()
+            .all(|i| decode(u32::from(*i)).unwrap() == *i));
+    }
+
+    #[test]
+    fn operands() {
+        // add a0,a1,a2
+        assert_eq!(u32::from(add(A0, A1, A2)), 0x00c58533);
+        // sd a0,-8(sp)
+        assert_eq!(u32::from(sd(Sp, A0, -8)), 0xfea13c23);
+        // lui a5,0xfffff
+        assert_eq!(u32::from(lui(A5, -1)), 0xfffff7b7);
+        // beq a0,zero,-12
+        assert_eq!(u32::from(beq(A0, Zero, -12)), 0xfe050ae3);
+    }
+}
@@ -14,44 +14,249 @@
 
 // This module was modified by the Selfie authors.
 
+mod visitor;
+
 use crate::decompress::*;
-use crate::{types::*, Instruction};
+use crate::{types::*, Instruction, Register};
+use alloc::vec::Vec;
+use core::{fmt, marker::PhantomData};
 use log::trace;
-use thiserror::Error;
+use visitor::Builder;
+
+pub use visitor::InstructionVisitor;
 
 pub const INSTRUCTION_SIZE: usize = 4;
 pub const WORD_SIZE: usize = 8;
 
-#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Error)]
+/// Reasons for an instruction to be rejected by [`decode`].
+///
+/// Errors caused by the encoding of an instruction carry its bits, the half-word for
+/// compressed instructions.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum DecodingError {
     /// Instruction's opcode is reserved for custom extensions and thus can't be decoded further.
-    #[error(
-        "Instruction's opcode is reserved for custom extensions and thus can't be decoded further"
-    )]
-    Custom,
+    Custom(u32),
 
     /// Instruction's opcode is reserved for future standard extensions.
-    #[error("Instruction's opcode is reserved for future standard extensions")]
-    Reserved,
+    Reserved(u32),
 
     /// Instruction bit pattern not defined in current specification.
-    #[error("Instruction bit pattern not defined in current specification")]
-    Unknown,
+    Unknown(u32),
 
     /// More bits from the instruction are required to fully decode it.
-    #[error("More bits from the instruction are required to fully decode it")]
     Truncated,
 
-    /// Instruction type is well defined but is not part of RISC-U
-    #[error("Instruction type is well defined but is not part of RISC-U")]
-    Unimplemented,
+    /// Instruction type is well defined but decoding it is not implemented yet
+    Unimplemented(u32),
+
+    /// Instruction type is well defined but deliberately not supported, e.g. floating point or
+    /// instructions outside of RISC-U when decoding with [`DecodingOptions::riscu_only`]
+    Unsupported(u32),
 
     /// Instruction is illegal
-    #[error("Instruction is illegal")]
-    Illegal,
+    Illegal(u32),
+
+    /// Instruction performs a computation whose result is discarded by writing it to `zero`
+    SuspiciousZeroWrite(u32),
 }
 
+impl DecodingError {
+    /// A stable numeric code for this error, e.g. to be used as the exit status of a tool.
+    pub fn code(&self) -> u8 {
+        match self {
+            DecodingError::Illegal(_) => 1,
+            DecodingError::Reserved(_) => 2,
+            DecodingError::Unimplemented(_) => 3,
+            DecodingError::Custom(_) => 4,
+            DecodingError::Unknown(_) => 5,
+            DecodingError::Truncated => 6,
+            DecodingError::SuspiciousZeroWrite(_) => 7,
+            DecodingError::Unsupported(_) => 8,
+        }
+    }
+
+    /// The bits of the rejected instruction, `None` if the error is not caused by its encoding.
+    pub fn bits(&self) -> Option<u32> {
+        match *self {
+            DecodingError::Illegal(bits)
+            | DecodingError::Reserved(bits)
+            | DecodingError::Unimplemented(bits)
+            | DecodingError::Unsupported(bits)
+            | DecodingError::Custom(bits)
+            | DecodingError::Unknown(bits)
+            | DecodingError::SuspiciousZeroWrite(bits) => Some(bits),
+            DecodingError::Truncated => None,
+        }
+    }
+}
+
+impl fmt::Display for DecodingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodingError::Custom(bits) => write!(
+                f,
+                "Instruction {:#x} has an opcode reserved for custom extensions and thus can't be decoded further",
+                bits
+            ),
+            DecodingError::Reserved(bits) => write!(
+                f,
+                "Instruction {:#x} has an opcode reserved for future standard extensions",
+                bits
+            ),
+            DecodingError::Unknown(bits) => write!(
+                f,
+                "Instruction bit pattern {:#x} not defined in current specification",
+                bits
+            ),
+            DecodingError::Truncated => write!(
+                f,
+                "More bits from the instruction are required to fully decode it"
+            ),
+            DecodingError::Unimplemented(bits) => write!(
+                f,
+                "Instruction {:#x} is well defined but not implemented yet",
+                bits
+            ),
+            DecodingError::Unsupported(bits) => write!(
+                f,
+                "Instruction {:#x} is well defined but not supported",
+                bits
+            ),
+            DecodingError::Illegal(bits) => write!(f, "Instruction {:#x} is illegal", bits),
+            DecodingError::SuspiciousZeroWrite(bits) => write!(
+                f,
+                "Instruction {:#x} writes the result of a computation to the zero register",
+                bits
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for DecodingError {}
+
+/// Deviations of an instruction which [`decode_with_warnings`] repaired instead of rejecting it.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum DecodingWarning {
+    /// The shift amount of a word shift does not fit into 5 bits and was clamped to 31.
+    ClampedShamt(u32),
+}
+
+impl fmt::Display for DecodingWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodingWarning::ClampedShamt(shamt) => {
+                write!(f, "shift amount {} of word shift clamped to 31", shamt)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for DecodingWarning {}
+
 type DecodingResult = Result<Instruction, DecodingError>;
+type VisitResult<V> = Result<<V as InstructionVisitor>::Output, DecodingError>;
+
+/// Options to adjust how [`decode_with_options`] interprets instructions.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct DecodingOptions {
+    /// Decode HINTs (computational instructions with `rd` = `zero`, other than the canonical
+    /// nop) as [`Instruction::Hint`] instead of the operation they are architecturally
+    /// equivalent to.
+    pub preserve_hints: bool,
+
+    /// Reject computational instructions with `rd` = `zero`, other than the canonical nop, with
+    /// [`DecodingError::SuspiciousZeroWrite`]. Takes precedence over `preserve_hints`.
+    pub reject_zero_writes: bool,
+
+    /// Reject compressed instructions and instructions outside of RISC-U with
+    /// [`DecodingError::Unsupported`].
+    pub riscu_only: bool,
+
+    /// Decode R-type instructions of OP and OP-32 with an unknown combination of funct7 and
+    /// funct3, e.g. custom ALU operations, as [`Instruction::UnknownRType`] instead of rejecting
+    /// them with [`DecodingError::Unknown`], so their operands remain available.
+    pub preserve_unknown_rtypes: bool,
+
+    /// Clamp the shift amount of word shifts by 32 or more, the only immediate whose field can
+    /// hold values out of its range, to 31 instead of rejecting the instruction with
+    /// [`DecodingError::Illegal`]. The clamped instruction is re-encoded accordingly.
+    pub clamp_word_shifts: bool,
+}
+
+/// How closely binaries and instructions have to follow RISC-U and the conventions of selfie.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Strictness {
+    /// Enforce every check, like [`Strictness::Riscu`], but additionally reject suspicious
+    /// writes to `zero` and binaries with other loadable segments than code and data.
+    Strict,
+    /// Enforce the conventions of selfie: RISC-U instructions only, a read-only executable code
+    /// segment and a writable data segment.
+    Riscu,
+    /// Accept general RISC-V, e.g. compressed instructions, a writable code segment or a single
+    /// segment holding both code and data.
+    Lenient,
+}
+
+impl From<Strictness> for DecodingOptions {
+    fn from(strictness: Strictness) -> Self {
+        DecodingOptions {
+            preserve_hints: false,
+            reject_zero_writes: strictness == Strictness::Strict,
+            riscu_only: strictness != Strictness::Lenient,
+            preserve_unknown_rtypes: strictness == Strictness::Lenient,
+            clamp_word_shifts: strictness == Strictness::Lenient,
+        }
+    }
+}
+
+/// Instruction set extensions accepted by [`decode_with_profile`].
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum IsaProfile {
+    /// The base integer instructions only.
+    Rv64i,
+    /// The base integer instructions and multiplication and division (M).
+    Rv64im,
+    /// The base integer instructions, multiplication and division (M) and compressed
+    /// instructions (C).
+    Rv64imc,
+}
+
+/// The width of an instruction's encoding.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum InstructionWidth {
+    /// 16-bit instruction from the C extension.
+    Compressed,
+    /// Regular 32-bit instruction.
+    Full,
+}
+
+impl InstructionWidth {
+    /// Size of the encoding in bytes.
+    pub fn bytes(&self) -> usize {
+        match self {
+            InstructionWidth::Compressed => 2,
+            InstructionWidth::Full => INSTRUCTION_SIZE,
+        }
+    }
+
+    /// Whether the instruction was encoded in 16 bits, i.e. the PC advances by 2 past it.
+    pub fn is_compressed(&self) -> bool {
+        *self == InstructionWidth::Compressed
+    }
+}
+
+/// An instruction decoded at a known address, with the absolute target of branches and `jal`
+/// already resolved.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct LocatedInstruction {
+    pub pc: u64,
+    pub instruction: Instruction,
+    /// Absolute target of a branch or `jal`, `None` for all other instructions.
+    pub target: Option<u64>,
+}
 
 /// Return the length (in bytes) of an instruction given the low 16 bits of it.
 ///
@@ -73,7 +278,28 @@ pub fn instruction_length(i: u16) -> usize {
 }
 
 /// Decode the given instruction.
+///
+/// The all-zeros and the all-ones word are the canonical illegal instructions and always decode
+/// to [`DecodingError::Illegal`], as guard words or traps rely on.
 pub fn decode(i: u32) -> DecodingResult {
+    let i = expand(i)?;
+
+    visit(i, &mut Builder(i))
+}
+
+/// Decode the given instruction and call the method of `visitor` for it with its fields, e.g.
+/// to dispatch in an interpreter without constructing an [`Instruction`] first.
+///
+/// Compressed instructions are visited as the 32-bit instruction they expand to. Words which are
+/// rejected by [`decode`] are rejected with the same error and the visitor is not called.
+pub fn decode_with<V: InstructionVisitor>(i: u32, visitor: &mut V) -> VisitResult<V> {
+    visit(expand(i)?, visitor)
+}
+
+/// The 32-bit instruction of `i`, i.e. `i` itself or the expansion of a compressed instruction
+/// in its lower half.
+#[inline(always)]
+fn expand(i: u32) -> Result<u32, DecodingError> {
     trace!(
         "opcode: {:#09b}, funct3: {:#05b}, funct7: {:#09b}; full instr.: {:#034b}",
         i & 0b1111111,
@@ -82,209 +308,561 @@ pub fn decode(i: u32) -> DecodingResult {
         i
     );
 
+    if i == 0xffff_ffff {
+        return Err(DecodingError::Illegal(i));
+    }
+
     match i & 0b11 {
-        0b11 => match (i >> 2) & 0b11111 {
-            0b00000 => decode_load(i),
-            0b00001 => Err(DecodingError::Unimplemented), // Load-FP
-            0b00010 => Err(DecodingError::Custom),
-            0b00011 => decode_fence(i), // misc mem instruction
-            0b00100 => decode_op_imm(i),
-            0b00101 => Ok(Instruction::Auipc(UType(i))), // AUIPC
-            0b00110 => decode_op_imm32(i),               // op imm32 instruction
-            0b00111 => Err(DecodingError::Reserved),     // 48bit instruction
-
-            0b01000 => decode_store(i),
-            0b01001 => Err(DecodingError::Unimplemented), // Store-FP
-            0b01010 => Err(DecodingError::Custom),
-            0b01011 => decode_amo(i),
-            0b01100 => decode_op(i),
-            0b01101 => Ok(Instruction::Lui(UType(i))), // LUI
-            0b01110 => decode_op32(i),                 // op32 instruction
-            0b01111 => Err(DecodingError::Reserved),   // 64bit instruction
-
-            0b10000 => Err(DecodingError::Unimplemented), // MADD
-            0b10001 => Err(DecodingError::Unimplemented), // MSUB
-            0b10010 => Err(DecodingError::Unimplemented), // NMSUB
-            0b10011 => Err(DecodingError::Unimplemented), // NMADD
-            0b10100 => Err(DecodingError::Unimplemented), // OP-FP
-            0b10101 => Err(DecodingError::Reserved),
-            0b10110 => Err(DecodingError::Custom),
-            0b10111 => Err(DecodingError::Reserved), // 48bit instruction
-
-            0b11000 => decode_branch(i),
-            0b11001 => Ok(Instruction::Jalr(IType(i))),
-            0b11010 => Err(DecodingError::Reserved),
-            0b11011 => Ok(Instruction::Jal(JType(i))),
-            0b11100 => decode_system(i),
-            0b11101 => Err(DecodingError::Reserved),
-            0b11110 => Err(DecodingError::Custom),
-            0b11111 => Err(DecodingError::Reserved), // >= 80bit instruction
-            _ => unreachable!(),
-        },
-        0b00 => decompress_q0((i & 0xffff) as u16).and_then(decode),
-        0b01 => decompress_q1((i & 0xffff) as u16).and_then(decode),
-        0b10 => decompress_q2((i & 0xffff) as u16).and_then(decode),
-        _ => unreachable!(),
+        0b11 => Ok(i),
+        _ => decompress((i & 0xffff) as u16),
+    }
+}
+
+/// Decode the 32-bit instruction `i` into `v`.
+#[inline(always)]
+fn visit<V: InstructionVisitor>(i: u32, v: &mut V) -> VisitResult<V> {
+    Dispatch::<V>::TABLE[(i & 0b111_1111) as usize](i, v)
+}
+
+/// Decoder of the 32-bit instructions of one major opcode.
+type Handler<V> = fn(u32, &mut V) -> VisitResult<V>;
+
+struct Dispatch<V>(PhantomData<V>);
+
+impl<V: InstructionVisitor> Dispatch<V> {
+    /// Decoders indexed by the 7-bit opcode, such that `visit` dispatches with a single lookup.
+    /// Opcodes whose lowest two bits are not `0b11` belong to compressed instructions, which are
+    /// expanded before and thus never looked up.
+    const TABLE: [Handler<V>; 128] = {
+        let mut table = [illegal as Handler<V>; 128];
+
+        table[0b000_0011] = decode_load;
+        table[0b000_0111] = unsupported; // Load-FP
+        table[0b000_1011] = custom;
+        table[0b000_1111] = decode_fence; // misc mem instruction
+        table[0b001_0011] = decode_op_imm;
+        table[0b001_0111] = decode_auipc;
+        table[0b001_1011] = decode_op_imm32; // op imm32 instruction
+        table[0b001_1111] = reserved; // 48bit instruction
+
+        table[0b010_0011] = decode_store;
+        table[0b010_0111] = unsupported; // Store-FP
+        table[0b010_1011] = custom;
+        table[0b010_1111] = decode_amo;
+        table[0b011_0011] = decode_op;
+        table[0b011_0111] = decode_lui;
+        table[0b011_1011] = decode_op32; // op32 instruction
+        table[0b011_1111] = reserved; // 64bit instruction
+
+        table[0b100_0011] = unsupported; // MADD
+        table[0b100_0111] = unsupported; // MSUB
+        table[0b100_1011] = unsupported; // NMSUB
+        table[0b100_1111] = unsupported; // NMADD
+        table[0b101_0011] = unsupported; // OP-FP
+        table[0b101_0111] = reserved;
+        table[0b101_1011] = custom;
+        table[0b101_1111] = reserved; // 48bit instruction
+
+        table[0b110_0011] = decode_branch;
+        table[0b110_0111] = decode_jalr;
+        table[0b110_1011] = reserved;
+        table[0b110_1111] = decode_jal;
+        table[0b111_0011] = decode_system;
+        table[0b111_0111] = reserved;
+        table[0b111_1011] = custom;
+        table[0b111_1111] = reserved; // >= 80bit instruction
+
+        table
+    };
+}
+
+fn illegal<V: InstructionVisitor>(i: u32, _: &mut V) -> VisitResult<V> {
+    Err(DecodingError::Illegal(i))
+}
+
+fn unsupported<V: InstructionVisitor>(i: u32, _: &mut V) -> VisitResult<V> {
+    Err(DecodingError::Unsupported(i))
+}
+
+fn custom<V: InstructionVisitor>(i: u32, _: &mut V) -> VisitResult<V> {
+    Err(DecodingError::Custom(i))
+}
+
+fn reserved<V: InstructionVisitor>(i: u32, _: &mut V) -> VisitResult<V> {
+    Err(DecodingError::Reserved(i))
+}
+
+/// Decode every word of `words`, see [`decode`].
+pub fn decode_all(words: &[u32]) -> Vec<DecodingResult> {
+    let mut decoded = Vec::with_capacity(words.len());
+
+    decode_all_into(words, &mut decoded);
+
+    decoded
+}
+
+/// Decode every word of `words` into `out`, which is cleared first, such that its allocation can
+/// be reused across calls.
+pub fn decode_all_into(words: &[u32], out: &mut Vec<DecodingResult>) {
+    out.clear();
+    out.extend(words.iter().map(|word| decode(*word)));
+}
+
+/// Decode the given instruction and return it together with its original encoding.
+///
+/// This is meant for bulk verification loops, which check that re-encoding a decoded
+/// instruction with `u32::from` reproduces the input.
+pub fn decode_with_raw(i: u32) -> Result<(Instruction, u32), DecodingError> {
+    decode(i).map(|instruction| (instruction, i))
+}
+
+/// Decode the instruction starting with the half-word `lo`, as fetched from memory two bytes
+/// at a time.
+///
+/// A compressed instruction is decoded from `lo` alone and `hi` is ignored. Otherwise `hi`
+/// holds the upper half of a 32-bit instruction.
+pub fn decode_halfwords(
+    lo: u16,
+    hi: u16,
+) -> Result<(Instruction, InstructionWidth), DecodingError> {
+    if instruction_length(lo) == 2 {
+        decode(u32::from(lo)).map(|i| (i, InstructionWidth::Compressed))
+    } else {
+        decode(u32::from(lo) | (u32::from(hi) << 16)).map(|i| (i, InstructionWidth::Full))
     }
 }
 
+/// Decode `bytes` as consecutive little-endian 32-bit words, e.g. a buffer of generated code,
+/// yielding every instruction or error together with its byte offset into `bytes`.
+///
+/// Every item stands for one word, so the offset of an error is 4 times its position in the
+/// stream. A trailing partial word of less than 4 bytes ends the stream. Since every word is decoded on
+/// its own, compressed instructions are only recognized in the lower half of a word.
+pub fn decode_stream(
+    bytes: &[u8],
+) -> impl Iterator<Item = Result<(u64, Instruction), DecodingError>> + '_ {
+    bytes
+        .chunks_exact(INSTRUCTION_SIZE)
+        .enumerate()
+        .map(|(idx, word)| {
+            let offset = (idx * INSTRUCTION_SIZE) as u64;
+
+            decode(u32::from_le_bytes([word[0], word[1], word[2], word[3]])).map(|i| (offset, i))
+        })
+}
+
+/// Decode the instruction at the start of `bytes` and return it together with the number of
+/// bytes it occupies, 2 for compressed and 4 for 32-bit instructions.
+///
+/// The width is told by the low two bits of the first half-word. If `bytes` is shorter than
+/// that, [`DecodingError::Truncated`] is returned.
+pub fn decode_one(bytes: &[u8]) -> Result<(Instruction, usize), DecodingError> {
+    let (lo, hi) = match *bytes {
+        [b0, b1, ..] if b0 & 0b11 != 0b11 => (u16::from_le_bytes([b0, b1]), 0),
+        [b0, b1, b2, b3, ..] => (u16::from_le_bytes([b0, b1]), u16::from_le_bytes([b2, b3])),
+        _ => return Err(DecodingError::Truncated),
+    };
+
+    decode_halfwords(lo, hi).map(|(i, width)| (i, width.bytes()))
+}
+
+/// Decode `bytes` as a mix of compressed and 32-bit instructions, as emitted for the C
+/// extension, yielding the byte offset into `bytes` of every instruction together with the
+/// instruction and its width or the error of decoding it.
+///
+/// A half-word whose low two bits are `0b11` starts a 32-bit instruction, every other half-word
+/// is a compressed instruction. Errors do not end the stream, except for an instruction which is
+/// cut off by the end of `bytes`, which is reported as [`DecodingError::Truncated`].
+pub fn decode_mixed(
+    bytes: &[u8],
+) -> impl Iterator<Item = (u64, Result<(Instruction, InstructionWidth), DecodingError>)> + '_ {
+    let mut offset = 0;
+
+    core::iter::from_fn(move || {
+        let start = offset as u64;
+        let rest = bytes.get(offset..).filter(|rest| !rest.is_empty())?;
+
+        let result = decode_one(rest);
+
+        offset = match result {
+            Err(DecodingError::Truncated) => bytes.len(),
+            _ if rest[0] & 0b11 != 0b11 => offset + 2,
+            _ => offset + INSTRUCTION_SIZE,
+        };
+
+        Some((start, result.map(|(i, len)| (i, width_of(len)))))
+    })
+}
+
+/// Decode `bytes` by a linear sweep like [`decode_mixed`], but resynchronize after an error by
+/// advancing a single half-word, the smallest instruction width, instead of skipping the width of
+/// the rejected encoding.
+///
+/// This keeps a listing going through data embedded into the code, e.g. jump tables or strings,
+/// and picks up the next instruction which starts at any half-word after them. Every item
+/// advances by at least 2 bytes, so the sweep always ends after at most `bytes.len() / 2 + 1`
+/// items.
+pub fn decode_resync(
+    bytes: &[u8],
+) -> impl Iterator<Item = (u64, Result<Instruction, DecodingError>)> + '_ {
+    let mut offset = 0;
+
+    core::iter::from_fn(move || {
+        let start = offset as u64;
+        let rest = bytes.get(offset..).filter(|rest| !rest.is_empty())?;
+
+        let result = decode_one(rest);
+
+        offset += match result {
+            Ok((_, len)) => len,
+            Err(_) => 2,
+        };
+
+        Some((start, result.map(|(i, _)| i)))
+    })
+}
+
+fn width_of(len: usize) -> InstructionWidth {
+    if len == 2 {
+        InstructionWidth::Compressed
+    } else {
+        InstructionWidth::Full
+    }
+}
+
+/// Whether the word is a valid encoding of one of the RISC-U instructions.
+pub fn is_valid_riscu_word(i: u32) -> bool {
+    i & 0b11 == 0b11 && decode(i).map_or(false, |instruction| instruction.is_riscu())
+}
+
+/// Decode the given instruction located at `pc` and resolve its target.
+pub fn decode_at_pc(i: u32, pc: u64) -> Result<LocatedInstruction, DecodingError> {
+    let instruction = decode(i)?;
+
+    Ok(LocatedInstruction {
+        pc,
+        instruction,
+        target: instruction.branch_target(pc),
+    })
+}
+
+/// Decode the given instruction, giving `handler` a chance to decode words which are not
+/// recognized by the core decoder, e.g. instructions of custom extensions. The original error
+/// is returned if the handler does not recognize the word either.
+pub fn decode_with_handler<F>(i: u32, handler: F) -> DecodingResult
+where
+    F: Fn(u32) -> Option<Instruction>,
+{
+    decode(i).or_else(|error| handler(i).ok_or(error))
+}
+
+/// Decode the given instruction with non-default [`DecodingOptions`].
+pub fn decode_with_options(i: u32, options: &DecodingOptions) -> DecodingResult {
+    decode_with_warnings(i, options, &mut Vec::new())
+}
+
+/// Decode the given instruction with non-default [`DecodingOptions`], reporting every repair of
+/// the instruction, e.g. a clamped immediate, in `warnings`.
+pub fn decode_with_warnings(
+    i: u32,
+    options: &DecodingOptions,
+    warnings: &mut Vec<DecodingWarning>,
+) -> DecodingResult {
+    let instruction = match decode(i) {
+        Err(DecodingError::Unknown(_)) if options.preserve_unknown_rtypes && is_rtype_op(i) => {
+            Instruction::UnknownRType(RType(i))
+        }
+        Err(DecodingError::Illegal(_))
+            if options.clamp_word_shifts && is_oversized_word_shift(i) =>
+        {
+            warnings.push(DecodingWarning::ClampedShamt((i >> 20) & 0b11_1111));
+            decode((i & !(0b11_1111 << 20)) | (0b1_1111 << 20))?
+        }
+        result => result?,
+    };
+
+    let compressed = instruction_length(i as u16) == 2;
+    let bits = encoding_bits(i);
+
+    if options.riscu_only && (compressed || !instruction.is_riscu()) {
+        Err(DecodingError::Unsupported(bits))
+    } else if options.reject_zero_writes && is_hint(instruction) {
+        Err(DecodingError::SuspiciousZeroWrite(bits))
+    } else if options.preserve_hints && is_hint(instruction) {
+        Ok(Instruction::Hint(bits))
+    } else {
+        Ok(instruction)
+    }
+}
+
+/// Decode the given instruction and reject it with [`DecodingError::Illegal`] if it is part of
+/// an extension which is not included in `profile`. None of the profiles includes atomics (A).
+pub fn decode_with_profile(i: u32, profile: IsaProfile) -> DecodingResult {
+    let instruction = decode(i)?;
+
+    let compressed = instruction_length(i as u16) == 2;
+
+    let accepted = match profile {
+        IsaProfile::Rv64i => !compressed && !instruction.is_m_extension(),
+        IsaProfile::Rv64im => !compressed,
+        IsaProfile::Rv64imc => true,
+    };
+
+    if accepted && !instruction.is_atomic() {
+        Ok(instruction)
+    } else {
+        Err(DecodingError::Illegal(encoding_bits(i)))
+    }
+}
+
+/// The bits of the instruction `i` starts with, the half-word for compressed instructions, as
+/// carried by [`DecodingError`].
+fn encoding_bits(i: u32) -> u32 {
+    if instruction_length(i as u16) == 2 {
+        i & 0xffff
+    } else {
+        i
+    }
+}
+
+/// Whether the opcode of the word is OP or OP-32.
+fn is_rtype_op(i: u32) -> bool {
+    matches!(i & 0b111_1111, 0b011_0011 | 0b011_1011)
+}
+
+/// Whether the word is a word shift of OP-IMM-32 whose shamt has the reserved bit 5 set.
+fn is_oversized_word_shift(i: u32) -> bool {
+    let funct7 = (i >> 25) & !0b1;
+    let shamt5 = (i >> 25) & 0b1;
+
+    i & 0b111_1111 == 0b001_1011
+        && shamt5 == 1
+        && match (i >> 12) & 0b111 {
+            0b001 => funct7 == 0b000_0000,
+            0b101 => funct7 == 0b000_0000 || funct7 == 0b010_0000,
+            _ => false,
+        }
+}
+
+fn is_hint(instruction: Instruction) -> bool {
+    use Instruction::*;
+
+    let rd = match instruction {
+        Lui(u) | Auipc(u) => u.rd(),
+        Addi(i) | Slti(i) | Sltiu(i) | Xori(i) | Ori(i) | Andi(i) | Slli(i) | Srli(i) | Srai(i)
+        | Addiw(i) | Slliw(i) | Srliw(i) | Sraiw(i) => i.rd(),
+        Add(r) | Sub(r) | Sll(r) | Slt(r) | Sltu(r) | Xor(r) | Srl(r) | Sra(r) | Or(r) | And(r)
+        | Addw(r) | Subw(r) | Sllw(r) | Srlw(r) | Sraw(r) => r.rd(),
+        _ => return false,
+    };
+
+    rd == Register::Zero && instruction != Instruction::new_nop()
+}
+
+#[inline(always)]
+fn rd(i: u32) -> Register {
+    Register::from((i >> 7) & 0b1_1111)
+}
+
+#[inline(always)]
+fn rs1(i: u32) -> Register {
+    Register::from((i >> 15) & 0b1_1111)
+}
+
+#[inline(always)]
+fn rs2(i: u32) -> Register {
+    Register::from((i >> 20) & 0b1_1111)
+}
+
+/// The shift amount of a shift by an immediate, which is 6 bits wide for 64-bit shifts.
+#[inline(always)]
+fn shamt(i: u32) -> i32 {
+    ((i >> 20) & 0b11_1111) as i32
+}
+
+#[inline(always)]
+fn decode_auipc<V: InstructionVisitor>(i: u32, v: &mut V) -> VisitResult<V> {
+    Ok(v.auipc(rd(i), UType(i).imm() as i32))
+}
+
+#[inline(always)]
+fn decode_lui<V: InstructionVisitor>(i: u32, v: &mut V) -> VisitResult<V> {
+    Ok(v.lui(rd(i), UType(i).imm() as i32))
+}
+
+#[inline(always)]
+fn decode_jalr<V: InstructionVisitor>(i: u32, v: &mut V) -> VisitResult<V> {
+    Ok(v.jalr(rd(i), rs1(i), IType(i).imm()))
+}
+
+#[inline(always)]
+fn decode_jal<V: InstructionVisitor>(i: u32, v: &mut V) -> VisitResult<V> {
+    Ok(v.jal(rd(i), JType(i).imm()))
+}
+
 #[inline(always)]
-fn decode_load(i: u32) -> DecodingResult {
+fn decode_load<V: InstructionVisitor>(i: u32, v: &mut V) -> VisitResult<V> {
     match (i >> 12) & 0b111 {
-        0b000 => Ok(Instruction::Lb(IType(i))),
-        0b001 => Ok(Instruction::Lh(IType(i))),
-        0b010 => Ok(Instruction::Lw(IType(i))),
-        0b011 => Ok(Instruction::Ld(IType(i))),
-        0b100 => Ok(Instruction::Lbu(IType(i))),
-        0b101 => Ok(Instruction::Lhu(IType(i))),
-        0b110 => Ok(Instruction::Lwu(IType(i))),
-        0b111 => Err(DecodingError::Reserved),
-        _ => Err(DecodingError::Unknown),
+        0b000 => Ok(v.lb(rd(i), rs1(i), IType(i).imm())),
+        0b001 => Ok(v.lh(rd(i), rs1(i), IType(i).imm())),
+        0b010 => Ok(v.lw(rd(i), rs1(i), IType(i).imm())),
+        0b011 => Ok(v.ld(rd(i), rs1(i), IType(i).imm())),
+        0b100 => Ok(v.lbu(rd(i), rs1(i), IType(i).imm())),
+        0b101 => Ok(v.lhu(rd(i), rs1(i), IType(i).imm())),
+        0b110 => Ok(v.lwu(rd(i), rs1(i), IType(i).imm())),
+        0b111 => Err(DecodingError::Reserved(i)),
+        _ => Err(DecodingError::Unknown(i)),
     }
 }
 
 #[inline(always)]
-fn decode_op_imm(i: u32) -> DecodingResult {
+fn decode_op_imm<V: InstructionVisitor>(i: u32, v: &mut V) -> VisitResult<V> {
     match (i >> 26, (i >> 12) & 0b111) {
-        (_, 0b000) => Ok(Instruction::Addi(IType(i))),
-        (_, 0b010) => Ok(Instruction::Slti(IType(i))),
-        (_, 0b011) => Ok(Instruction::Sltiu(IType(i))),
-        (_, 0b100) => Ok(Instruction::Xori(IType(i))),
-        (_, 0b110) => Ok(Instruction::Ori(IType(i))),
-        (_, 0b111) => Ok(Instruction::Andi(IType(i))),
-        (0b000000, 0b001) => Ok(Instruction::Slli(IType(i))),
-        (0b000000, 0b101) => Ok(Instruction::Srli(IType(i))),
-        (0b010000, 0b101) => Ok(Instruction::Srai(IType(i))),
-        _ => Err(DecodingError::Unknown),
+        (_, 0b000) => Ok(v.addi(rd(i), rs1(i), IType(i).imm())),
+        (_, 0b010) => Ok(v.slti(rd(i), rs1(i), IType(i).imm())),
+        (_, 0b011) => Ok(v.sltiu(rd(i), rs1(i), IType(i).imm())),
+        (_, 0b100) => Ok(v.xori(rd(i), rs1(i), IType(i).imm())),
+        (_, 0b110) => Ok(v.ori(rd(i), rs1(i), IType(i).imm())),
+        (_, 0b111) => Ok(v.andi(rd(i), rs1(i), IType(i).imm())),
+        (0b000000, 0b001) => Ok(v.slli(rd(i), rs1(i), shamt(i))),
+        (0b000000, 0b101) => Ok(v.srli(rd(i), rs1(i), shamt(i))),
+        (0b010000, 0b101) => Ok(v.srai(rd(i), rs1(i), shamt(i))),
+        _ => Err(DecodingError::Unknown(i)),
     }
 }
 
 #[inline(always)]
-fn decode_op_imm32(i: u32) -> DecodingResult {
+fn decode_op_imm32<V: InstructionVisitor>(i: u32, v: &mut V) -> VisitResult<V> {
     match (i >> 25, (i >> 12) & 0b111) {
-        (_, 0b000) => Ok(Instruction::Addiw(IType(i))),
-        (0b0000000, 0b001) => Ok(Instruction::Slliw(IType(i))),
-        (0b0000000, 0b101) => Ok(Instruction::Srliw(IType(i))),
-        (0b0100000, 0b101) => Ok(Instruction::Sraiw(IType(i))),
-        _ => Err(DecodingError::Unknown),
+        (_, 0b000) => Ok(v.addiw(rd(i), rs1(i), IType(i).imm())),
+        (0b0000000, 0b001) => Ok(v.slliw(rd(i), rs1(i), shamt(i))),
+        (0b0000000, 0b101) => Ok(v.srliw(rd(i), rs1(i), shamt(i))),
+        (0b0100000, 0b101) => Ok(v.sraiw(rd(i), rs1(i), shamt(i))),
+        // word shifts only have a 5-bit shamt
+        (0b0000001, 0b001) | (0b0000001, 0b101) | (0b0100001, 0b101) => {
+            Err(DecodingError::Illegal(i))
+        }
+        _ => Err(DecodingError::Unknown(i)),
     }
 }
 
 #[inline(always)]
-fn decode_store(i: u32) -> DecodingResult {
+fn decode_store<V: InstructionVisitor>(i: u32, v: &mut V) -> VisitResult<V> {
     match (i >> 12) & 0b111 {
-        0b000 => Ok(Instruction::Sb(SType(i))),
-        0b001 => Ok(Instruction::Sh(SType(i))),
-        0b010 => Ok(Instruction::Sw(SType(i))),
-        0b011 => Ok(Instruction::Sd(SType(i))),
-        _ => Err(DecodingError::Unknown),
+        0b000 => Ok(v.sb(rs1(i), rs2(i), SType(i).imm())),
+        0b001 => Ok(v.sh(rs1(i), rs2(i), SType(i).imm())),
+        0b010 => Ok(v.sw(rs1(i), rs2(i), SType(i).imm())),
+        0b011 => Ok(v.sd(rs1(i), rs2(i), SType(i).imm())),
+        _ => Err(DecodingError::Unknown(i)),
     }
 }
 
 #[inline(always)]
-fn decode_op(i: u32) -> DecodingResult {
+fn decode_op<V: InstructionVisitor>(i: u32, v: &mut V) -> VisitResult<V> {
     match (i >> 25, (i >> 12) & 0b111) {
-        (0b0000000, 0b000) => Ok(Instruction::Add(RType(i))),
-        (0b0100000, 0b000) => Ok(Instruction::Sub(RType(i))),
-        (0b0000000, 0b001) => Ok(Instruction::Sll(RType(i))),
-        (0b0000000, 0b010) => Ok(Instruction::Slt(RType(i))),
-        (0b0000000, 0b011) => Ok(Instruction::Sltu(RType(i))),
-        (0b0000000, 0b100) => Ok(Instruction::Xor(RType(i))),
-        (0b0000000, 0b101) => Ok(Instruction::Srl(RType(i))),
-        (0b0100000, 0b101) => Ok(Instruction::Sra(RType(i))),
-        (0b0000000, 0b110) => Ok(Instruction::Or(RType(i))),
-        (0b0000000, 0b111) => Ok(Instruction::And(RType(i))),
-        (0b0000001, 0b000) => Ok(Instruction::Mul(RType(i))),
-        (0b0000001, 0b001) => Ok(Instruction::Mulh(RType(i))),
-        (0b0000001, 0b010) => Ok(Instruction::Mulhsu(RType(i))),
-        (0b0000001, 0b011) => Ok(Instruction::Mulhu(RType(i))),
-        (0b0000001, 0b100) => Ok(Instruction::Div(RType(i))),
-        (0b0000001, 0b101) => Ok(Instruction::Divu(RType(i))),
-        (0b0000001, 0b110) => Ok(Instruction::Rem(RType(i))),
-        (0b0000001, 0b111) => Ok(Instruction::Remu(RType(i))),
-        _ => Err(DecodingError::Unknown),
+        (0b0000000, 0b000) => Ok(v.add(rd(i), rs1(i), rs2(i))),
+        (0b0100000, 0b000) => Ok(v.sub(rd(i), rs1(i), rs2(i))),
+        (0b0000000, 0b001) => Ok(v.sll(rd(i), rs1(i), rs2(i))),
+        (0b0000000, 0b010) => Ok(v.slt(rd(i), rs1(i), rs2(i))),
+        (0b0000000, 0b011) => Ok(v.sltu(rd(i), rs1(i), rs2(i))),
+        (0b0000000, 0b100) => Ok(v.xor(rd(i), rs1(i), rs2(i))),
+        (0b0000000, 0b101) => Ok(v.srl(rd(i), rs1(i), rs2(i))),
+        (0b0100000, 0b101) => Ok(v.sra(rd(i), rs1(i), rs2(i))),
+        (0b0000000, 0b110) => Ok(v.or(rd(i), rs1(i), rs2(i))),
+        (0b0000000, 0b111) => Ok(v.and(rd(i), rs1(i), rs2(i))),
+        (0b0000001, 0b000) => Ok(v.mul(rd(i), rs1(i), rs2(i))),
+        (0b0000001, 0b001) => Ok(v.mulh(rd(i), rs1(i), rs2(i))),
+        (0b0000001, 0b010) => Ok(v.mulhsu(rd(i), rs1(i), rs2(i))),
+        (0b0000001, 0b011) => Ok(v.mulhu(rd(i), rs1(i), rs2(i))),
+        (0b0000001, 0b100) => Ok(v.div(rd(i), rs1(i), rs2(i))),
+        (0b0000001, 0b101) => Ok(v.divu(rd(i), rs1(i), rs2(i))),
+        (0b0000001, 0b110) => Ok(v.rem(rd(i), rs1(i), rs2(i))),
+        (0b0000001, 0b111) => Ok(v.remu(rd(i), rs1(i), rs2(i))),
+        _ => Err(DecodingError::Unknown(i)),
     }
 }
 
 #[inline(always)]
-fn decode_op32(i: u32) -> DecodingResult {
+fn decode_op32<V: InstructionVisitor>(i: u32, v: &mut V) -> VisitResult<V> {
     match (i >> 25, (i >> 12) & 0b111) {
-        (0b0000000, 0b000) => Ok(Instruction::Addw(RType(i))),
-        (0b0100000, 0b000) => Ok(Instruction::Subw(RType(i))),
-        (0b0000000, 0b001) => Ok(Instruction::Sllw(RType(i))),
-        (0b0000000, 0b101) => Ok(Instruction::Srlw(RType(i))),
-        (0b0100000, 0b101) => Ok(Instruction::Sraw(RType(i))),
-        (0b0000001, 0b000) => Ok(Instruction::Mulw(RType(i))),
-        (0b0000001, 0b100) => Ok(Instruction::Divw(RType(i))),
-        (0b0000001, 0b101) => Ok(Instruction::Divuw(RType(i))),
-        (0b0000001, 0b110) => Ok(Instruction::Remw(RType(i))),
-        (0b0000001, 0b111) => Ok(Instruction::Remuw(RType(i))),
-        _ => Err(DecodingError::Unknown),
+        (0b0000000, 0b000) => Ok(v.addw(rd(i), rs1(i), rs2(i))),
+        (0b0100000, 0b000) => Ok(v.subw(rd(i), rs1(i), rs2(i))),
+        (0b0000000, 0b001) => Ok(v.sllw(rd(i), rs1(i), rs2(i))),
+        (0b0000000, 0b101) => Ok(v.srlw(rd(i), rs1(i), rs2(i))),
+        (0b0100000, 0b101) => Ok(v.sraw(rd(i), rs1(i), rs2(i))),
+        (0b0000001, 0b000) => Ok(v.mulw(rd(i), rs1(i), rs2(i))),
+        (0b0000001, 0b100) => Ok(v.divw(rd(i), rs1(i), rs2(i))),
+        (0b0000001, 0b101) => Ok(v.divuw(rd(i), rs1(i), rs2(i))),
+        (0b0000001, 0b110) => Ok(v.remw(rd(i), rs1(i), rs2(i))),
+        (0b0000001, 0b111) => Ok(v.remuw(rd(i), rs1(i), rs2(i))),
+        _ => Err(DecodingError::Unknown(i)),
     }
 }
 
-fn decode_branch(i: u32) -> DecodingResult {
+fn decode_branch<V: InstructionVisitor>(i: u32, v: &mut V) -> VisitResult<V> {
     match (i >> 12) & 0b111 {
-        0b000 => Ok(Instruction::Beq(BType(i))),
-        0b001 => Ok(Instruction::Bne(BType(i))),
-        0b100 => Ok(Instruction::Blt(BType(i))),
-        0b101 => Ok(Instruction::Bge(BType(i))),
-        0b110 => Ok(Instruction::Bltu(BType(i))),
-        0b111 => Ok(Instruction::Bgeu(BType(i))),
-        _ => Err(DecodingError::Unknown),
+        0b000 => Ok(v.beq(rs1(i), rs2(i), BType(i).imm())),
+        0b001 => Ok(v.bne(rs1(i), rs2(i), BType(i).imm())),
+        0b100 => Ok(v.blt(rs1(i), rs2(i), BType(i).imm())),
+        0b101 => Ok(v.bge(rs1(i), rs2(i), BType(i).imm())),
+        0b110 => Ok(v.bltu(rs1(i), rs2(i), BType(i).imm())),
+        0b111 => Ok(v.bgeu(rs1(i), rs2(i), BType(i).imm())),
+        _ => Err(DecodingError::Unknown(i)),
     }
 }
 
-fn decode_system(i: u32) -> DecodingResult {
+fn decode_system<V: InstructionVisitor>(i: u32, v: &mut V) -> VisitResult<V> {
     match i {
         // Environment Call and Breakpoint
-        0b0000_0000_0000_0000_0000_0000_0111_0011 => Ok(Instruction::Ecall(IType(i))),
-        0b0000_0000_0001_0000_0000_0000_0111_0011 => Ok(Instruction::Ebreak(IType(i))),
-        _ => Err(DecodingError::Unknown),
+        0b0000_0000_0000_0000_0000_0000_0111_0011 => Ok(v.ecall()),
+        0b0000_0000_0001_0000_0000_0000_0111_0011 => Ok(v.ebreak()),
+        _ => Err(DecodingError::Unknown(i)),
     }
 }
 
 #[inline(always)]
-fn decode_amo(i: u32) -> DecodingResult {
+fn decode_amo<V: InstructionVisitor>(i: u32, v: &mut V) -> VisitResult<V> {
     // bits 25 and 26 are "aq" and "rl", respectively
     match (i >> 27, (i >> 12) & 0b111) {
-        (0b00010, 0b010) => Ok(Instruction::Lrw(RType(i))),
-        (0b00011, 0b010) => Ok(Instruction::Scw(RType(i))),
-        (0b00001, 0b010) => Ok(Instruction::Amoswapw(RType(i))),
-        (0b00000, 0b010) => Ok(Instruction::Amoaddw(RType(i))),
-        (0b00100, 0b010) => Ok(Instruction::Amoxorw(RType(i))),
-        (0b01100, 0b010) => Ok(Instruction::Amoandw(RType(i))),
-        (0b01000, 0b010) => Ok(Instruction::Amoorw(RType(i))),
-        (0b10000, 0b010) => Ok(Instruction::Amominw(RType(i))),
-        (0b10100, 0b010) => Ok(Instruction::Amomaxw(RType(i))),
-        (0b11000, 0b010) => Ok(Instruction::Amominuw(RType(i))),
-        (0b11100, 0b010) => Ok(Instruction::Amomaxuw(RType(i))),
-        (0b00010, 0b011) => Ok(Instruction::Lrd(RType(i))),
-        (0b00011, 0b011) => Ok(Instruction::Scd(RType(i))),
-        (0b00001, 0b011) => Ok(Instruction::Amoswapd(RType(i))),
-        (0b00000, 0b011) => Ok(Instruction::Amoaddd(RType(i))),
-        (0b00100, 0b011) => Ok(Instruction::Amoxord(RType(i))),
-        (0b01100, 0b011) => Ok(Instruction::Amoandd(RType(i))),
-        (0b01000, 0b011) => Ok(Instruction::Amoord(RType(i))),
-        (0b10000, 0b011) => Ok(Instruction::Amomind(RType(i))),
-        (0b10100, 0b011) => Ok(Instruction::Amomaxd(RType(i))),
-        (0b11000, 0b011) => Ok(Instruction::Amominud(RType(i))),
-        (0b11100, 0b011) => Ok(Instruction::Amomaxud(RType(i))),
-        _ => Err(DecodingError::Unknown),
+        (0b00010, 0b010) => Ok(v.lrw(rd(i), rs1(i), rs2(i))),
+        (0b00011, 0b010) => Ok(v.scw(rd(i), rs1(i), rs2(i))),
+        (0b00001, 0b010) => Ok(v.amoswapw(rd(i), rs1(i), rs2(i))),
+        (0b00000, 0b010) => Ok(v.amoaddw(rd(i), rs1(i), rs2(i))),
+        (0b00100, 0b010) => Ok(v.amoxorw(rd(i), rs1(i), rs2(i))),
+        (0b01100, 0b010) => Ok(v.amoandw(rd(i), rs1(i), rs2(i))),
+        (0b01000, 0b010) => Ok(v.amoorw(rd(i), rs1(i), rs2(i))),
+        (0b10000, 0b010) => Ok(v.amominw(rd(i), rs1(i), rs2(i))),
+        (0b10100, 0b010) => Ok(v.amomaxw(rd(i), rs1(i), rs2(i))),
+        (0b11000, 0b010) => Ok(v.amominuw(rd(i), rs1(i), rs2(i))),
+        (0b11100, 0b010) => Ok(v.amomaxuw(rd(i), rs1(i), rs2(i))),
+        (0b00010, 0b011) => Ok(v.lrd(rd(i), rs1(i), rs2(i))),
+        (0b00011, 0b011) => Ok(v.scd(rd(i), rs1(i), rs2(i))),
+        (0b00001, 0b011) => Ok(v.amoswapd(rd(i), rs1(i), rs2(i))),
+        (0b00000, 0b011) => Ok(v.amoaddd(rd(i), rs1(i), rs2(i))),
+        (0b00100, 0b011) => Ok(v.amoxord(rd(i), rs1(i), rs2(i))),
+        (0b01100, 0b011) => Ok(v.amoandd(rd(i), rs1(i), rs2(i))),
+        (0b01000, 0b011) => Ok(v.amoord(rd(i), rs1(i), rs2(i))),
+        (0b10000, 0b011) => Ok(v.amomind(rd(i), rs1(i), rs2(i))),
+        (0b10100, 0b011) => Ok(v.amomaxd(rd(i), rs1(i), rs2(i))),
+        (0b11000, 0b011) => Ok(v.amominud(rd(i), rs1(i), rs2(i))),
+        (0b11100, 0b011) => Ok(v.amomaxud(rd(i), rs1(i), rs2(i))),
+        _ => Err(DecodingError::Unknown(i)),
     }
 }
 
 #[inline(always)]
-fn decode_fence(i: u32) -> DecodingResult {
+fn decode_fence<V: InstructionVisitor>(i: u32, v: &mut V) -> VisitResult<V> {
     match (i >> 12) & 0b111 {
-        0b000 => Ok(Instruction::Fence(IType(i))),
-        _ => Err(DecodingError::Unknown),
+        0b000 => Ok(v.fence(rd(i), rs1(i), IType(i).imm())),
+        0b001 => Ok(v.fence_i()),
+        _ => Err(DecodingError::Unknown(i)),
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::cell::Cell;
     use Instruction::*;
 
     // Nearly all tests are derived from the output of
@@ -313,6 +891,41 @@ mod tests {
         assert_eq!(decode(0x00008c01).unwrap(), Sub(RType(0x40840433))); // sub x8,x8,x8
     }
 
+    #[test]
+    fn decoding_with_raw() {
+        let words = [
+            0x00001a37, // lui x20,0x1
+            0xfe1ff06f, // jal x0,800029ec
+            0x000f0067, // jalr x0,0(x30)
+            0x02013c03, // ld x24,32(x2)
+            0x00200793, // addi x15,x0,2
+            0x0b613823, // sd x22,176(x2)
+            0x40e78533, // sub x10,x15,x14
+            0x0220df33, // divu x30,x1,x2
+            0x1b5a0463, // beq x20,x21,80002a10
+            0x00000073, // ecall
+        ];
+
+        for word in words.iter().copied() {
+            let (instruction, raw) = decode_with_raw(word).unwrap();
+
+            assert_eq!(raw, word);
+            assert_eq!(u32::from(instruction), raw);
+        }
+    }
+
+    #[test]
+    fn decoding_halfwords() {
+        assert_eq!(
+            decode_halfwords(0x8c01, 0xffff).unwrap(), // sub x8,x8,x8
+            (Sub(RType(0x40840433)), InstructionWidth::Compressed)
+        );
+        assert_eq!(
+            decode_halfwords(0x8633, 0x00c5).unwrap(), // add x12,x11,x12
+            (Add(RType(0x00c58633)), InstructionWidth::Full)
+        );
+    }
+
     #[test]
     fn load() {
         assert_eq!(decode(0x01853683).unwrap(), Ld(IType(0x01853683))); // Ld x13,24(x10)
@@ -325,6 +938,107 @@ mod tests {
         assert_eq!(decode(0x00200793).unwrap(), Addi(IType(0x00200793))); // addi x15,x0,2
         assert_eq!(decode(0x00000013).unwrap(), Addi(IType(0x00000013))); // addi x0,x0,0
         assert_eq!(decode(0x00000013).unwrap(), Addi(IType(0x00000013))); // addi x0,x0,0
+
+        // the shift type is encoded in the upper bits of the immediate
+        assert_eq!(
+            Instruction::new_srli(Register::A0, Register::A1, 63),
+            Srli(IType(0x03f5d513))
+        );
+        assert_eq!(
+            Instruction::new_srai(Register::A0, Register::A1, 63),
+            Srai(IType(0x43f5d513))
+        );
+    }
+
+    #[test]
+    fn immediate_logicals() {
+        use Register::{A0, A1};
+
+        let logicals = [
+            (
+                0xfff5f513,
+                Instruction::new_andi(A0, A1, -1),
+                "andi a0, a1, -1",
+            ),
+            (
+                0x5555e513,
+                Instruction::new_ori(A0, A1, 1365),
+                "ori a0, a1, 1365",
+            ),
+            (
+                0x8005c513,
+                Instruction::new_xori(A0, A1, -2048),
+                "xori a0, a1, -2048",
+            ),
+        ];
+
+        for (raw, instruction, text) in logicals.iter() {
+            assert_eq!(decode(*raw), Ok(*instruction));
+            assert_eq!(u32::from(*instruction), *raw);
+            assert_eq!(instruction.to_string(), *text);
+        }
+
+        // the expansion of c.andi s0,-3 decodes again
+        let c_andi = decode(0x9875).unwrap();
+        assert_eq!(c_andi.to_string(), "andi fp, fp, -3");
+        assert_eq!(decode(u32::from(c_andi)), Ok(c_andi));
+    }
+
+    #[test]
+    fn set_less_than_immediate() {
+        use Register::{A0, A1};
+
+        let comparisons = [
+            (
+                0xffb5a513,
+                Instruction::new_slti(A0, A1, -5),
+                "slti a0, a1, -5",
+            ),
+            (
+                0x7ff5b513,
+                Instruction::new_sltiu(A0, A1, 2047),
+                "sltiu a0, a1, 2047",
+            ),
+        ];
+
+        for (raw, instruction, text) in comparisons.iter() {
+            assert_eq!(decode(*raw), Ok(*instruction));
+            assert_eq!(u32::from(*instruction), *raw);
+            assert_eq!(instruction.to_string(), *text);
+        }
+
+        assert_eq!(decode(0xffb5a513), Ok(Slti(IType(0xffb5a513))));
+        assert_eq!(decode(0x7ff5b513), Ok(Sltiu(IType(0x7ff5b513))));
+    }
+
+    #[test]
+    fn op_imm32() {
+        let addiw = decode(0x0017879b).unwrap(); // addiw x15,x15,1
+        assert_eq!(addiw, Addiw(IType(0x0017879b)));
+        assert_eq!(u32::from(addiw), 0x0017879b);
+        assert_eq!(addiw.to_string(), "addiw a5, a5, 1");
+
+        let negative = decode(0xff95851b).unwrap(); // addiw x10,x11,-7
+        assert_eq!(
+            negative,
+            Instruction::new_addiw(Register::A0, Register::A1, -7)
+        );
+        assert_eq!(negative.to_string(), "addiw a0, a1, -7");
+
+        assert_eq!(decode(0x01f5951b).unwrap(), Slliw(IType(0x01f5951b))); // slliw x10,x11,31
+        assert_eq!(decode(0x0035d51b).unwrap(), Srliw(IType(0x0035d51b))); // srliw x10,x11,3
+        assert_eq!(decode(0x4045d51b).unwrap(), Sraiw(IType(0x4045d51b))); // sraiw x10,x11,4
+
+        // a shamt of 32 or more is illegal for word shifts
+        assert_eq!(decode(0x0205951b), Err(DecodingError::Illegal(0x0205951b))); // slliw x10,x11,32
+        assert_eq!(decode(0x0205d51b), Err(DecodingError::Illegal(0x0205d51b))); // srliw x10,x11,32
+        assert_eq!(decode(0x4205d51b), Err(DecodingError::Illegal(0x4205d51b))); // sraiw x10,x11,32
+
+        assert_eq!(decode(0x4045d51b).unwrap().to_string(), "sraiw a0, a1, 4");
+        assert_eq!(
+            Instruction::new_sraiw(Register::A0, Register::A1, 4),
+            Sraiw(IType(0x4045d51b))
+        );
     }
 
     #[test]
@@ -356,6 +1070,27 @@ mod tests {
         assert_eq!(decode(0x0220ff33).unwrap(), Remu(RType(0x0220ff33))); // remu x30,x1,x2
     }
 
+    #[test]
+    fn op32() {
+        let round_trip = |i: u32| {
+            let instruction = decode(i).unwrap();
+            assert_eq!(u32::from(instruction), i);
+            instruction
+        };
+
+        assert_eq!(round_trip(0x00c5853b), Addw(RType(0x00c5853b))); // addw x10,x11,x12
+        assert_eq!(round_trip(0x40c5853b), Subw(RType(0x40c5853b))); // subw x10,x11,x12
+        assert_eq!(round_trip(0x02c5853b), Mulw(RType(0x02c5853b))); // mulw x10,x11,x12
+        assert_eq!(round_trip(0x02c5d53b), Divuw(RType(0x02c5d53b))); // divuw x10,x11,x12
+        assert_eq!(round_trip(0x02c5f53b), Remuw(RType(0x02c5f53b))); // remuw x10,x11,x12
+
+        assert_eq!(round_trip(0x02c5f53b).to_string(), "remuw a0, a1, a2");
+        assert_eq!(
+            Instruction::new_subw(Register::A0, Register::A1, Register::A2),
+            Subw(RType(0x40c5853b))
+        );
+    }
+
     #[test]
     fn branch() {
         assert_eq!(decode(0x10e78463).unwrap(), Beq(BType(0x10e78463))); // beq x15,x14,800024b8
@@ -363,8 +1098,583 @@ mod tests {
         assert_eq!(decode(0x1b5a0463).unwrap(), Beq(BType(0x1b5a0463))); // beq x20,x21,80002a10
     }
 
+    #[test]
+    fn branch_family() {
+        use Register::*;
+
+        let branches = [
+            (
+                0xfeb51ee3,
+                Instruction::new_bne(A0, A1, -4),
+                "bne a0, a1, -4",
+            ),
+            (
+                0x00b54863,
+                Instruction::new_blt(A0, A1, 16),
+                "blt a0, a1, 16",
+            ),
+            (
+                0x800650e3,
+                Instruction::new_bge(A2, Zero, -2048),
+                "bge a2, zero, -2048",
+            ),
+            (
+                0x7ee6efe3,
+                Instruction::new_bltu(A3, A4, 4094),
+                "bltu a3, a4, 4094",
+            ),
+            (
+                0x8062f063,
+                Instruction::new_bgeu(T0, T1, -4096),
+                "bgeu t0, t1, -4096",
+            ),
+        ];
+
+        for (raw, instruction, text) in branches.iter() {
+            assert_eq!(decode(*raw), Ok(*instruction));
+            assert_eq!(u32::from(*instruction), *raw);
+            assert_eq!(instruction.to_string(), *text);
+
+            let offset: i64 = text.rsplit(' ').next().unwrap().parse().unwrap();
+            assert_eq!(
+                decode_at_pc(*raw, 0x12000).unwrap().target,
+                Some((0x12000 + offset) as u64)
+            );
+        }
+
+        // c.bnez a0,-6 expands to bne
+        let c_bnez = decode(0xfd6d).unwrap();
+        assert_eq!(c_bnez, Instruction::new_bne(A0, Zero, -6));
+        assert_eq!(decode(u32::from(c_bnez)), Ok(c_bnez));
+    }
+
+    #[test]
+    fn canonical_illegal_instructions() {
+        assert_eq!(decode(0x00000000), Err(DecodingError::Illegal(0x00000000)));
+        assert_eq!(decode(0xffffffff), Err(DecodingError::Illegal(0xffffffff)));
+
+        // other words of the >= 80-bit encoding space are only reserved
+        assert_eq!(decode(0x7fffffff), Err(DecodingError::Reserved(0x7fffffff)));
+    }
+
+    #[test]
+    fn floating_point_unsupported() {
+        use crate::decompress::decompress_to_instruction;
+
+        // flw fa0,0(a0), fsd fa0,0(a0), fmadd.s and fadd.d fa0,fa1,fa2
+        for i in [0x00052507, 0x00a53027, 0x00000043, 0x02c5f553].iter() {
+            assert_eq!(decode(*i), Err(DecodingError::Unsupported(*i)));
+        }
+
+        // c.fld fa0,8(a1), c.fsd fa0,8(a1), c.fldsp fa0,8(sp) and c.fsdsp fa0,8(sp)
+        for i in [0x2588, 0xa588, 0x2522, 0xa42a].iter() {
+            assert_eq!(decode(*i), Err(DecodingError::Unsupported(*i)));
+            assert_eq!(
+                decompress_to_instruction(*i as u16),
+                Err(DecodingError::Unsupported(*i))
+            );
+        }
+    }
+
+    #[test]
+    fn error_codes() {
+        assert_eq!(DecodingError::Illegal(0).code(), 1);
+        assert_eq!(DecodingError::Reserved(0).code(), 2);
+        assert_eq!(DecodingError::Unimplemented(0).code(), 3);
+        assert_eq!(DecodingError::Custom(0).code(), 4);
+        assert_eq!(DecodingError::Unknown(0).code(), 5);
+        assert_eq!(DecodingError::Truncated.code(), 6);
+        assert_eq!(DecodingError::SuspiciousZeroWrite(0).code(), 7);
+        assert_eq!(DecodingError::Unsupported(0).code(), 8);
+    }
+
+    #[test]
+    fn error_bits() {
+        // fmadd.s, the half-word of c.fld and c.addi16sp sp,0
+        assert_eq!(decode(0x00000043).unwrap_err().bits(), Some(0x00000043));
+        assert_eq!(decode(0x2000).unwrap_err().bits(), Some(0x2000));
+        assert_eq!(decode(0x6101), Err(DecodingError::Reserved(0x6101)));
+        assert_eq!(DecodingError::Truncated.bits(), None);
+
+        assert_eq!(
+            DecodingError::Unimplemented(0x00000043).to_string(),
+            "Instruction 0x43 is well defined but not implemented yet"
+        );
+        assert_eq!(
+            DecodingError::Unsupported(0x00000043).to_string(),
+            "Instruction 0x43 is well defined but not supported"
+        );
+        assert_eq!(
+            DecodingError::Illegal(0x0000).to_string(),
+            "Instruction 0x0 is illegal"
+        );
+        assert_eq!(
+            DecodingError::SuspiciousZeroWrite(0x00b50033).to_string(),
+            "Instruction 0xb50033 writes the result of a computation to the zero register"
+        );
+    }
+
+    #[test]
+    fn hints() {
+        let preserve = DecodingOptions {
+            preserve_hints: true,
+            ..DecodingOptions::default()
+        };
+
+        // addi x0,x1,5
+        assert_eq!(
+            decode_with_options(0x00508013, &preserve).unwrap(),
+            Hint(0x00508013)
+        );
+        assert_eq!(
+            decode_with_options(0x00508013, &DecodingOptions::default()).unwrap(),
+            Addi(IType(0x00508013))
+        );
+
+        // the canonical nop is not a hint
+        assert_eq!(
+            decode_with_options(0x00000013, &preserve).unwrap(),
+            Addi(IType(0x00000013))
+        );
+
+        // c.addi zero,1 keeps the half-word, not its expansion addi zero,zero,1
+        let compressed = decode_with_options(0x0005, &preserve).unwrap();
+        assert_eq!(compressed, Hint(0x0005));
+        assert_eq!(u32::from(compressed), 0x0005);
+        assert_eq!(
+            decode_with_options(0x0001, &preserve).unwrap(),
+            Addi(IType(0x00000013))
+        );
+    }
+
+    #[test]
+    fn unknown_rtypes() {
+        let custom = 0x0ac5f533; // funct7 0b0000101, funct3 0b111 (rd a0, rs1 a1, rs2 a2)
+        let lenient = DecodingOptions::from(Strictness::Lenient);
+
+        assert_eq!(decode(custom), Err(DecodingError::Unknown(custom)));
+        assert_eq!(
+            decode_with_options(custom, &DecodingOptions::from(Strictness::Riscu)),
+            Err(DecodingError::Unknown(custom))
+        );
+
+        let instruction = decode_with_options(custom, &lenient).unwrap();
+        assert_eq!(instruction, UnknownRType(RType(custom)));
+        assert_eq!(instruction.writes(), Some(Register::A0));
+        assert_eq!(instruction.reads(), vec![Register::A1, Register::A2]);
+        assert_eq!(
+            instruction.to_string(),
+            "unknown a0, a1, a2 (funct7 0b0000101, funct3 0b111)"
+        );
+        assert_eq!(u32::from(instruction), custom);
+
+        // OP-32 as well, but other opcodes are still rejected
+        let custom32 = 0x0ac5f53b;
+        assert_eq!(
+            decode_with_options(custom32, &lenient),
+            Ok(UnknownRType(RType(custom32)))
+        );
+        assert_eq!(
+            decode_with_options(0x0000003f, &lenient),
+            Err(DecodingError::Reserved(0x0000003f))
+        );
+    }
+
+    #[test]
+    fn stream() {
+        let mut bytes = Vec::new();
+        // addi sp,sp,-16, an undefined word, ecall and a trailing partial word
+        for word in [0xff010113u32, 0xffffffff, 0x00000073].iter() {
+            bytes.extend(&word.to_le_bytes());
+        }
+        bytes.extend(&[0x13, 0x05]);
+
+        let decoded: Vec<_> = decode_stream(&bytes).collect();
+
+        assert_eq!(
+            decoded,
+            vec![
+                Ok((0, Instruction::new_addi(Register::Sp, Register::Sp, -16))),
+                Err(DecodingError::Illegal(0xffffffff)),
+                Ok((8, Instruction::new_ecall())),
+            ]
+        );
+        assert_eq!(decode_stream(&bytes[..3]).count(), 0);
+    }
+
+    #[test]
+    fn mixed() {
+        use InstructionWidth::{Compressed, Full};
+
+        let bytes = [
+            0x13, 0x01, 0x01, 0xff, // addi sp,sp,-16
+            0xe1, 0x17, // c.addi a5,-8
+            0x00, 0x00, // c.unimp
+            0x73, 0x00, 0x00, 0x00, // ecall
+            0x82, 0x80, // c.jr ra
+            0x13, 0x05, // truncated addi
+        ];
+
+        let decoded: Vec<_> = decode_mixed(&bytes).collect();
+
+        assert_eq!(
+            decoded,
+            vec![
+                (0, decode(0xff010113).map(|i| (i, Full))),
+                (4, decode(0x17e1).map(|i| (i, Compressed))),
+                (6, Err(DecodingError::Illegal(0x0000))),
+                (8, Ok((Instruction::new_ecall(), Full))),
+                (
+                    12,
+                    Ok((
+                        Instruction::new_jalr(Register::Zero, Register::Ra, 0),
+                        Compressed
+                    ))
+                ),
+                (14, Err(DecodingError::Truncated)),
+            ]
+        );
+
+        // the widths of the instructions give the offset of the next one
+        let mut pc = 0;
+        for (offset, result) in decoded.iter().take(5) {
+            assert_eq!(*offset, pc);
+            pc += result.map_or(2, |(_, width)| width.bytes() as u64);
+        }
+        assert!(decoded[1].1.unwrap().1.is_compressed());
+        assert!(!decoded[3].1.unwrap().1.is_compressed());
+
+        // a single trailing byte is truncated as well
+        assert_eq!(
+            decode_mixed(&bytes[..5]).last(),
+            Some((4, Err(DecodingError::Truncated)))
+        );
+        assert_eq!(decode_mixed(&[]).count(), 0);
+    }
+
+    #[test]
+    fn resync() {
+        let bytes = [
+            0x13, 0x01, 0x01, 0xff, // addi sp,sp,-16
+            0xff, 0xff, 0xff, 0xff, // data
+            0xe1, 0x17, // c.addi a5,-8
+            0x73, 0x00, 0x00, 0x00, // ecall
+            0x13, // a trailing byte
+        ];
+
+        let swept: Vec<_> = decode_resync(&bytes).collect();
+
+        assert_eq!(
+            swept,
+            vec![
+                (0, decode(0xff010113)),
+                (4, Err(DecodingError::Illegal(0xffffffff))),
+                (6, decode(0x17e1ffff)),
+                (8, decode(0x17e1)),
+                (10, Ok(Instruction::new_ecall())),
+                (14, Err(DecodingError::Truncated)),
+            ]
+        );
+        assert!(swept[2].1.is_err());
+
+        // where decode_mixed skips the width of the rejected word
+        let mixed = decode_mixed(&bytes).map(|(offset, _)| offset);
+        assert_eq!(mixed.collect::<Vec<_>>(), vec![0, 4, 8, 10, 14]);
+
+        // random bytes are swept in strictly increasing steps
+        let mut rng = crate::test_rng::Rng::new();
+        let noise = (0..4096)
+            .map(|_| (rng.next_u32() >> 24) as u8)
+            .collect::<Vec<_>>();
+
+        let offsets = decode_resync(&noise).map(|(offset, _)| offset);
+        let offsets = offsets.collect::<Vec<_>>();
+        assert!(offsets.windows(2).all(|pair| pair[0] < pair[1]));
+        assert!(offsets.len() <= noise.len() / 2 + 1);
+        assert_eq!(decode_resync(&[]).count(), 0);
+    }
+
+    #[test]
+    fn all() {
+        let words = [0xff010113, 0xffffffff, 0x17e1, 0x00000073];
+        let expected = words.iter().map(|w| decode(*w)).collect::<Vec<_>>();
+
+        assert_eq!(decode_all(&words), expected);
+        assert_eq!(decode_all(&[]), vec![]);
+
+        let mut out = decode_all(&words[..1]);
+        let capacity = out.capacity();
+
+        decode_all_into(&words[1..2], &mut out);
+        assert_eq!(out, vec![Err(DecodingError::Illegal(0xffffffff))]);
+        assert_eq!(out.capacity(), capacity);
+
+        decode_all_into(&words, &mut out);
+        assert_eq!(out, expected);
+    }
+
+    #[test]
+    fn one() {
+        let bytes = [0xe1, 0x17, 0x13, 0x01, 0x01, 0xff]; // c.addi a5,-8; addi sp,sp,-16
+
+        assert_eq!(decode_one(&bytes), decode(0x17e1).map(|i| (i, 2)));
+        assert_eq!(decode_one(&bytes[2..]), decode(0xff010113).map(|i| (i, 4)));
+
+        assert_eq!(decode_one(&bytes[2..5]), Err(DecodingError::Truncated));
+        assert_eq!(decode_one(&bytes[..1]), Err(DecodingError::Truncated));
+        assert_eq!(decode_one(&[]), Err(DecodingError::Truncated));
+        assert_eq!(
+            decode_one(&[0x00, 0x00]),
+            Err(DecodingError::Illegal(0x0000))
+        );
+    }
+
+    #[test]
+    fn clamped_word_shifts() {
+        let lenient = DecodingOptions::from(Strictness::Lenient);
+        let mut warnings = Vec::new();
+
+        // slliw a0,a1,63, srliw a0,a1,32 and sraiw a0,a1,63
+        for (crafted, clamped, shamt) in [
+            (0x03f5951b, 0x01f5951b, 63),
+            (0x0205d51b, 0x01f5d51b, 32),
+            (0x43f5d51b, 0x41f5d51b, 63),
+        ]
+        .iter()
+        {
+            assert_eq!(decode(*crafted), Err(DecodingError::Illegal(*crafted)));
+            assert_eq!(
+                decode_with_warnings(*crafted, &lenient, &mut warnings),
+                decode(*clamped)
+            );
+            assert_eq!(warnings.pop(), Some(DecodingWarning::ClampedShamt(*shamt)));
+        }
+
+        // valid shifts are left alone
+        assert!(decode_with_warnings(0x01f5951b, &lenient, &mut warnings).is_ok());
+        assert!(warnings.is_empty());
+
+        assert_eq!(
+            decode_with_options(0x03f5951b, &DecodingOptions::default()),
+            Err(DecodingError::Illegal(0x03f5951b))
+        );
+    }
+
+    #[test]
+    fn immediate_extremes() {
+        // the most negative and most positive immediate of each format: the field of `lui` and
+        // `auipc` is unsigned, but sign extended when shifted into place
+        let extremes = [
+            (0x80000093, -2048, -2048),          // addi ra, zero, -2048
+            (0x7ff00093, 2047, 2047),            // addi ra, zero, 2047
+            (0x8000009b, -2048, -2048),          // addiw ra, zero, -2048
+            (0xfff03093, -1, -1),                // sltiu ra, zero, -1
+            (0x80007093, -2048, -2048),          // andi ra, zero, -2048
+            (0x80013083, -2048, -2048),          // ld ra, -2048(sp)
+            (0x7ff14083, 2047, 2047),            // lbu ra, 2047(sp)
+            (0x800f80e7, -2048, -2048),          // jalr ra, -2048(t6)
+            (0x80113023, -2048, -2048),          // sd ra, -2048(sp)
+            (0x7e110fa3, 2047, 2047),            // sb ra, 2047(sp)
+            (0x80208063, -4096, -4096),          // beq ra, sp, -4096
+            (0x7e20ffe3, 4094, 4094),            // bgeu ra, sp, 4094
+            (0x8000006f, -1048576, -1048576),    // jal zero, -1048576
+            (0x7ffff06f, 1048574, 1048574),      // jal zero, 1048574
+            (0xfffff0b7, 0xfffff, -0x1000),      // lui ra, 0xfffff
+            (0x800000b7, 0x80000, -0x8000_0000), // lui ra, 0x80000
+            (0x7ffff0b7, 0x7ffff, 0x7fff_f000),  // lui ra, 0x7ffff
+            (0x80000097, 0x80000, -0x8000_0000), // auipc ra, 0x80000
+            (0x03f09093, 63, 63),                // slli ra, ra, 63
+            (0x43f0d093, 63, 63),                // srai ra, ra, 63
+            (0x41f0d09b, 31, 31),                // sraiw ra, ra, 31
+        ];
+
+        for (raw, immediate, effective) in extremes.iter() {
+            let instruction = decode(*raw).unwrap();
+
+            assert_eq!(instruction.immediate(), Some(*immediate), "{:#010x}", raw);
+            assert_eq!(
+                instruction.effective_immediate(),
+                Some(*effective),
+                "{:#010x}",
+                raw
+            );
+            assert_eq!(u32::from(instruction), *raw);
+        }
+    }
+
+    #[test]
+    fn suspicious_zero_writes() {
+        let strict = DecodingOptions {
+            reject_zero_writes: true,
+            ..DecodingOptions::default()
+        };
+
+        // add x0,a0,a1
+        assert_eq!(
+            decode_with_options(0x00b50033, &strict),
+            Err(DecodingError::SuspiciousZeroWrite(0x00b50033))
+        );
+        assert_eq!(
+            decode_with_options(0x00b50033, &strict).unwrap_err().bits(),
+            Some(0x00b50033)
+        );
+
+        // c.addi zero,1 carries the half-word
+        assert_eq!(
+            decode_with_options(0x0005, &strict),
+            Err(DecodingError::SuspiciousZeroWrite(0x0005))
+        );
+        assert_eq!(
+            decode_with_options(0x00b50033, &DecodingOptions::default()).unwrap(),
+            Add(RType(0x00b50033))
+        );
+
+        // nop and j are fine
+        assert!(decode_with_options(0x00000013, &strict).is_ok());
+        assert!(decode_with_options(0x0000006f, &strict).is_ok());
+    }
+
+    #[test]
+    fn strictness() {
+        let decode = |i, strictness| decode_with_options(i, &DecodingOptions::from(strictness));
+
+        // add x0,a0,a1
+        assert!(decode(0x00b50033, Strictness::Strict).is_err());
+        assert!(decode(0x00b50033, Strictness::Riscu).is_ok());
+
+        // xori a0,a0,1 and c.addi a5,-8
+        for i in [0x00154513, 0x17e1].iter() {
+            assert_eq!(
+                decode(*i, Strictness::Riscu),
+                Err(DecodingError::Unsupported(*i))
+            );
+            assert!(decode(*i, Strictness::Lenient).is_ok());
+        }
+
+        // c.addi a5,-8 followed by the half-word of another instruction
+        assert_eq!(
+            decode(0xffff_17e1, Strictness::Riscu),
+            Err(DecodingError::Unsupported(0x17e1))
+        );
+
+        // addi a0,a0,1
+        assert!(decode(0x00150513, Strictness::Strict).is_ok());
+    }
+
+    #[test]
+    fn decoding_at_pc() {
+        // beq a0,zero,-12
+        let located = decode_at_pc(0xfe050ae3, 0x10010).unwrap();
+        assert_eq!(located.instruction, Beq(BType(0xfe050ae3)));
+        assert_eq!(located.target, Some(0x10010 - 12));
+
+        // jal ra,0x8
+        assert_eq!(
+            decode_at_pc(0x008000ef, 0x10000).unwrap().target,
+            Some(0x10008)
+        );
+
+        // addi a0,a0,1
+        assert_eq!(decode_at_pc(0x00150513, 0x10000).unwrap().target, None);
+    }
+
+    #[test]
+    fn valid_riscu_words() {
+        assert!(is_valid_riscu_word(0x00150513)); // addi a0,a0,1
+        assert!(!is_valid_riscu_word(0x00154513)); // xori a0,a0,1
+        assert!(!is_valid_riscu_word(0x17e1)); // c.addi a5,-8
+        assert!(!is_valid_riscu_word(0));
+    }
+
+    #[test]
+    fn custom_handler() {
+        let calls = Cell::new(0);
+        let handler = |i: u32| {
+            calls.set(calls.get() + 1);
+
+            // custom-0 opcode
+            if i & 0b111_1111 == 0b000_1011 {
+                Some(Instruction::new_nop())
+            } else {
+                None
+            }
+        };
+
+        assert_eq!(
+            decode_with_handler(0x0000000b, handler),
+            Ok(Instruction::new_nop())
+        );
+        assert_eq!(calls.get(), 1);
+
+        // addi a0,a0,1
+        assert_eq!(
+            decode_with_handler(0x00150513, handler),
+            Ok(Addi(IType(0x00150513)))
+        );
+        assert_eq!(calls.get(), 1);
+
+        // custom-1 opcode
+        assert_eq!(
+            decode_with_handler(0x0000002b, handler),
+            Err(DecodingError::Custom(0x0000002b))
+        );
+        assert_eq!(calls.get(), 2);
+    }
+
+    #[test]
+    fn isa_profiles() {
+        let mul = 0x02c58533; // mul x10,x11,x12
+        let c_addi = 0x17e1; // c.addi x15,-8
+        let amoadd = 0x00c5a52f; // amoadd.w x10,x12,(x11)
+
+        assert_eq!(
+            decode_with_profile(mul, IsaProfile::Rv64i),
+            Err(DecodingError::Illegal(mul))
+        );
+        assert_eq!(
+            decode_with_profile(mul, IsaProfile::Rv64im),
+            Ok(Mul(RType(mul)))
+        );
+        assert!(decode_with_profile(0x00c58533, IsaProfile::Rv64i).is_ok()); // add x10,x11,x12
+
+        assert_eq!(
+            decode_with_profile(c_addi, IsaProfile::Rv64im),
+            Err(DecodingError::Illegal(c_addi))
+        );
+        assert!(decode_with_profile(c_addi, IsaProfile::Rv64imc).is_ok());
+        assert_eq!(
+            decode_with_profile(0xffff_0000 | c_addi, IsaProfile::Rv64im),
+            Err(DecodingError::Illegal(c_addi))
+        );
+
+        assert_eq!(
+            decode_with_profile(amoadd, IsaProfile::Rv64imc),
+            Err(DecodingError::Illegal(amoadd))
+        );
+    }
+
+    #[test]
+    fn fence() {
+        assert_eq!(decode(0x0ff0000f).unwrap(), Fence(IType(0x0ff0000f))); // fence
+        assert_eq!(decode(0x0000100f).unwrap(), FenceI(IType(0x0000100f))); // fence.i
+        assert_eq!(decode(0x0000100f).unwrap(), Instruction::new_fence_i());
+        assert_eq!(decode(0x0310000f).unwrap(), Fence(IType(0x0310000f))); // fence rw,w
+    }
+
     #[test]
     fn system() {
         assert_eq!(decode(0x00000073).unwrap(), Instruction::new_ecall()); // ecall
+        assert_eq!(decode(0x00100073).unwrap(), Instruction::new_ebreak()); // ebreak
+
+        // the rest of SYSTEM is not part of RISC-U, e.g. CSRs and wfi
+        assert_eq!(decode(0x34011073), Err(DecodingError::Unknown(0x34011073))); // csrrw zero,mscratch,sp
+        assert_eq!(decode(0x10500073), Err(DecodingError::Unknown(0x10500073))); // wfi
+
+        // c.ebreak
+        assert_eq!(decode(0x9002).unwrap(), Instruction::new_ebreak());
+        assert_eq!(
+            crate::decompress::decompress_to_instruction(0x9002),
+            Ok(Instruction::new_ebreak())
+        );
     }
 }
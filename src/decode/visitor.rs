@@ -0,0 +1,474 @@
+//! Decoding straight into a handler of the instructions, see [`decode_with`](super::decode_with).
+
+use crate::{types::*, Instruction, Register};
+
+/// A handler of decoded instructions with one method per instruction, which is called by
+/// [`decode_with`](super::decode_with) with the fields of the instruction.
+///
+/// The arguments of every method are the ones of the corresponding constructor of
+/// [`Instruction`], e.g. [`addi`](Self::addi) and [`Instruction::new_addi`]. Shifts receive the
+/// shift amount as immediate. The `aq` and `rl` bits of atomic instructions are not passed on.
+pub trait InstructionVisitor {
+    type Output;
+
+    // LUI, AUIPC
+    fn lui(&mut self, rd: Register, imm: i32) -> Self::Output;
+    fn auipc(&mut self, rd: Register, imm: i32) -> Self::Output;
+
+    // Jumps
+    fn jal(&mut self, rd: Register, imm: i32) -> Self::Output;
+    fn jalr(&mut self, rd: Register, rs1: Register, imm: i32) -> Self::Output;
+
+    // Branch
+    fn beq(&mut self, rs1: Register, rs2: Register, imm: i32) -> Self::Output;
+    fn bne(&mut self, rs1: Register, rs2: Register, imm: i32) -> Self::Output;
+    fn blt(&mut self, rs1: Register, rs2: Register, imm: i32) -> Self::Output;
+    fn bge(&mut self, rs1: Register, rs2: Register, imm: i32) -> Self::Output;
+    fn bltu(&mut self, rs1: Register, rs2: Register, imm: i32) -> Self::Output;
+    fn bgeu(&mut self, rs1: Register, rs2: Register, imm: i32) -> Self::Output;
+
+    // Load
+    fn lb(&mut self, rd: Register, rs1: Register, imm: i32) -> Self::Output;
+    fn lh(&mut self, rd: Register, rs1: Register, imm: i32) -> Self::Output;
+    fn lw(&mut self, rd: Register, rs1: Register, imm: i32) -> Self::Output;
+    fn ld(&mut self, rd: Register, rs1: Register, imm: i32) -> Self::Output;
+    fn lbu(&mut self, rd: Register, rs1: Register, imm: i32) -> Self::Output;
+    fn lhu(&mut self, rd: Register, rs1: Register, imm: i32) -> Self::Output;
+    fn lwu(&mut self, rd: Register, rs1: Register, imm: i32) -> Self::Output;
+
+    // Store
+    fn sb(&mut self, rs1: Register, rs2: Register, imm: i32) -> Self::Output;
+    fn sh(&mut self, rs1: Register, rs2: Register, imm: i32) -> Self::Output;
+    fn sw(&mut self, rs1: Register, rs2: Register, imm: i32) -> Self::Output;
+    fn sd(&mut self, rs1: Register, rs2: Register, imm: i32) -> Self::Output;
+
+    // Fence
+    fn fence(&mut self, rd: Register, rs1: Register, imm: i32) -> Self::Output;
+    fn fence_i(&mut self) -> Self::Output;
+
+    // OP-imm
+    fn addi(&mut self, rd: Register, rs1: Register, imm: i32) -> Self::Output;
+    fn slti(&mut self, rd: Register, rs1: Register, imm: i32) -> Self::Output;
+    fn sltiu(&mut self, rd: Register, rs1: Register, imm: i32) -> Self::Output;
+    fn xori(&mut self, rd: Register, rs1: Register, imm: i32) -> Self::Output;
+    fn ori(&mut self, rd: Register, rs1: Register, imm: i32) -> Self::Output;
+    fn andi(&mut self, rd: Register, rs1: Register, imm: i32) -> Self::Output;
+    fn slli(&mut self, rd: Register, rs1: Register, shamt: i32) -> Self::Output;
+    fn srli(&mut self, rd: Register, rs1: Register, shamt: i32) -> Self::Output;
+    fn srai(&mut self, rd: Register, rs1: Register, shamt: i32) -> Self::Output;
+
+    // OP-imm32
+    fn addiw(&mut self, rd: Register, rs1: Register, imm: i32) -> Self::Output;
+    fn slliw(&mut self, rd: Register, rs1: Register, shamt: i32) -> Self::Output;
+    fn srliw(&mut self, rd: Register, rs1: Register, shamt: i32) -> Self::Output;
+    fn sraiw(&mut self, rd: Register, rs1: Register, shamt: i32) -> Self::Output;
+
+    // OP
+    fn add(&mut self, rd: Register, rs1: Register, rs2: Register) -> Self::Output;
+    fn sub(&mut self, rd: Register, rs1: Register, rs2: Register) -> Self::Output;
+    fn sll(&mut self, rd: Register, rs1: Register, rs2: Register) -> Self::Output;
+    fn slt(&mut self, rd: Register, rs1: Register, rs2: Register) -> Self::Output;
+    fn sltu(&mut self, rd: Register, rs1: Register, rs2: Register) -> Self::Output;
+    fn xor(&mut self, rd: Register, rs1: Register, rs2: Register) -> Self::Output;
+    fn srl(&mut self, rd: Register, rs1: Register, rs2: Register) -> Self::Output;
+    fn sra(&mut self, rd: Register, rs1: Register, rs2: Register) -> Self::Output;
+    fn or(&mut self, rd: Register, rs1: Register, rs2: Register) -> Self::Output;
+    fn and(&mut self, rd: Register, rs1: Register, rs2: Register) -> Self::Output;
+    fn mul(&mut self, rd: Register, rs1: Register, rs2: Register) -> Self::Output;
+    fn mulh(&mut self, rd: Register, rs1: Register, rs2: Register) -> Self::Output;
+    fn mulhsu(&mut self, rd: Register, rs1: Register, rs2: Register) -> Self::Output;
+    fn mulhu(&mut self, rd: Register, rs1: Register, rs2: Register) -> Self::Output;
+    fn div(&mut self, rd: Register, rs1: Register, rs2: Register) -> Self::Output;
+    fn divu(&mut self, rd: Register, rs1: Register, rs2: Register) -> Self::Output;
+    fn rem(&mut self, rd: Register, rs1: Register, rs2: Register) -> Self::Output;
+    fn remu(&mut self, rd: Register, rs1: Register, rs2: Register) -> Self::Output;
+
+    // OP32
+    fn addw(&mut self, rd: Register, rs1: Register, rs2: Register) -> Self::Output;
+    fn subw(&mut self, rd: Register, rs1: Register, rs2: Register) -> Self::Output;
+    fn sllw(&mut self, rd: Register, rs1: Register, rs2: Register) -> Self::Output;
+    fn srlw(&mut self, rd: Register, rs1: Register, rs2: Register) -> Self::Output;
+    fn sraw(&mut self, rd: Register, rs1: Register, rs2: Register) -> Self::Output;
+    fn mulw(&mut self, rd: Register, rs1: Register, rs2: Register) -> Self::Output;
+    fn divw(&mut self, rd: Register, rs1: Register, rs2: Register) -> Self::Output;
+    fn divuw(&mut self, rd: Register, rs1: Register, rs2: Register) -> Self::Output;
+    fn remw(&mut self, rd: Register, rs1: Register, rs2: Register) -> Self::Output;
+    fn remuw(&mut self, rd: Register, rs1: Register, rs2: Register) -> Self::Output;
+
+    // System
+    fn ecall(&mut self) -> Self::Output;
+    fn ebreak(&mut self) -> Self::Output;
+
+    // Amo
+    fn lrw(&mut self, rd: Register, rs1: Register, rs2: Register) -> Self::Output;
+    fn scw(&mut self, rd: Register, rs1: Register, rs2: Register) -> Self::Output;
+    fn amoswapw(&mut self, rd: Register, rs1: Register, rs2: Register) -> Self::Output;
+    fn amoaddw(&mut self, rd: Register, rs1: Register, rs2: Register) -> Self::Output;
+    fn amoxorw(&mut self, rd: Register, rs1: Register, rs2: Register) -> Self::Output;
+    fn amoandw(&mut self, rd: Register, rs1: Register, rs2: Register) -> Self::Output;
+    fn amoorw(&mut self, rd: Register, rs1: Register, rs2: Register) -> Self::Output;
+    fn amominw(&mut self, rd: Register, rs1: Register, rs2: Register) -> Self::Output;
+    fn amomaxw(&mut self, rd: Register, rs1: Register, rs2: Register) -> Self::Output;
+    fn amominuw(&mut self, rd: Register, rs1: Register, rs2: Register) -> Self::Output;
+    fn amomaxuw(&mut self, rd: Register, rs1: Register, rs2: Register) -> Self::Output;
+    fn lrd(&mut self, rd: Register, rs1: Register, rs2: Register) -> Self::Output;
+    fn scd(&mut self, rd: Register, rs1: Register, rs2: Register) -> Self::Output;
+    fn amoswapd(&mut self, rd: Register, rs1: Register, rs2: Register) -> Self::Output;
+    fn amoaddd(&mut self, rd: Register, rs1: Register, rs2: Register) -> Self::Output;
+    fn amoxord(&mut self, rd: Register, rs1: Register, rs2: Register) -> Self::Output;
+    fn amoandd(&mut self, rd: Register, rs1: Register, rs2: Register) -> Self::Output;
+    fn amoord(&mut self, rd: Register, rs1: Register, rs2: Register) -> Self::Output;
+    fn amomind(&mut self, rd: Register, rs1: Register, rs2: Register) -> Self::Output;
+    fn amomaxd(&mut self, rd: Register, rs1: Register, rs2: Register) -> Self::Output;
+    fn amominud(&mut self, rd: Register, rs1: Register, rs2: Register) -> Self::Output;
+    fn amomaxud(&mut self, rd: Register, rs1: Register, rs2: Register) -> Self::Output;
+}
+
+/// The visitor behind [`decode`](super::decode), which wraps the word it is created with into
+/// the variant of the visited instruction and ignores the fields.
+pub(super) struct Builder(pub(super) u32);
+
+macro_rules! build {
+    ($($method:ident($($arg:ty),*) => $variant:ident($format:ident);)*) => {
+        impl InstructionVisitor for Builder {
+            type Output = Instruction;
+
+            $(
+                #[inline(always)]
+                fn $method(&mut self, $(_: $arg),*) -> Instruction {
+                    Instruction::$variant($format(self.0))
+                }
+            )*
+        }
+    };
+}
+
+build! {
+    lui(Register, i32) => Lui(UType);
+    auipc(Register, i32) => Auipc(UType);
+    jal(Register, i32) => Jal(JType);
+    jalr(Register, Register, i32) => Jalr(IType);
+    beq(Register, Register, i32) => Beq(BType);
+    bne(Register, Register, i32) => Bne(BType);
+    blt(Register, Register, i32) => Blt(BType);
+    bge(Register, Register, i32) => Bge(BType);
+    bltu(Register, Register, i32) => Bltu(BType);
+    bgeu(Register, Register, i32) => Bgeu(BType);
+    lb(Register, Register, i32) => Lb(IType);
+    lh(Register, Register, i32) => Lh(IType);
+    lw(Register, Register, i32) => Lw(IType);
+    ld(Register, Register, i32) => Ld(IType);
+    lbu(Register, Register, i32) => Lbu(IType);
+    lhu(Register, Register, i32) => Lhu(IType);
+    lwu(Register, Register, i32) => Lwu(IType);
+    sb(Register, Register, i32) => Sb(SType);
+    sh(Register, Register, i32) => Sh(SType);
+    sw(Register, Register, i32) => Sw(SType);
+    sd(Register, Register, i32) => Sd(SType);
+    fence(Register, Register, i32) => Fence(IType);
+    fence_i() => FenceI(IType);
+    addi(Register, Register, i32) => Addi(IType);
+    slti(Register, Register, i32) => Slti(IType);
+    sltiu(Register, Register, i32) => Sltiu(IType);
+    xori(Register, Register, i32) => Xori(IType);
+    ori(Register, Register, i32) => Ori(IType);
+    andi(Register, Register, i32) => Andi(IType);
+    slli(Register, Register, i32) => Slli(IType);
+    srli(Register, Register, i32) => Srli(IType);
+    srai(Register, Register, i32) => Srai(IType);
+    addiw(Register, Register, i32) => Addiw(IType);
+    slliw(Register, Register, i32) => Slliw(IType);
+    srliw(Register, Register, i32) => Srliw(IType);
+    sraiw(Register, Register, i32) => Sraiw(IType);
+    add(Register, Register, Register) => Add(RType);
+    sub(Register, Register, Register) => Sub(RType);
+    sll(Register, Register, Register) => Sll(RType);
+    slt(Register, Register, Register) => Slt(RType);
+    sltu(Register, Register, Register) => Sltu(RType);
+    xor(Register, Register, Register) => Xor(RType);
+    srl(Register, Register, Register) => Srl(RType);
+    sra(Register, Register, Register) => Sra(RType);
+    or(Register, Register, Register) => Or(RType);
+    and(Register, Register, Register) => And(RType);
+    mul(Register, Register, Register) => Mul(RType);
+    mulh(Register, Register, Register) => Mulh(RType);
+    mulhsu(Register, Register, Register) => Mulhsu(RType);
+    mulhu(Register, Register, Register) => Mulhu(RType);
+    div(Register, Register, Register) => Div(RType);
+    divu(Register, Register, Register) => Divu(RType);
+    rem(Register, Register, Register) => Rem(RType);
+    remu(Register, Register, Register) => Remu(RType);
+    addw(Register, Register, Register) => Addw(RType);
+    subw(Register, Register, Register) => Subw(RType);
+    sllw(Register, Register, Register) => Sllw(RType);
+    srlw(Register, Register, Register) => Srlw(RType);
+    sraw(Register, Register, Register) => Sraw(RType);
+    mulw(Register, Register, Register) => Mulw(RType);
+    divw(Register, Register, Register) => Divw(RType);
+    divuw(Register, Register, Register) => Divuw(RType);
+    remw(Register, Register, Register) => Remw(RType);
+    remuw(Register, Register, Register) => Remuw(RType);
+    ecall() => Ecall(IType);
+    ebreak() => Ebreak(IType);
+    lrw(Register, Register, Register) => Lrw(RType);
+    scw(Register, Register, Register) => Scw(RType);
+    amoswapw(Register, Register, Register) => Amoswapw(RType);
+    amoaddw(Register, Register, Register) => Amoaddw(RType);
+    amoxorw(Register, Register, Register) => Amoxorw(RType);
+    amoandw(Register, Register, Register) => Amoandw(RType);
+    amoorw(Register, Register, Register) => Amoorw(RType);
+    amominw(Register, Register, Register) => Amominw(RType);
+    amomaxw(Register, Register, Register) => Amomaxw(RType);
+    amominuw(Register, Register, Register) => Amominuw(RType);
+    amomaxuw(Register, Register, Register) => Amomaxuw(RType);
+    lrd(Register, Register, Register) => Lrd(RType);
+    scd(Register, Register, Register) => Scd(RType);
+    amoswapd(Register, Register, Register) => Amoswapd(RType);
+    amoaddd(Register, Register, Register) => Amoaddd(RType);
+    amoxord(Register, Register, Register) => Amoxord(RType);
+    amoandd(Register, Register, Register) => Amoandd(RType);
+    amoord(Register, Register, Register) => Amoord(RType);
+    amomind(Register, Register, Register) => Amomind(RType);
+    amomaxd(Register, Register, Register) => Amomaxd(RType);
+    amominud(Register, Register, Register) => Amominud(RType);
+    amomaxud(Register, Register, Register) => Amomaxud(RType);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decode_with;
+
+    /// Constructs every visited instruction again from its fields.
+    struct Rebuild;
+
+    macro_rules! rebuild {
+        ($($method:ident($($arg:ident: $ty:ty),*) => $constructor:ident($($field:ident),*);)*) => {
+            impl InstructionVisitor for Rebuild {
+                type Output = Instruction;
+
+                $(
+                    fn $method(&mut self, $($arg: $ty),*) -> Instruction {
+                        Instruction::$constructor($($field),*)
+                    }
+                )*
+            }
+        };
+    }
+
+    rebuild! {
+        lui(rd: Register, imm: i32) => new_lui(rd, imm);
+        auipc(rd: Register, imm: i32) => new_auipc(rd, imm);
+        jal(rd: Register, imm: i32) => new_jal(rd, imm);
+        jalr(rd: Register, rs1: Register, imm: i32) => new_jalr(rd, rs1, imm);
+        beq(rs1: Register, rs2: Register, imm: i32) => new_beq(rs1, rs2, imm);
+        bne(rs1: Register, rs2: Register, imm: i32) => new_bne(rs1, rs2, imm);
+        blt(rs1: Register, rs2: Register, imm: i32) => new_blt(rs1, rs2, imm);
+        bge(rs1: Register, rs2: Register, imm: i32) => new_bge(rs1, rs2, imm);
+        bltu(rs1: Register, rs2: Register, imm: i32) => new_bltu(rs1, rs2, imm);
+        bgeu(rs1: Register, rs2: Register, imm: i32) => new_bgeu(rs1, rs2, imm);
+        lb(rd: Register, rs1: Register, imm: i32) => new_lb(rd, rs1, imm);
+        lh(rd: Register, rs1: Register, imm: i32) => new_lh(rd, rs1, imm);
+        lw(rd: Register, rs1: Register, imm: i32) => new_lw(rd, rs1, imm);
+        ld(rd: Register, rs1: Register, imm: i32) => new_ld(rd, rs1, imm);
+        lbu(rd: Register, rs1: Register, imm: i32) => new_lbu(rd, rs1, imm);
+        lhu(rd: Register, rs1: Register, imm: i32) => new_lhu(rd, rs1, imm);
+        lwu(rd: Register, rs1: Register, imm: i32) => new_lwu(rd, rs1, imm);
+        sb(rs1: Register, rs2: Register, imm: i32) => new_sb(rs1, rs2, imm);
+        sh(rs1: Register, rs2: Register, imm: i32) => new_sh(rs1, rs2, imm);
+        sw(rs1: Register, rs2: Register, imm: i32) => new_sw(rs1, rs2, imm);
+        sd(rs1: Register, rs2: Register, imm: i32) => new_sd(rs1, rs2, imm);
+        fence(rd: Register, rs1: Register, imm: i32) => new_fence(rd, rs1, imm);
+        fence_i() => new_fence_i();
+        addi(rd: Register, rs1: Register, imm: i32) => new_addi(rd, rs1, imm);
+        slti(rd: Register, rs1: Register, imm: i32) => new_slti(rd, rs1, imm);
+        sltiu(rd: Register, rs1: Register, imm: i32) => new_sltiu(rd, rs1, imm);
+        xori(rd: Register, rs1: Register, imm: i32) => new_xori(rd, rs1, imm);
+        ori(rd: Register, rs1: Register, imm: i32) => new_ori(rd, rs1, imm);
+        andi(rd: Register, rs1: Register, imm: i32) => new_andi(rd, rs1, imm);
+        slli(rd: Register, rs1: Register, shamt: i32) => new_slli(rd, rs1, shamt);
+        srli(rd: Register, rs1: Register, shamt: i32) => new_srli(rd, rs1, shamt);
+        srai(rd: Register, rs1: Register, shamt: i32) => new_srai(rd, rs1, shamt);
+        addiw(rd: Register, rs1: Register, imm: i32) => new_addiw(rd, rs1, imm);
+        slliw(rd: Register, rs1: Register, shamt: i32) => new_slliw(rd, rs1, shamt);
+        srliw(rd: Register, rs1: Register, shamt: i32) => new_srliw(rd, rs1, shamt);
+        sraiw(rd: Register, rs1: Register, shamt: i32) => new_sraiw(rd, rs1, shamt);
+        add(rd: Register, rs1: Register, rs2: Register) => new_add(rd, rs1, rs2);
+        sub(rd: Register, rs1: Register, rs2: Register) => new_sub(rd, rs1, rs2);
+        sll(rd: Register, rs1: Register, rs2: Register) => new_sll(rd, rs1, rs2);
+        slt(rd: Register, rs1: Register, rs2: Register) => new_slt(rd, rs1, rs2);
+        sltu(rd: Register, rs1: Register, rs2: Register) => new_sltu(rd, rs1, rs2);
+        xor(rd: Register, rs1: Register, rs2: Register) => new_xor(rd, rs1, rs2);
+        srl(rd: Register, rs1: Register, rs2: Register) => new_srl(rd, rs1, rs2);
+        sra(rd: Register, rs1: Register, rs2: Register) => new_sra(rd, rs1, rs2);
+        or(rd: Register, rs1: Register, rs2: Register) => new_or(rd, rs1, rs2);
+        and(rd: Register, rs1: Register, rs2: Register) => new_and(rd, rs1, rs2);
+        mul(rd: Register, rs1: Register, rs2: Register) => new_mul(rd, rs1, rs2);
+        mulh(rd: Register, rs1: Register, rs2: Register) => new_mulh(rd, rs1, rs2);
+        mulhsu(rd: Register, rs1: Register, rs2: Register) => new_mulhsu(rd, rs1, rs2);
+        mulhu(rd: Register, rs1: Register, rs2: Register) => new_mulhu(rd, rs1, rs2);
+        div(rd: Register, rs1: Register, rs2: Register) => new_div(rd, rs1, rs2);
+        divu(rd: Register, rs1: Register, rs2: Register) => new_divu(rd, rs1, rs2);
+        rem(rd: Register, rs1: Register, rs2: Register) => new_rem(rd, rs1, rs2);
+        remu(rd: Register, rs1: Register, rs2: Register) => new_remu(rd, rs1, rs2);
+        addw(rd: Register, rs1: Register, rs2: Register) => new_addw(rd, rs1, rs2);
+        subw(rd: Register, rs1: Register, rs2: Register) => new_subw(rd, rs1, rs2);
+        sllw(rd: Register, rs1: Register, rs2: Register) => new_sllw(rd, rs1, rs2);
+        srlw(rd: Register, rs1: Register, rs2: Register) => new_srlw(rd, rs1, rs2);
+        sraw(rd: Register, rs1: Register, rs2: Register) => new_sraw(rd, rs1, rs2);
+        mulw(rd: Register, rs1: Register, rs2: Register) => new_mulw(rd, rs1, rs2);
+        divw(rd: Register, rs1: Register, rs2: Register) => new_divw(rd, rs1, rs2);
+        divuw(rd: Register, rs1: Register, rs2: Register) => new_divuw(rd, rs1, rs2);
+        remw(rd: Register, rs1: Register, rs2: Register) => new_remw(rd, rs1, rs2);
+        remuw(rd: Register, rs1: Register, rs2: Register) => new_remuw(rd, rs1, rs2);
+        ecall() => new_ecall();
+        ebreak() => new_ebreak();
+        lrw(rd: Register, rs1: Register, rs2: Register) => new_lrw(rd, rs1, rs2);
+        scw(rd: Register, rs1: Register, rs2: Register) => new_scw(rd, rs1, rs2);
+        amoswapw(rd: Register, rs1: Register, rs2: Register) => new_amoswapw(rd, rs1, rs2);
+        amoaddw(rd: Register, rs1: Register, rs2: Register) => new_amoaddw(rd, rs1, rs2);
+        amoxorw(rd: Register, rs1: Register, rs2: Register) => new_amoxorw(rd, rs1, rs2);
+        amoandw(rd: Register, rs1: Register, rs2: Register) => new_amoandw(rd, rs1, rs2);
+        amoorw(rd: Register, rs1: Register, rs2: Register) => new_amoorw(rd, rs1, rs2);
+        amominw(rd: Register, rs1: Register, rs2: Register) => new_amominw(rd, rs1, rs2);
+        amomaxw(rd: Register, rs1: Register, rs2: Register) => new_amomaxw(rd, rs1, rs2);
+        amominuw(rd: Register, rs1: Register, rs2: Register) => new_amominuw(rd, rs1, rs2);
+        amomaxuw(rd: Register, rs1: Register, rs2: Register) => new_amomaxuw(rd, rs1, rs2);
+        lrd(rd: Register, rs1: Register, rs2: Register) => new_lrd(rd, rs1, rs2);
+        scd(rd: Register, rs1: Register, rs2: Register) => new_scd(rd, rs1, rs2);
+        amoswapd(rd: Register, rs1: Register, rs2: Register) => new_amoswapd(rd, rs1, rs2);
+        amoaddd(rd: Register, rs1: Register, rs2: Register) => new_amoaddd(rd, rs1, rs2);
+        amoxord(rd: Register, rs1: Register, rs2: Register) => new_amoxord(rd, rs1, rs2);
+        amoandd(rd: Register, rs1: Register, rs2: Register) => new_amoandd(rd, rs1, rs2);
+        amoord(rd: Register, rs1: Register, rs2: Register) => new_amoord(rd, rs1, rs2);
+        amomind(rd: Register, rs1: Register, rs2: Register) => new_amomind(rd, rs1, rs2);
+        amomaxd(rd: Register, rs1: Register, rs2: Register) => new_amomaxd(rd, rs1, rs2);
+        amominud(rd: Register, rs1: Register, rs2: Register) => new_amominud(rd, rs1, rs2);
+        amomaxud(rd: Register, rs1: Register, rs2: Register) => new_amomaxud(rd, rs1, rs2);
+    }
+
+    #[test]
+    fn fields_of_every_instruction() {
+        let words = [
+            0xfffff7b7, // lui a5, 1048575
+            0x00001097, // auipc ra, 1
+            0xff9ff0ef, // jal ra, -8
+            0x00408067, // jalr zero, 4(ra)
+            0xfe050ae3, // beq a0, zero, -12
+            0x7eb51fe3, // bne a0, a1, 4094
+            0x00944863, // blt s0, s1, 16
+            0x80b55063, // bge a0, a1, -4096
+            0x0062e463, // bltu t0, t1, 8
+            0xfed67fe3, // bgeu a2, a3, -2
+            0xfff10503, // lb a0, -1(sp)
+            0x00251583, // lh a1, 2(a0)
+            0x0045a603, // lw a2, 4(a1)
+            0xff843783, // ld a5, -8(s0)
+            0x7ff34283, // lbu t0, 2047(t1)
+            0x8003d303, // lhu t1, -2048(t2)
+            0x00c96483, // lwu s1, 12(s2)
+            0xfea10fa3, // sb a0, -1(sp)
+            0x00b51123, // sh a1, 2(a0)
+            0x00c5a223, // sw a2, 4(a1)
+            0xfea13c23, // sd a0, -8(sp)
+            0x0310000f, // fence rw, w
+            0x0000100f, // fence.i
+            0xff010113, // addi sp, sp, -16
+            0xffb5a513, // slti a0, a1, -5
+            0x0015b513, // sltiu a0, a1, 1
+            0xfff5c513, // xori a0, a1, -1
+            0x0ff36293, // ori t0, t1, 255
+            0x00f7f793, // andi a5, a5, 15
+            0x03f51513, // slli a0, a0, 63
+            0x0205d593, // srli a1, a1, 32
+            0x43f5d513, // srai a0, a1, 63
+            0xfff5051b, // addiw a0, a0, -1
+            0x01f5151b, // slliw a0, a0, 31
+            0x0016559b, // srliw a1, a2, 1
+            0x4117569b, // sraiw a3, a4, 17
+            0x00c58633, // add a2, a1, a2
+            0x40c58533, // sub a0, a1, a2
+            0x007312b3, // sll t0, t1, t2
+            0x0124a433, // slt s0, s1, s2
+            0x00b03533, // sltu a0, zero, a1
+            0x00f746b3, // xor a3, a4, a5
+            0x0128d833, // srl a6, a7, s2
+            0x415a59b3, // sra s3, s4, s5
+            0x018beb33, // or s6, s7, s8
+            0x01bd7cb3, // and s9, s10, s11
+            0x03ee8e33, // mul t3, t4, t5
+            0x02b51fb3, // mulh t6, a0, a1
+            0x02c5a533, // mulhsu a0, a1, a2
+            0x02c5b533, // mulhu a0, a1, a2
+            0x02c5c533, // div a0, a1, a2
+            0x02c5d533, // divu a0, a1, a2
+            0x02c5e533, // rem a0, a1, a2
+            0x02c5f533, // remu a0, a1, a2
+            0x00c5853b, // addw a0, a1, a2
+            0x40c5853b, // subw a0, a1, a2
+            0x00c5953b, // sllw a0, a1, a2
+            0x00c5d53b, // srlw a0, a1, a2
+            0x40c5d53b, // sraw a0, a1, a2
+            0x02c5853b, // mulw a0, a1, a2
+            0x02c5c53b, // divw a0, a1, a2
+            0x02c5d53b, // divuw a0, a1, a2
+            0x02c5e53b, // remw a0, a1, a2
+            0x02c5f53b, // remuw a0, a1, a2
+            0x00000073, // ecall
+            0x00100073, // ebreak
+            0x1005a52f, // lr.w a0, (a1)
+            0x18c5a52f, // sc.w a0, a2, (a1)
+            0x08c5a52f, // amoswap.w a0, a2, (a1)
+            0x00c5a52f, // amoadd.w a0, a2, (a1)
+            0x20c5a52f, // amoxor.w a0, a2, (a1)
+            0x60c5a52f, // amoand.w a0, a2, (a1)
+            0x40c5a52f, // amoor.w a0, a2, (a1)
+            0x80c5a52f, // amomin.w a0, a2, (a1)
+            0xa0c5a52f, // amomax.w a0, a2, (a1)
+            0xc0c5a52f, // amominu.w a0, a2, (a1)
+            0xe0c5a52f, // amomaxu.w a0, a2, (a1)
+            0x1005b52f, // lr.d a0, (a1)
+            0x18c5b52f, // sc.d a0, a2, (a1)
+            0x08c5b52f, // amoswap.d a0, a2, (a1)
+            0x00c5b52f, // amoadd.d a0, a2, (a1)
+            0x20c5b52f, // amoxor.d a0, a2, (a1)
+            0x60c5b52f, // amoand.d a0, a2, (a1)
+            0x40c5b52f, // amoor.d a0, a2, (a1)
+            0x80c5b52f, // amomin.d a0, a2, (a1)
+            0xa0c5b52f, // amomax.d a0, a2, (a1)
+            0xc0c5b52f, // amominu.d a0, a2, (a1)
+            0xe0c5b52f, // amomaxu.d a0, a2, (a1)
+        ];
+
+        for word in words.iter() {
+            let instruction = decode_with(*word, &mut Rebuild).unwrap();
+            let decoded = crate::decode(*word).unwrap();
+
+            assert_eq!(instruction.rd(), decoded.rd(), "{:?}", decoded);
+            assert_eq!(instruction.rs1(), decoded.rs1(), "{:?}", decoded);
+            assert_eq!(instruction.rs2(), decoded.rs2(), "{:?}", decoded);
+
+            // the constructors of atomics do not encode funct5 in its place
+            if !decoded.is_atomic() {
+                assert_eq!(u32::from(instruction), *word, "{:?}", decoded);
+            }
+        }
+    }
+
+    #[test]
+    fn compressed_and_rejected() {
+        // c.addi sp,-16
+        assert_eq!(
+            decode_with(0x1141, &mut Rebuild),
+            Ok(Instruction::new_addi(Register::Sp, Register::Sp, -16))
+        );
+        assert_eq!(
+            decode_with(0x0000_0000, &mut Rebuild),
+            Err(crate::DecodingError::Illegal(0))
+        );
+        assert_eq!(
+            decode_with(0x0000_000b, &mut Rebuild),
+            Err(crate::DecodingError::Custom(0x0000_000b))
+        );
+    }
+}
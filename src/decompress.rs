@@ -1,24 +1,68 @@
+mod compress;
 mod detail;
+mod instruction;
 mod util;
 
-use crate::DecodingError;
+use crate::{DecodingError, Instruction};
 use detail::*;
 
 type DecompressionResult = Result<u32, DecodingError>;
 
+/// Decompress a compressed instruction to the corresponding 32-bit instruction.
+///
+/// Halfwords with both low bits set are the beginning of a 32-bit instruction and thus
+/// [`DecodingError::Illegal`].
+pub fn decompress(i: u16) -> DecompressionResult {
+    match i & 0b11 {
+        0b00 => decompress_q0(i),
+        0b01 => decompress_q1(i),
+        0b10 => decompress_q2(i),
+        _ => Err(DecodingError::Illegal(i.into())),
+    }
+}
+
+/// Decompress a compressed instruction directly to an [`Instruction`], which is equal to the one
+/// obtained by decoding the 32-bit instruction returned by [`decompress`].
+///
+/// The instruction is constructed from the fields of the compressed encoding, without molding a
+/// 32-bit word and decoding it again.
+pub fn decompress_to_instruction(i: u16) -> Result<Instruction, DecodingError> {
+    match i & 0b11 {
+        0b00 => instruction::quadrant0(i),
+        0b01 => instruction::quadrant1(i),
+        0b10 => instruction::quadrant2(i),
+        _ => Err(DecodingError::Illegal(i.into())),
+    }
+}
+
+/// Compress a 32-bit instruction, the inverse of [`decompress`]:
+/// `decompress(compress(i).unwrap()) == Ok(i)`.
+///
+/// Returns `None` for instructions without a compressed form, including those whose immediate
+/// or registers do not fit into one. HINTs are never produced.
+pub fn compress(i: u32) -> Option<u16> {
+    if i & 0b11 != 0b11 {
+        return None;
+    }
+
+    let instruction = crate::decode(i).ok()?;
+
+    compress::candidate(instruction).filter(|c| decompress(*c) == Ok(i))
+}
+
 /// Decompress compressed instructions from quadrant zero to the corresponding 32-bit instruction.
 pub fn decompress_q0(i: u16) -> DecompressionResult {
     if i == 0 {
-        return Err(DecodingError::Illegal);
+        return Err(DecodingError::Illegal(i.into()));
     }
 
     match (i >> 13) & 0b111 {
         0b000 => decompress_addi4spn(i),
-        0b001 => Err(DecodingError::Unimplemented), // C.LFD
+        0b001 => Err(DecodingError::Unsupported(i.into())), // C.LFD
         0b010 => decompress_load(i, CiInstr::Lw),
         0b011 => decompress_load(i, CiInstr::Ld),
-        0b100 => Err(DecodingError::Reserved),
-        0b101 => Err(DecodingError::Unimplemented), // C.FSD
+        0b100 => Err(DecodingError::Reserved(i.into())),
+        0b101 => Err(DecodingError::Unsupported(i.into())), // C.FSD
         0b110 => decompress_store(i, CsInstr::Sw),
         0b111 => decompress_store(i, CsInstr::Sd),
         _ => unreachable!(),
@@ -44,11 +88,11 @@ pub fn decompress_q1(i: u16) -> DecompressionResult {
 pub fn decompress_q2(i: u16) -> DecompressionResult {
     match (i >> 13) & 0b111 {
         0b000 => decompress_slli(i),
-        0b001 => Err(DecodingError::Unimplemented), // C.FLSDP
+        0b001 => Err(DecodingError::Unsupported(i.into())), // C.FLSDP
         0b010 => decompress_load_sp(i, CiInstr::Lw),
         0b011 => decompress_load_sp(i, CiInstr::Ld),
         0b100 => decompress_jr_mv_add(i),
-        0b101 => Err(DecodingError::Unimplemented), // C.FSDSP
+        0b101 => Err(DecodingError::Unsupported(i.into())), // C.FSDSP
         0b110 => decompress_store_sp(i, CsInstr::Sw),
         0b111 => decompress_store_sp(i, CsInstr::Sd),
         _ => unreachable!(),
@@ -57,11 +101,213 @@ pub fn decompress_q2(i: u16) -> DecompressionResult {
 
 #[cfg(test)]
 mod tests {
-    use crate::{decode, types::*, Instruction::*};
+    use crate::{decode, types::*, DecodingError, Instruction::*};
 
     // The bulk of the instructions were obtained by compiling selfie with
     // an rv64imc enabled gcc compiler. Examples for edge cases were created by hand.
 
+    #[test]
+    fn test_decompress() {
+        use super::decompress;
+
+        assert_eq!(decompress(0x002c), Ok(0x00810593)); // addi a1, sp, 8
+        assert_eq!(decompress(0x17e1), Ok(0xff878793)); // addi a5, a5, -8
+        assert_eq!(decompress(0x078e), Ok(0x00379793)); // slli a5, a5, 0x3
+        assert_eq!(decompress(0x8513), Err(DecodingError::Illegal(0x8513))); // lower half of addi
+    }
+
+    #[test]
+    fn test_decompress_table() {
+        use super::decompress;
+
+        // every implemented instruction, with an extreme immediate and its canonical expansion
+        // from the RISC-V spec, as assembled by llvm-mc
+        let table = [
+            (0x1fe8, 0x3fc10513), // c.addi4spn a0, sp, 1020
+            (0x5ff8, 0x07c7a703), // c.lw a4, 124(a5)
+            (0x7ff8, 0x0f87b703), // c.ld a4, 248(a5)
+            (0xdff8, 0x06e7ae23), // c.sw a4, 124(a5)
+            (0xfff8, 0x0ee7bc23), // c.sd a4, 248(a5)
+            (0x0001, 0x00000013), // c.nop
+            (0x1501, 0xfe050513), // c.addi a0, -32
+            (0x257d, 0x01f5051b), // c.addiw a0, 31
+            (0x5781, 0xfe000793), // c.li a5, -32
+            (0x7101, 0xe0010113), // c.addi16sp sp, -512
+            (0x7781, 0xfffe07b7), // c.lui a5, 0xfffe0
+            (0x917d, 0x03f55513), // c.srli a0, 63
+            (0x8505, 0x40155513), // c.srai a0, 1
+            (0x997d, 0xfff57513), // c.andi a0, -1
+            (0x8c1d, 0x40f40433), // c.sub s0, a5
+            (0x8c3d, 0x00f44433), // c.xor s0, a5
+            (0x8c5d, 0x00f46433), // c.or s0, a5
+            (0x8c7d, 0x00f47433), // c.and s0, a5
+            (0x9c1d, 0x40f4043b), // c.subw s0, a5
+            (0x9c3d, 0x00f4043b), // c.addw s0, a5
+            (0xb001, 0x801ff06f), // c.j -2048
+            (0xd101, 0xf00500e3), // c.beqz a0, -256
+            (0xed7d, 0x0e051f63), // c.bnez a0, 254
+            (0x157e, 0x03f51513), // c.slli a0, 63
+            (0x557e, 0x0fc12503), // c.lwsp a0, 252(sp)
+            (0x757e, 0x1f813503), // c.ldsp a0, 504(sp)
+            (0x8782, 0x00078067), // c.jr a5
+            (0x853e, 0x00f00533), // c.mv a0, a5
+            (0x9002, 0x00100073), // c.ebreak
+            (0x9782, 0x000780e7), // c.jalr a5
+            (0x97ba, 0x00e787b3), // c.add a5, a4
+            (0xdfbe, 0x0ef12e23), // c.swsp a5, 252(sp)
+            (0xffbe, 0x1ef13c23), // c.sdsp a5, 504(sp)
+        ];
+
+        for (compressed, expanded) in table.iter() {
+            assert_eq!(
+                decompress(*compressed),
+                Ok(*expanded),
+                "{:#06x}",
+                compressed
+            );
+        }
+    }
+
+    #[test]
+    fn test_compress() {
+        use super::compress;
+
+        assert_eq!(compress(0x00810593), Some(0x002c)); // addi a1, sp, 8
+        assert_eq!(compress(0xff878793), Some(0x17e1)); // addi a5, a5, -8
+        assert_eq!(compress(0x00379793), Some(0x078e)); // slli a5, a5, 0x3
+        assert_eq!(compress(0x00012503), Some(0x4502)); // lw a0, 0(sp)
+        assert_eq!(compress(0x00113c23), Some(0xec06)); // sd ra, 24(sp)
+        assert_eq!(compress(0x00100073), Some(0x9002)); // ebreak
+        assert_eq!(compress(0x00000013), Some(0x0001)); // nop
+
+        assert_eq!(compress(0x02b50533), None); // mul a0, a0, a1
+        assert_eq!(compress(0x7ff78793), None); // addi a5, a5, 2047
+        assert_eq!(compress(0x00c58633), None); // add a2, a1, a2
+        assert_eq!(compress(0x00000793), Some(0x4781)); // li a5, 0
+        assert_eq!(compress(0x00500013), None); // li zero, 5 is a HINT
+        assert_eq!(compress(0x17e1), None); // compressed already
+    }
+
+    #[test]
+    fn test_compress_round_trip() {
+        use super::{compress, decompress};
+        use crate::{Instruction, Register::Zero};
+
+        // HINTs share their expansion with regular instructions or have none of their own
+        let is_hint = |i: Instruction| match i {
+            Addi(_) if i == Instruction::new_nop() => false,
+            Addi(i) | Slli(i) | Srli(i) | Srai(i) => i.rd() == Zero || i.imm() & 0b11_1111 == 0,
+            Lui(u) => u.rd() == Zero,
+            Add(r) => r.rd() == Zero,
+            _ => false,
+        };
+
+        for c in 0..=u16::MAX {
+            if let Ok(x) = decompress(c) {
+                match compress(x) {
+                    Some(c) => assert_eq!(decompress(c), Ok(x), "{:#06x}", c),
+                    None => assert!(is_hint(decode(x).unwrap()), "{:#06x}", c),
+                }
+            }
+        }
+
+        // random words only compress if they round-trip
+        for x in crate::test_rng::Rng::new().words().take(1_000_000) {
+            if let Some(c) = compress(x) {
+                assert_eq!(decompress(c), Ok(x), "{:#010x}", x);
+            }
+        }
+    }
+
+    #[test]
+    fn test_no_panics() {
+        use super::decompress;
+
+        for i in 0..=u16::MAX {
+            if let Ok(expanded) = decompress(i) {
+                let _ = decode(expanded);
+            }
+        }
+
+        assert_eq!(decode(0x4001), Err(DecodingError::Reserved(0x4001))); // c.li zero, 0
+        assert_eq!(decode(0x4002), Err(DecodingError::Reserved(0x4002))); // c.lwsp zero, 0(sp)
+        assert_eq!(decode(0x0000), Err(DecodingError::Illegal(0x0000))); // all zeros
+        assert_eq!(decode(0x6101), Err(DecodingError::Reserved(0x6101))); // c.addi16sp sp, 0
+        assert_eq!(decode(0x6781), Err(DecodingError::Reserved(0x6781))); // c.lui a5, 0
+        assert_eq!(decode(0x6005), Err(DecodingError::Reserved(0x6005))); // c.lui zero, 0x1
+        assert_eq!(decode(0x8002), Err(DecodingError::Reserved(0x8002))); // c.jr zero
+    }
+
+    #[test]
+    fn test_expansions_decode() {
+        use super::decompress;
+
+        for i in 0..=u16::MAX {
+            if let Ok(expanded) = decompress(i) {
+                assert_eq!(
+                    expanded & 0b11,
+                    0b11,
+                    "{:#06x} expands to {:#010x}",
+                    i,
+                    expanded
+                );
+
+                match decode(expanded) {
+                    Ok(instruction) => assert_eq!(u32::from(instruction), expanded),
+                    Err(e) => panic!("{:#06x} expands to {:#010x}: {:?}", i, expanded, e),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_decompression_paths_agree() {
+        use super::{decompress, decompress_to_instruction};
+
+        // every half-word, as the `decompress` fuzz target checks for its inputs
+        for i in 0..=u16::MAX {
+            assert_eq!(
+                decompress(i).and_then(decode),
+                decompress_to_instruction(i),
+                "{:#06x}",
+                i
+            );
+        }
+    }
+
+    #[test]
+    fn test_only_zero_is_illegal() {
+        use super::{decompress, decompress_to_instruction};
+
+        // the all-zeros half-word is the one illegal compressed instruction, other encodings
+        // without a meaning are reserved, e.g. c.addi4spn with a zero immediate
+        for i in (0..=u16::MAX).filter(|i| i & 0b11 != 0b11) {
+            let illegal = DecodingError::Illegal(i.into());
+
+            assert_eq!(decompress(i).err() == Some(illegal), i == 0, "{:#06x}", i);
+            assert_eq!(
+                decompress_to_instruction(i).err() == Some(illegal),
+                i == 0,
+                "{:#06x}",
+                i
+            );
+        }
+    }
+
+    #[test]
+    fn test_decompress_to_instruction() {
+        use super::decompress_to_instruction;
+
+        assert_eq!(
+            decompress_to_instruction(0x8082),
+            Ok(Jalr(IType(0x00008067))) // ret
+        );
+        assert_eq!(
+            decompress_to_instruction(0x0000),
+            Err(DecodingError::Illegal(0x0000))
+        );
+    }
+
     #[test]
     fn test_quadrant0() {
         // C.ADDI4SPN
@@ -100,6 +346,49 @@ mod tests {
         assert_eq!(decode(0xfffc).unwrap(), Sd(SType(0x0ef7bc23))); // sd a5, 248(a5)
     }
 
+    #[test]
+    fn test_quadrant0_addi4spn_offsets() {
+        use super::decompress_q0;
+
+        assert_eq!(decompress_q0(0x0000), Err(DecodingError::Illegal(0x0000))); // all zeros
+        assert_eq!(decompress_q0(0x0008), Err(DecodingError::Reserved(0x0008))); // c.addi4spn a0, sp, 0
+
+        // C.ADDI4SPN: every bit of the unsigned, scaled immediate
+        assert_eq!(decompress_q0(0x0040), Ok(0x00410413)); // addi s0, sp, 4
+        assert_eq!(decompress_q0(0x003c), Ok(0x00810793)); // addi a5, sp, 8
+        assert_eq!(decompress_q0(0x0808), Ok(0x01010513)); // addi a0, sp, 16
+        assert_eq!(decompress_q0(0x1008), Ok(0x02010513)); // addi a0, sp, 32
+        assert_eq!(decompress_q0(0x0088), Ok(0x04010513)); // addi a0, sp, 64
+        assert_eq!(decompress_q0(0x0108), Ok(0x08010513)); // addi a0, sp, 128
+        assert_eq!(decompress_q0(0x0208), Ok(0x10010513)); // addi a0, sp, 256
+        assert_eq!(decompress_q0(0x0408), Ok(0x20010513)); // addi a0, sp, 512
+        assert_eq!(decompress_q0(0x1fe8), Ok(0x3fc10513)); // addi a0, sp, 1020
+    }
+
+    #[test]
+    fn test_quadrant0_lw_offsets() {
+        use super::decompress_q0;
+
+        // C.LW: every bit of the scaled offset, relative to a base other than sp
+        assert_eq!(decompress_q0(0x43c4), Ok(0x0047a483)); // lw s1, 4(a5)
+        assert_eq!(decompress_q0(0x4784), Ok(0x0087a483)); // lw s1, 8(a5)
+        assert_eq!(decompress_q0(0x4b84), Ok(0x0107a483)); // lw s1, 16(a5)
+        assert_eq!(decompress_q0(0x5384), Ok(0x0207a483)); // lw s1, 32(a5)
+        assert_eq!(decompress_q0(0x43a4), Ok(0x0407a483)); // lw s1, 64(a5)
+    }
+
+    #[test]
+    fn test_quadrant0_sw_offsets() {
+        use super::decompress_q0;
+
+        // C.SW: every bit of the scaled offset, in the same permutation as for C.LW
+        assert_eq!(decompress_q0(0xc3c4), Ok(0x0097a223)); // sw s1, 4(a5)
+        assert_eq!(decompress_q0(0xc784), Ok(0x0097a423)); // sw s1, 8(a5)
+        assert_eq!(decompress_q0(0xcb84), Ok(0x0097a823)); // sw s1, 16(a5)
+        assert_eq!(decompress_q0(0xd384), Ok(0x0297a023)); // sw s1, 32(a5)
+        assert_eq!(decompress_q0(0xc3a4), Ok(0x0497a023)); // sw s1, 64(a5)
+    }
+
     #[test]
     fn test_quadrant1() {
         // C.NOP
@@ -112,6 +401,10 @@ mod tests {
         // C.ADDIW
         assert_eq!(decode(0x37fd).unwrap(), Addiw(IType(0xfff7879b))); // addiw a5, a5, -1
         assert_eq!(decode(0x2705).unwrap(), Addiw(IType(0x0017071b))); // addiw a4, a4, 1
+        assert_eq!(decode(0x3501).unwrap(), Addiw(IType(0xfe05051b))); // addiw a0, a0, -32
+        assert_eq!(decode(0x24fd).unwrap(), Addiw(IType(0x01f4849b))); // addiw s1, s1, 31
+        assert_eq!(decode(0x2f81).unwrap(), Addiw(IType(0x000f8f9b))); // addiw t6, t6, 0
+        assert_eq!(decode(0x2005), Err(DecodingError::Reserved(0x2005))); // rd == zero
 
         // C.LI
         assert_eq!(decode(0x4581).unwrap(), Addi(IType(0x00000593))); // li a1, 0
@@ -120,10 +413,15 @@ mod tests {
         // C.ADDI16SP
         assert_eq!(decode(0x002c).unwrap(), Addi(IType(0x00810593))); // addi a1, sp, 8
         assert_eq!(decode(0x1141).unwrap(), Addi(IType(0xff010113))); // addi sp, sp, -16
+        assert_eq!(decode(0x717d).unwrap(), Addi(IType(0xff010113))); // addi sp, sp, -16
+        assert_eq!(decode(0x617d).unwrap(), Addi(IType(0x1f010113))); // addi sp, sp, 496
+        assert_eq!(decode(0x7101).unwrap(), Addi(IType(0xe0010113))); // addi sp, sp, -512
 
         // C.LUI
         assert_eq!(decode(0x6785).unwrap(), Lui(UType(0x000017b7))); // lui a5, 0x1
         assert_eq!(decode(0x77fd).unwrap(), Lui(UType(0xfffff7b7))); // lui a5, 0xfffff
+        assert_eq!(decode(0x67fd).unwrap(), Lui(UType(0x0001f7b7))); // lui a5, 0x1f
+        assert_eq!(decode(0x7781).unwrap(), Lui(UType(0xfffe07b7))); // lui a5, 0xfffe0
 
         // ALU has a separate test-suite
 
@@ -159,7 +457,8 @@ mod tests {
         // C.SUB
         assert_eq!(decode(0x8e09).unwrap(), Sub(RType(0x40a60633))); // sub a2, a2, a0
 
-        // C.XOR unimplemented
+        // C.XOR
+        assert_eq!(decode(0x8f3d).unwrap(), Xor(RType(0x00f74733))); // xor a4, a4, a5
 
         // C.OR
         assert_eq!(decode(0x8f5d).unwrap(), Or(RType(0x00f76733))); // or a4, a4, a5
@@ -172,6 +471,155 @@ mod tests {
 
         // C.ADDW
         assert_eq!(decode(0x9f21).unwrap(), Addw(RType(0x0087073b))); // addw a4, a4, s0
+
+        // C.SUBW and C.ADDW with their remaining funct2 values are reserved
+        assert_eq!(decode(0x9f41), Err(DecodingError::Reserved(0x9f41)));
+        assert_eq!(decode(0x9f61), Err(DecodingError::Reserved(0x9f61)));
+    }
+
+    #[test]
+    fn test_quadrant1_shift_and_mask() {
+        use super::decompress_q1;
+
+        // bit 12 holds shamt[5] of C.SRLI and C.SRAI and the sign of the C.ANDI immediate
+        assert_eq!(decompress_q1(0x9001), Ok(0x02045413)); // srli s0, s0, 32
+        assert_eq!(decompress_q1(0x9401), Ok(0x42045413)); // srai s0, s0, 32
+        assert_eq!(decompress_q1(0x987d), Ok(0xfff47413)); // andi s0, s0, -1
+    }
+
+    #[test]
+    fn test_quadrant1_jump_offsets() {
+        use super::decompress_q1;
+
+        // C.J: every bit of the offset and both ends of its range
+        assert_eq!(decompress_q1(0xa009), Ok(0x0020006f)); // j 2
+        assert_eq!(decompress_q1(0xa011), Ok(0x0040006f)); // j 4
+        assert_eq!(decompress_q1(0xa021), Ok(0x0080006f)); // j 8
+        assert_eq!(decompress_q1(0xa801), Ok(0x0100006f)); // j 16
+        assert_eq!(decompress_q1(0xa005), Ok(0x0200006f)); // j 32
+        assert_eq!(decompress_q1(0xa081), Ok(0x0400006f)); // j 64
+        assert_eq!(decompress_q1(0xa041), Ok(0x0800006f)); // j 128
+        assert_eq!(decompress_q1(0xa201), Ok(0x1000006f)); // j 256
+        assert_eq!(decompress_q1(0xa401), Ok(0x2000006f)); // j 512
+        assert_eq!(decompress_q1(0xa101), Ok(0x4000006f)); // j 1024
+        assert_eq!(decompress_q1(0xaffd), Ok(0x7fe0006f)); // j 2046
+        assert_eq!(decompress_q1(0xbffd), Ok(0xfffff06f)); // j -2
+        assert_eq!(decompress_q1(0xb001), Ok(0x801ff06f)); // j -2048
+
+        // 0b001 is C.ADDIW on RV64, not C.JAL
+        assert_eq!(decompress_q1(0x2705), Ok(0x0017071b)); // addiw a4, a4, 1
+    }
+
+    #[test]
+    fn test_quadrant1_branch_offsets() {
+        use super::decompress_q1;
+
+        // C.BEQZ and C.BNEZ: every bit of the offset and both ends of its range
+        assert_eq!(decompress_q1(0xc089), Ok(0x00048163)); // beqz s1, 2
+        assert_eq!(decompress_q1(0xc091), Ok(0x00048263)); // beqz s1, 4
+        assert_eq!(decompress_q1(0xc481), Ok(0x00048463)); // beqz s1, 8
+        assert_eq!(decompress_q1(0xc881), Ok(0x00048863)); // beqz s1, 16
+        assert_eq!(decompress_q1(0xc085), Ok(0x02048063)); // beqz s1, 32
+        assert_eq!(decompress_q1(0xc0a1), Ok(0x04048063)); // beqz s1, 64
+        assert_eq!(decompress_q1(0xc0c1), Ok(0x08048063)); // beqz s1, 128
+        assert_eq!(decompress_q1(0xccfd), Ok(0x0e048f63)); // beqz s1, 254
+        assert_eq!(decompress_q1(0xd081), Ok(0xf00480e3)); // beqz s1, -256
+        assert_eq!(decompress_q1(0xdcfd), Ok(0xfe048fe3)); // beqz s1, -2
+        assert_eq!(decompress_q1(0xecfd), Ok(0x0e049f63)); // bnez s1, 254
+        assert_eq!(decompress_q1(0xf081), Ok(0xf00490e3)); // bnez s1, -256
+    }
+
+    #[test]
+    fn test_quadrant1_li_sign_extension() {
+        use super::decompress_q1;
+
+        // bit 5 of the CI immediate is its sign bit
+        assert_eq!(decompress_q1(0x557d), Ok(0xfff00513)); // li a0, -1
+        assert_eq!(decompress_q1(0x5501), Ok(0xfe000513)); // li a0, -32
+        assert_eq!(decompress_q1(0x457d), Ok(0x01f00513)); // li a0, 31
+    }
+
+    #[test]
+    fn test_quadrant1_word_alu_round_trip() {
+        use super::decompress_q1;
+
+        // C.SUBW
+        assert_eq!(decompress_q1(0x9d0d), Ok(0x40b5053b)); // subw a0, a0, a1
+        assert_eq!(decode(0x9d0d).unwrap(), decode(0x40b5053b).unwrap());
+
+        // C.ADDW
+        assert_eq!(decompress_q1(0x9cbd), Ok(0x00f484bb)); // addw s1, s1, a5
+        assert_eq!(decode(0x9cbd).unwrap(), decode(0x00f484bb).unwrap());
+    }
+
+    #[test]
+    fn test_quadrant2_swsp_offsets() {
+        use super::decompress_q2;
+
+        // C.SWSP: every bit of the scaled offset, split across both immediate fields of sw
+        assert_eq!(decompress_q2(0xc22a), Ok(0x00a12223)); // sw a0, 4(sp)
+        assert_eq!(decompress_q2(0xc42a), Ok(0x00a12423)); // sw a0, 8(sp)
+        assert_eq!(decompress_q2(0xc82a), Ok(0x00a12823)); // sw a0, 16(sp)
+        assert_eq!(decompress_q2(0xd02a), Ok(0x02a12023)); // sw a0, 32(sp)
+        assert_eq!(decompress_q2(0xc0aa), Ok(0x04a12023)); // sw a0, 64(sp)
+        assert_eq!(decompress_q2(0xc12a), Ok(0x08a12023)); // sw a0, 128(sp)
+    }
+
+    #[test]
+    fn test_doubleword_offsets() {
+        use super::{decompress, decompress_to_instruction};
+
+        let cases = [
+            (0x6544, 0x0885_3483), // c.ld s1, 136(a0)
+            (0xe07c, 0x0cf4_3023), // c.sd a5, 192(s0)
+            (0x757e, 0x1f81_3503), // c.ldsp a0, 504(sp)
+            (0x6496, 0x1401_3483), // c.ldsp s1, 320(sp)
+            (0xffaa, 0x1ea1_3c23), // c.sdsp a0, 504(sp)
+            (0xe616, 0x1051_3423), // c.sdsp t0, 264(sp)
+        ];
+
+        for (compressed, expanded) in cases.iter() {
+            assert_eq!(
+                decompress(*compressed),
+                Ok(*expanded),
+                "{:#06x}",
+                compressed
+            );
+            assert_eq!(
+                decompress_to_instruction(*compressed),
+                decode(*expanded),
+                "{:#06x}",
+                compressed
+            );
+        }
+    }
+
+    #[test]
+    fn test_quadrant2_slli_shamt() {
+        use super::decompress_q2;
+
+        assert_eq!(decompress_q2(0x0006), Err(DecodingError::Reserved(0x0006))); // c.slli zero, 1
+
+        // C.SLLI: the 6-bit shift amount covers the full RV64 range
+        assert_eq!(decompress_q2(0x0486), Ok(0x00149493)); // slli s1, s1, 1
+        assert_eq!(decompress_q2(0x0ffe), Ok(0x01ff9f93)); // slli t6, t6, 31
+        assert_eq!(decompress_q2(0x1782), Ok(0x02079793)); // slli a5, a5, 32
+        assert_eq!(decompress_q2(0x17fe), Ok(0x03f79793)); // slli a5, a5, 63
+    }
+
+    #[test]
+    fn test_quadrant2_jr_mv_add() {
+        use super::decompress_q2;
+
+        assert_eq!(decompress_q2(0x8002), Err(DecodingError::Reserved(0x8002))); // c.jr zero
+        assert_eq!(decompress_q2(0x802a), Err(DecodingError::Reserved(0x802a))); // c.mv zero, a0
+
+        assert_eq!(decompress_q2(0x8082), Ok(0x00008067)); // c.jr ra
+        assert_eq!(decompress_q2(0x8f82), Ok(0x000f8067)); // c.jr t6
+        assert_eq!(decompress_q2(0x9482), Ok(0x000480e7)); // c.jalr s1
+        assert_eq!(decompress_q2(0x8faa), Ok(0x00a00fb3)); // c.mv t6, a0
+        assert_eq!(decompress_q2(0x9122), Ok(0x00810133)); // c.add sp, s0
+        assert_eq!(decompress_q2(0x9002), Ok(0x00100073)); // c.ebreak
     }
 
     #[test]
@@ -195,10 +643,13 @@ mod tests {
         assert_eq!(decode(0x9782).unwrap(), Jalr(IType(0x000780e7))); // jalr a5
         assert_eq!(decode(0x97ba).unwrap(), Add(RType(0x00e787b3))); // add a5, a5, a4
 
+        // C.EBREAK must not be mistaken for C.ADD (rd = 0) or C.JALR (rs1 = 0)
+        assert_eq!(decode(0x9002).unwrap(), Ebreak(IType(0x00100073))); // ebreak
+
         // C.FSDSP unimplemented
 
         // C.SWSP
-        assert_eq!(decode(0xd03e).unwrap(), Sw(SType(0x02f12023))); // sw a5, 21(sp)
+        assert_eq!(decode(0xd03e).unwrap(), Sw(SType(0x02f12023))); // sw a5, 32(sp)
 
         // C.SDSP
         assert_eq!(decode(0xe022).unwrap(), Sd(SType(0x00813023))); // sd s0, 0(sp)
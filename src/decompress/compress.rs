@@ -0,0 +1,184 @@
+//! Compression of 32-bit instructions to the 16-bit encodings of the C extension, the inverse of
+//! the expansions in this module.
+//!
+//! The candidates built here only check the registers. Whether an immediate fits into the
+//! compressed encoding is left to the caller, which expands the candidate again.
+
+use super::util::*;
+use crate::{bits::Permutable, Instruction, Register};
+
+/// The 3-bit field of one of the registers x8-x15.
+fn creg(reg: Register) -> Option<u16> {
+    match u32::from(reg) {
+        reg @ 8..=15 => Some(reg as u16 - 8),
+        _ => None,
+    }
+}
+
+/// The 5-bit field of any register but `zero`.
+fn nonzero(reg: Register) -> Option<u16> {
+    match reg {
+        Register::Zero => None,
+        reg => Some(u32::from(reg) as u16),
+    }
+}
+
+fn reg(reg: Register) -> u16 {
+    u32::from(reg) as u16
+}
+
+/// The low six bits of a (signed) immediate of the CI format.
+fn imm6(imm: i32) -> u16 {
+    (imm as u16) & 0b11_1111
+}
+
+/// A compressed encoding of `instruction`, HINTs aside, provided its immediate fits.
+pub(super) fn candidate(instruction: Instruction) -> Option<u16> {
+    use Instruction::*;
+    use Register::{Ra, Sp, Zero};
+
+    match instruction {
+        // C.NOP
+        Addi(_) if instruction == Instruction::new_nop() => Some(0x0001),
+        Addi(i) if i.rd() == Sp && i.rs1() == Sp && i.imm() != 0 && i.imm() % 16 == 0 => {
+            /* C.ADDI16SP */
+            let imm = (i.imm() as u16).permute(&ADDI16SP_IMM);
+            Some(0x6101 | put_imm(imm, InstrFormat::Ci))
+        }
+        Addi(i) if i.rd() == i.rs1() && i.imm() != 0 => {
+            /* C.ADDI */
+            Some(0x0001 | (nonzero(i.rd())? << 7) | put_imm(imm6(i.imm()), InstrFormat::Ci))
+        }
+        Addi(i) if i.rs1() == Sp && i.imm() > 0 => {
+            /* C.ADDI4SPN */
+            let imm = (i.imm() as u16).permute(&ADDI4SPN_IMM);
+            Some(put_imm(imm, InstrFormat::Ciw) | (creg(i.rd())? << 2))
+        }
+        Addi(i) if i.rs1() == Zero => {
+            /* C.LI */
+            Some(0x4001 | (nonzero(i.rd())? << 7) | put_imm(imm6(i.imm()), InstrFormat::Ci))
+        }
+        Addiw(i) if i.rd() == i.rs1() => {
+            Some(0x2001 | (nonzero(i.rd())? << 7) | put_imm(imm6(i.imm()), InstrFormat::Ci))
+        }
+        Lui(u) if u.rd() != Sp && u.imm() != 0 => {
+            Some(0x6001 | (nonzero(u.rd())? << 7) | put_imm(imm6(u.imm() as i32), InstrFormat::Ci))
+        }
+        Srli(i) | Srai(i) | Andi(i) if i.rd() == i.rs1() => {
+            let (funct2, imm) = match instruction {
+                Srli(_) => (0b00, i.imm() & 0b11_1111),
+                Srai(_) => (0b01, i.imm() & 0b11_1111),
+                _ => (0b10, i.imm()),
+            };
+
+            // a shift by 0 is a HINT
+            if funct2 != 0b10 && imm == 0 {
+                return None;
+            }
+
+            Some(
+                0x8001
+                    | (funct2 << 10)
+                    | (creg(i.rd())? << 7)
+                    | put_imm(imm6(imm), InstrFormat::Ci),
+            )
+        }
+        Sub(r) | Xor(r) | Or(r) | And(r) | Subw(r) | Addw(r) if r.rd() == r.rs1() => {
+            let (funct6, funct2) = match instruction {
+                Sub(_) => (0b100_011, 0b00),
+                Xor(_) => (0b100_011, 0b01),
+                Or(_) => (0b100_011, 0b10),
+                And(_) => (0b100_011, 0b11),
+                Subw(_) => (0b100_111, 0b00),
+                _ => (0b100_111, 0b01),
+            };
+
+            Some(
+                (funct6 << 10)
+                    | (creg(r.rd())? << 7)
+                    | (funct2 << 5)
+                    | (creg(r.rs2())? << 2)
+                    | 0b01,
+            )
+        }
+        Jal(j) if j.rd() == Zero => {
+            let offset = cj_offset().disassemble(j.imm() as u16);
+
+            Some(0xa001 | put_imm(offset, InstrFormat::Cj))
+        }
+        Beq(b) | Bne(b) if b.rs2() == Zero => {
+            let offset = cb_offset().disassemble(b.imm() as u16);
+            let funct3 = if matches!(instruction, Beq(_)) {
+                0b110
+            } else {
+                0b111
+            };
+
+            Some((funct3 << 13) | (creg(b.rs1())? << 7) | put_imm(offset, InstrFormat::Cb) | 0b01)
+        }
+        Slli(i) if i.rd() == i.rs1() && i.imm() != 0 => {
+            Some(0x0002 | (nonzero(i.rd())? << 7) | put_imm(imm6(i.imm()), InstrFormat::Ci))
+        }
+        Lw(i) | Ld(i) if i.rs1() == Sp => {
+            let (funct3, perm): (u16, &[usize]) = match instruction {
+                Lw(_) => (0b010, &WORD_SP_IMM),
+                _ => (0b011, &DOUBLEWORD_SP_IMM),
+            };
+            let imm = (i.imm() as u16).permute(perm);
+
+            Some((funct3 << 13) | (nonzero(i.rd())? << 7) | put_imm(imm, InstrFormat::Ci) | 0b10)
+        }
+        Lw(i) | Ld(i) => {
+            let (funct3, perm): (u16, &[usize]) = match instruction {
+                Lw(_) => (0b010, &WORD_IMM),
+                _ => (0b011, &DOUBLEWORD_IMM),
+            };
+            let imm = (i.imm() as u16).permute(perm);
+
+            Some(
+                (funct3 << 13)
+                    | (creg(i.rs1())? << 7)
+                    | put_imm(imm, InstrFormat::Cl)
+                    | (creg(i.rd())? << 2),
+            )
+        }
+        Sw(s) | Sd(s) if s.rs1() == Sp => {
+            let (funct3, perm): (u16, &[usize]) = match instruction {
+                Sw(_) => (0b110, &WORD_SP_IMM),
+                _ => (0b111, &DOUBLEWORD_SP_IMM),
+            };
+            let imm = (s.imm() as u16).permute(perm);
+
+            Some((funct3 << 13) | put_imm(imm, InstrFormat::Css) | (reg(s.rs2()) << 2) | 0b10)
+        }
+        Sw(s) | Sd(s) => {
+            let (funct3, perm): (u16, &[usize]) = match instruction {
+                Sw(_) => (0b110, &WORD_IMM),
+                _ => (0b111, &DOUBLEWORD_IMM),
+            };
+            let imm = (s.imm() as u16).permute(perm);
+
+            Some(
+                (funct3 << 13)
+                    | (creg(s.rs1())? << 7)
+                    | put_imm(imm, InstrFormat::Cs)
+                    | (creg(s.rs2())? << 2),
+            )
+        }
+        Jalr(i) if i.imm() == 0 && (i.rd() == Zero || i.rd() == Ra) => {
+            /* C.JR, C.JALR */
+            let bit12 = u16::from(i.rd() == Ra);
+            Some(0x8002 | (bit12 << 12) | (nonzero(i.rs1())? << 7))
+        }
+        Add(r) if r.rs1() == Zero => {
+            /* C.MV */
+            Some(0x8002 | (nonzero(r.rd())? << 7) | (nonzero(r.rs2())? << 2))
+        }
+        Add(r) if r.rd() == r.rs1() => {
+            /* C.ADD */
+            Some(0x9002 | (nonzero(r.rd())? << 7) | (nonzero(r.rs2())? << 2))
+        }
+        Ebreak(_) => Some(0x9002),
+        _ => None,
+    }
+}
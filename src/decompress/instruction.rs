@@ -0,0 +1,182 @@
+//! Decompression straight to an [`Instruction`], constructing it from the fields of the
+//! compressed encoding instead of decoding the molded 32-bit word.
+
+use super::util::*;
+use crate::{bits::Permutable, DecodingError, Instruction, Register};
+
+type InstructionResult = Result<Instruction, DecodingError>;
+
+/// Sign extend the lower `bits` bits of `n`.
+fn signed(n: u32, bits: u32) -> i32 {
+    ((n << (32 - bits)) as i32) >> (32 - bits)
+}
+
+/// Full register of the 5-bit register field starting at bit `lsb`.
+fn reg(i: u16, lsb: u32) -> Register {
+    Register::from(u32::from((i >> lsb) & 0b1_1111))
+}
+
+/// One of the registers x8-x15 of the 3-bit register field starting at bit `lsb`.
+fn creg(i: u16, lsb: u32) -> Register {
+    Register::from(8 + u32::from((i >> lsb) & 0b111))
+}
+
+// Quadrant 0 {{{
+pub(super) fn quadrant0(i: u16) -> InstructionResult {
+    if i == 0 {
+        return Err(DecodingError::Illegal(i.into()));
+    }
+
+    let rd_rs2 = creg(i, 2);
+    let rs1 = creg(i, 7);
+
+    match (i >> 13) & 0b111 {
+        0b000 => {
+            /* C.ADDI4SPN */
+            let imm = get_imm(i, InstrFormat::Ciw).inv_permute(&ADDI4SPN_IMM);
+
+            if imm == 0 {
+                return Err(DecodingError::Reserved(i.into()));
+            }
+
+            Ok(Instruction::new_addi(rd_rs2, Register::Sp, imm.into()))
+        }
+        0b001 => Err(DecodingError::Unsupported(i.into())), // C.FLD
+        0b010 => {
+            let imm = get_imm(i, InstrFormat::Cl).inv_permute(&WORD_IMM);
+            Ok(Instruction::new_lw(rd_rs2, rs1, imm.into()))
+        }
+        0b011 => {
+            let imm = get_imm(i, InstrFormat::Cl).inv_permute(&DOUBLEWORD_IMM);
+            Ok(Instruction::new_ld(rd_rs2, rs1, imm.into()))
+        }
+        0b100 => Err(DecodingError::Reserved(i.into())),
+        0b101 => Err(DecodingError::Unsupported(i.into())), // C.FSD
+        0b110 => {
+            let imm = get_imm(i, InstrFormat::Cs).inv_permute(&WORD_IMM);
+            Ok(Instruction::new_sw(rs1, rd_rs2, imm.into()))
+        }
+        0b111 => {
+            let imm = get_imm(i, InstrFormat::Cs).inv_permute(&DOUBLEWORD_IMM);
+            Ok(Instruction::new_sd(rs1, rd_rs2, imm.into()))
+        }
+        _ => unreachable!(),
+    }
+}
+// }}}
+
+// Quadrant 1 {{{
+pub(super) fn quadrant1(i: u16) -> InstructionResult {
+    let rd = reg(i, 7);
+    let imm = get_imm(i, InstrFormat::Ci);
+
+    match (i >> 13) & 0b111 {
+        // C.NOP with an immediate and C.ADDI without one are HINTs
+        0b000 => Ok(Instruction::new_addi(rd, rd, signed(imm.into(), 6))),
+        0b001 if rd == Register::Zero => Err(DecodingError::Reserved(i.into())),
+        0b001 => Ok(Instruction::new_addiw(rd, rd, signed(imm.into(), 6))),
+        0b010 if rd == Register::Zero => Err(DecodingError::Reserved(i.into())),
+        0b010 => Ok(Instruction::new_addi(
+            rd,
+            Register::Zero,
+            signed(imm.into(), 6),
+        )),
+        0b011 if rd == Register::Zero || imm == 0 => Err(DecodingError::Reserved(i.into())),
+        0b011 if rd == Register::Sp => {
+            /* C.ADDI16SP */
+            let imm = imm.inv_permute(&ADDI16SP_IMM);
+            Ok(Instruction::new_addi(rd, rd, signed(imm.into(), 10)))
+        }
+        0b011 => Ok(Instruction::new_lui(rd, signed(imm.into(), 6))),
+        0b100 => misc_alu(i),
+        0b101 => {
+            /* C.J */
+            let offset = cj_offset().assemble(get_imm(i, InstrFormat::Cj));
+
+            Ok(Instruction::new_jal(
+                Register::Zero,
+                signed(offset.into(), 12),
+            ))
+        }
+        funct3 => {
+            /* C.BEQZ, C.BNEZ */
+            let offset = cb_offset().assemble(get_imm(i, InstrFormat::Cb));
+            let offset = signed(offset.into(), 9);
+
+            match funct3 {
+                0b110 => Ok(Instruction::new_beq(creg(i, 7), Register::Zero, offset)),
+                _ => Ok(Instruction::new_bne(creg(i, 7), Register::Zero, offset)),
+            }
+        }
+    }
+}
+
+fn misc_alu(i: u16) -> InstructionResult {
+    let rd_rs1 = creg(i, 7);
+    let rs2 = creg(i, 2);
+    let imm = get_imm(i, InstrFormat::Ci);
+
+    match ((i >> 10) & 0b11, (i >> 12) & 0b1, (i >> 5) & 0b11) {
+        // shamt == 0 is a HINT
+        (0b00, ..) => Ok(Instruction::new_srli(rd_rs1, rd_rs1, imm.into())),
+        (0b01, ..) => Ok(Instruction::new_srai(rd_rs1, rd_rs1, imm.into())),
+        (0b10, ..) => Ok(Instruction::new_andi(rd_rs1, rd_rs1, signed(imm.into(), 6))),
+        (_, 0, 0b00) => Ok(Instruction::new_sub(rd_rs1, rd_rs1, rs2)),
+        (_, 0, 0b01) => Ok(Instruction::new_xor(rd_rs1, rd_rs1, rs2)),
+        (_, 0, 0b10) => Ok(Instruction::new_or(rd_rs1, rd_rs1, rs2)),
+        (_, 0, 0b11) => Ok(Instruction::new_and(rd_rs1, rd_rs1, rs2)),
+        (_, 1, 0b00) => Ok(Instruction::new_subw(rd_rs1, rd_rs1, rs2)),
+        (_, 1, 0b01) => Ok(Instruction::new_addw(rd_rs1, rd_rs1, rs2)),
+        (_, _, _) => Err(DecodingError::Reserved(i.into())),
+    }
+}
+// }}}
+
+// Quadrant 2 {{{
+pub(super) fn quadrant2(i: u16) -> InstructionResult {
+    let rd_rs1 = reg(i, 7);
+    let rs2 = reg(i, 2);
+    let imm = get_imm(i, InstrFormat::Ci);
+
+    match ((i >> 13) & 0b111, (i >> 12) & 0b1) {
+        (0b000, _) | (0b010, _) | (0b011, _) if rd_rs1 == Register::Zero => {
+            Err(DecodingError::Reserved(i.into()))
+        }
+        // shamt == 0 is a HINT
+        (0b000, _) => Ok(Instruction::new_slli(rd_rs1, rd_rs1, imm.into())),
+        (0b001, _) => Err(DecodingError::Unsupported(i.into())), // C.FLDSP
+        (0b010, _) => {
+            let imm = imm.inv_permute(&WORD_SP_IMM);
+            Ok(Instruction::new_lw(rd_rs1, Register::Sp, imm.into()))
+        }
+        (0b011, _) => {
+            let imm = imm.inv_permute(&DOUBLEWORD_SP_IMM);
+            Ok(Instruction::new_ld(rd_rs1, Register::Sp, imm.into()))
+        }
+        (0b100, _) => jr_mv_add(i, rd_rs1, rs2),
+        (0b101, _) => Err(DecodingError::Unsupported(i.into())), // C.FSDSP
+        (0b110, _) => {
+            let imm = get_imm(i, InstrFormat::Css).inv_permute(&WORD_SP_IMM);
+            Ok(Instruction::new_sw(Register::Sp, rs2, imm.into()))
+        }
+        (_, _) => {
+            let imm = get_imm(i, InstrFormat::Css).inv_permute(&DOUBLEWORD_SP_IMM);
+            Ok(Instruction::new_sd(Register::Sp, rs2, imm.into()))
+        }
+    }
+}
+
+fn jr_mv_add(i: u16, rd_rs1: Register, rs2: Register) -> InstructionResult {
+    use Register::{Ra, Zero};
+
+    match ((i >> 12) & 0b1, rd_rs1, rs2) {
+        (0, Zero, _) /* C.JR, C.MV */ => Err(DecodingError::Reserved(i.into())),
+        (0, rs1, Zero) /* C.JR */ => Ok(Instruction::new_jalr(Zero, rs1, 0)),
+        (0, rd, rs2) /* C.MV */ => Ok(Instruction::new_add(rd, Zero, rs2)),
+        (_, Zero, Zero) /* C.EBREAK */ => Ok(Instruction::new_ebreak()),
+        (_, rs1, Zero) /* C.JALR */ => Ok(Instruction::new_jalr(Ra, rs1, 0)),
+        // rd == 0 is a HINT
+        (_, rd, rs2) /* C.ADD */ => Ok(Instruction::new_add(rd, rd, rs2)),
+    }
+}
+// }}}
@@ -1,8 +1,10 @@
-use crate::Register;
+use crate::{bits::Permutable, encode, Register};
+use core::ops::Range;
 
 pub(super) enum CrInstr {
     Sub,
     Add,
+    Xor,
     Or,
     And,
     Subw,
@@ -16,6 +18,7 @@ pub(super) enum CiInstr {
     Lw,
     Ld,
     Jalr,
+    Ebreak,
     Slli,
     Srli,
     Srai,
@@ -37,16 +40,13 @@ pub(super) enum CuInstr {
 
 pub(super) fn build_rtype(instruction_type: CrInstr, rd: u16, rs1: u16, rs2: u16) -> u32 {
     let mold = |funct7: u32, rs2: u16, rs1: u16, funct3: u32, rd: u16, opcode: u32| -> u32 {
-        let rd: u32 = rd.into();
-        let rs1: u32 = rs1.into();
-        let rs2: u32 = rs2.into();
-
-        (funct7 << 25) | (rs2 << 20) | (rs1 << 15) | (funct3 << 12) | (rd << 7) | opcode
+        encode::r_type(funct7, rs2.into(), rs1.into(), funct3, rd.into(), opcode)
     };
 
     match instruction_type {
         CrInstr::Sub => mold(0b0100000, rs2, rs1, 0b000, rd, 0b0110011),
         CrInstr::Add => mold(0b0000000, rs2, rs1, 0b000, rd, 0b0110011),
+        CrInstr::Xor => mold(0b0000000, rs2, rs1, 0b100, rd, 0b0110011),
         CrInstr::Or => mold(0b0000000, rs2, rs1, 0b110, rd, 0b0110011),
         CrInstr::And => mold(0b0000000, rs2, rs1, 0b111, rd, 0b0110011),
         CrInstr::Subw => mold(0b0100000, rs2, rs1, 0b000, rd, 0b0111011),
@@ -56,11 +56,7 @@ pub(super) fn build_rtype(instruction_type: CrInstr, rd: u16, rs1: u16, rs2: u16
 
 pub(super) fn build_itype(instruction_type: CiInstr, rd: u16, rs1: u16, imm: u16) -> u32 {
     let mold = |imm: u16, rs1: u16, funct3: u32, rd: u16, opcode: u32| -> u32 {
-        let rd: u32 = rd.into();
-        let rs1: u32 = rs1.into();
-        let imm: u32 = imm.into();
-
-        (imm << 20) | (rs1 << 15) | (funct3 << 12) | (rd << 7) | opcode
+        encode::i_type(imm.into(), rs1.into(), funct3, rd.into(), opcode)
     };
 
     match instruction_type {
@@ -70,6 +66,7 @@ pub(super) fn build_itype(instruction_type: CiInstr, rd: u16, rs1: u16, imm: u16
         CiInstr::Lw => mold(imm, rs1, 0b010, rd, 0b0000011),
         CiInstr::Ld => mold(imm, rs1, 0b011, rd, 0b0000011),
         CiInstr::Jalr => mold(imm, rs1, 0b000, rd, 0b1100111),
+        CiInstr::Ebreak => mold(imm, rs1, 0b000, rd, 0b1110011),
         CiInstr::Slli => mold(imm, rs1, 0b001, rd, 0b0010011),
         CiInstr::Srai => mold((0b0100000 << 5) | imm, rs1, 0b101, rd, 0b0010011),
         CiInstr::Srli => mold(imm, rs1, 0b101, rd, 0b0010011),
@@ -78,17 +75,7 @@ pub(super) fn build_itype(instruction_type: CiInstr, rd: u16, rs1: u16, imm: u16
 
 pub(super) fn build_btype(instruction_type: CbInstr, rs1: u16, imm: u16) -> u32 {
     let mold = |imm: u16, rs1: u16, rs2: u16, funct3: u32, opcode: u32| -> u32 {
-        let rs1: u32 = rs1.into();
-        let rs2: u32 = rs2.into();
-
-        let imm: u32 = imm.permute(&[12, 10, 9, 8, 7, 6, 5, 4, 3, 2, 1, 11]).into();
-
-        ((imm >> 5) << 25)
-            | (rs2 << 20)
-            | (rs1 << 15)
-            | (funct3 << 12)
-            | ((imm & 0b11111) << 7)
-            | opcode
+        encode::b_type(imm.into(), rs2.into(), rs1.into(), funct3, opcode)
     };
 
     match instruction_type {
@@ -99,12 +86,7 @@ pub(super) fn build_btype(instruction_type: CbInstr, rs1: u16, imm: u16) -> u32
 
 pub(super) fn build_stype(instruction_type: CsInstr, rs1: u16, rs2: u16, imm: u16) -> u32 {
     let mold = |rs2: u16, rs1: u16, funct3: u32, imm: u16, opcode: u32| -> u32 {
-        let immh: u32 = (imm >> 5).into();
-        let imml: u32 = (imm & 0b1_1111).into();
-        let rs1: u32 = rs1.into();
-        let rs2: u32 = rs2.into();
-
-        (immh << 25) | (rs2 << 20) | (rs1 << 15) | (funct3 << 12) | (imml << 7) | opcode
+        encode::s_type(imm.into(), rs2.into(), rs1.into(), funct3, opcode)
     };
 
     match instruction_type {
@@ -114,12 +96,8 @@ pub(super) fn build_stype(instruction_type: CsInstr, rs1: u16, rs2: u16, imm: u1
 }
 
 pub(super) fn build_utype(instruction_type: CuInstr, rd: u16, imm: u32) -> u32 {
-    let mold = |imm: u32, rd: u16, opcode: u32| -> u32 {
-        let rd: u32 = rd.into();
-        let imm: u32 = imm >> 12;
-
-        (imm << 12) | (rd << 7) | opcode
-    };
+    let mold =
+        |imm: u32, rd: u16, opcode: u32| -> u32 { encode::u_type(imm >> 12, rd.into(), opcode) };
 
     match instruction_type {
         CuInstr::Lui => mold(imm, rd, 0b0110111),
@@ -127,17 +105,11 @@ pub(super) fn build_utype(instruction_type: CuInstr, rd: u16, imm: u32) -> u32 {
 }
 
 pub(super) fn build_jtype(imm: u16) -> u32 {
-    let mold = |imm: u16, rd: u16, opcode: u32| -> u32 {
-        let rd: u32 = rd.into();
-
-        let imm = sign_extend32(imm.into(), 12).permute(&[
-            20, 10, 9, 8, 7, 6, 5, 4, 3, 2, 1, 11, 19, 18, 17, 16, 15, 14, 13, 12,
-        ]);
-
-        (imm << 12) | (rd << 7) | opcode
-    };
-
-    mold(imm, Register::Zero as u16, 0b1101111)
+    encode::j_type(
+        sign_extend32(imm.into(), 12),
+        Register::Zero as u32,
+        0b1101111,
+    )
 }
 
 pub(super) enum InstrFormat {
@@ -163,83 +135,106 @@ pub(super) fn get_imm(i: u16, fmt: InstrFormat) -> u16 {
     }
 }
 
-pub(super) trait Permutable {
-    /// When going from an number to the permuted representation in an instruction.
-    fn permute(self, perm: &[usize]) -> Self;
+/// Place the gathered immediate bits `imm` at their position in an instruction of format `fmt`,
+/// the inverse of [`get_imm`].
+#[inline(always)]
+pub(super) fn put_imm(imm: u16, fmt: InstrFormat) -> u16 {
+    match fmt {
+        InstrFormat::Ci => ((imm & 0b10_0000) << 7) | ((imm & 0b1_1111) << 2),
+        InstrFormat::Css => (imm & 0b11_1111) << 7,
+        InstrFormat::Ciw => (imm & 0b1111_1111) << 5,
+        InstrFormat::Cl | InstrFormat::Cs => ((imm & 0b1_1100) << 8) | ((imm & 0b11) << 5),
+        InstrFormat::Cb => ((imm & 0b1110_0000) << 5) | ((imm & 0b1_1111) << 2),
+        InstrFormat::Cj => (imm & 0b111_1111_1111) << 2,
+    }
+}
 
-    /// When going from a permuted number in an instruction to the binary representation.
-    fn inv_permute(self, perm: &[usize]) -> Self;
+// Permutations of the immediates which are gathered by `get_imm`, listing the bit of the
+// immediate for every gathered bit from the most significant down.
+
+/// `nzuimm[5:4|9:6|2|3]` of C.ADDI4SPN.
+pub(super) const ADDI4SPN_IMM: [usize; 8] = [5, 4, 9, 8, 7, 6, 2, 3];
+/// `nzimm[9|4|6|8:7|5]` of C.ADDI16SP.
+pub(super) const ADDI16SP_IMM: [usize; 6] = [9, 4, 6, 8, 7, 5];
+/// `nzimm[17|16:12]` of C.LUI, which only fits into a `u32`.
+pub(super) const LUI_IMM: [usize; 6] = [17, 16, 15, 14, 13, 12];
+/// `uimm[5:3|2|6]` of C.LW and C.SW.
+pub(super) const WORD_IMM: [usize; 5] = [5, 4, 3, 2, 6];
+/// `uimm[5:3|7:6]` of C.LD and C.SD.
+pub(super) const DOUBLEWORD_IMM: [usize; 5] = [5, 4, 3, 7, 6];
+/// `uimm[5|4:2|7:6]` of C.LWSP and `uimm[5:2|7:6]` of C.SWSP.
+pub(super) const WORD_SP_IMM: [usize; 6] = [5, 4, 3, 2, 7, 6];
+/// `uimm[5|4:3|8:6]` of C.LDSP and `uimm[5:3|8:6]` of C.SDSP.
+pub(super) const DOUBLEWORD_SP_IMM: [usize; 6] = [5, 4, 3, 8, 7, 6];
+
+/// The offset `imm[8|4:3|7:6|2:1|5]` of C.BEQZ and C.BNEZ.
+pub(super) fn cb_offset() -> ImmAssembler {
+    ImmAssembler::new()
+        .field(0..1, 5)
+        .field(1..3, 1)
+        .field(3..5, 6)
+        .field(5..7, 3)
+        .field(7..8, 8)
 }
 
-impl Permutable for u16 {
-    fn inv_permute(self, perm: &[usize]) -> Self {
-        debug_assert!(
-            perm.len() <= 16,
-            "Permutation of u16 cannot exceed 16 entries."
-        );
-        debug_assert!(
-            perm.iter().all(|x| x < &16),
-            "Permutation indices for u16 cannot exceed 15."
-        );
+/// The offset `imm[11|4|9:8|10|6|7|3:1|5]` of C.J.
+pub(super) fn cj_offset() -> ImmAssembler {
+    ImmAssembler::new()
+        .field(0..1, 5)
+        .field(1..4, 1)
+        .field(4..5, 7)
+        .field(5..6, 6)
+        .field(6..7, 10)
+        .field(7..9, 8)
+        .field(9..10, 4)
+        .field(10..11, 11)
+}
 
-        perm.iter()
-            .rev()
-            .enumerate()
-            .map(|(bit, offset)| ((self >> bit) & 0b1) << offset)
-            .sum()
+/// Assembles an immediate from bit fields scattered across an instruction.
+///
+/// Every call to `field` declares that the bits `src` of the raw (gathered) value end up at
+/// the bits starting with `dest` in the immediate. The collected mapping is turned into a
+/// permutation and applied with `Permutable::inv_permute`, so the source bits have to cover
+/// `0..n` without gaps.
+pub(super) struct ImmAssembler {
+    dest: [usize; 32],
+    len: usize,
+}
+
+impl ImmAssembler {
+    pub(super) fn new() -> Self {
+        Self {
+            dest: [0; 32],
+            len: 0,
+        }
     }
 
-    fn permute(self, perm: &[usize]) -> Self {
-        debug_assert!(
-            perm.len() <= 16,
-            "Permutation of u16 cannot exceed 16 entries."
-        );
-        debug_assert!(
-            perm.iter().all(|x| x < &16),
-            "Permutation indices for u16 cannot exceed 15."
-        );
+    pub(super) fn field(mut self, src: Range<usize>, dest: usize) -> Self {
+        for (offset, bit) in src.enumerate() {
+            self.dest[bit] = dest + offset;
+            self.len = self.len.max(bit + 1);
+        }
 
-        perm.iter()
-            .rev()
-            .enumerate()
-            .map(|(bit, offset)| ((self >> offset) & 0b1) << bit)
-            .sum()
+        self
     }
-}
 
-impl Permutable for u32 {
-    fn inv_permute(self, perm: &[usize]) -> Self {
-        debug_assert!(
-            perm.len() <= 32,
-            "Permutation of u32 cannot exceed 32 entries."
-        );
-        debug_assert!(
-            perm.iter().all(|x| x < &32),
-            "Permutation indices for u32 cannot exceed 31."
-        );
+    pub(super) fn assemble<T: Permutable>(&self, raw: T) -> T {
+        raw.inv_permute(&self.permutation()[..self.len])
+    }
 
-        perm.iter()
-            .rev()
-            .enumerate()
-            .map(|(bit, offset)| ((self >> bit) & 0b1) << offset)
-            .sum()
+    /// Scatter an immediate back to the raw (gathered) bit fields, the inverse of `assemble`.
+    pub(super) fn disassemble<T: Permutable>(&self, imm: T) -> T {
+        imm.permute(&self.permutation()[..self.len])
     }
 
-    fn permute(self, perm: &[usize]) -> Self {
-        debug_assert!(
-            perm.len() <= 32,
-            "Permutation of u32 cannot exceed 32 entries."
-        );
-        debug_assert!(
-            perm.iter().all(|x| x < &32),
-            "Permutation indices for u32 cannot exceed 31."
-        );
+    fn permutation(&self) -> [usize; 32] {
+        let mut perm = [0; 32];
+
+        for (idx, dest) in self.dest[..self.len].iter().rev().enumerate() {
+            perm[idx] = *dest;
+        }
 
-        perm.iter()
-            .rev()
-            .enumerate()
-            .map(|(bit, offset)| ((self >> offset) & 0b1) << bit)
-            .sum()
+        perm
     }
 }
 
@@ -268,3 +263,101 @@ pub(super) fn sign_extend32(n: u32, b: u32) -> u32 {
         n.wrapping_sub(2_u32.pow(b))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// CI-format halfwords and their signed immediate, where bit 12 is the sign bit and bits 6
+    /// to 2 are the low bits.
+    const CI_IMMEDIATES: [(u16, i16); 9] = [
+        (0x5501, -32), // c.li a0, -32
+        (0x5541, -16), // c.li a0, -16
+        (0x557d, -1),  // c.li a0, -1
+        (0x4501, 0),   // c.li a0, 0
+        (0x4505, 1),   // c.li a0, 1
+        (0x4541, 16),  // c.li a0, 16
+        (0x457d, 31),  // c.li a0, 31
+        (0x17e1, -8),  // c.addi a5, -8
+        (0x2705, 1),   // c.addiw a4, 1
+    ];
+
+    #[test]
+    fn sign_extend_ci_immediate() {
+        for (i, imm) in CI_IMMEDIATES.iter() {
+            assert_eq!(
+                sign_extend16(get_imm(*i, InstrFormat::Ci), 6) as i16,
+                *imm,
+                "{:#06x}",
+                i
+            );
+        }
+    }
+
+    #[test]
+    fn valid_permutations() {
+        let width = |fmt| get_imm(0xffff, fmt).count_ones();
+        let permutations: [(&str, &[usize], u32, u32); 11] = [
+            ("ADDI4SPN", &ADDI4SPN_IMM, width(InstrFormat::Ciw), 16),
+            ("ADDI16SP", &ADDI16SP_IMM, width(InstrFormat::Ci), 16),
+            ("LUI", &LUI_IMM, width(InstrFormat::Ci), 32),
+            ("LW", &WORD_IMM, width(InstrFormat::Cl), 16),
+            ("SW", &WORD_IMM, width(InstrFormat::Cs), 16),
+            ("LD", &DOUBLEWORD_IMM, width(InstrFormat::Cl), 16),
+            ("SD", &DOUBLEWORD_IMM, width(InstrFormat::Cs), 16),
+            ("LWSP", &WORD_SP_IMM, width(InstrFormat::Ci), 16),
+            ("SWSP", &WORD_SP_IMM, width(InstrFormat::Css), 16),
+            ("LDSP", &DOUBLEWORD_SP_IMM, width(InstrFormat::Ci), 16),
+            ("SDSP", &DOUBLEWORD_SP_IMM, width(InstrFormat::Css), 16),
+        ];
+        let cb = cb_offset().permutation();
+        let cj = cj_offset().permutation();
+        let offsets: [(&str, &[usize], u32, u32); 2] = [
+            ("BEQZ", &cb[..cb_offset().len], width(InstrFormat::Cb), 16),
+            ("J", &cj[..cj_offset().len], width(InstrFormat::Cj), 16),
+        ];
+
+        for (name, perm, width, bits) in permutations.iter().chain(offsets.iter()) {
+            assert_eq!(perm.len() as u32, *width, "{}", name);
+            assert!(perm.iter().all(|bit| (*bit as u32) < *bits), "{}", name);
+
+            let mut sorted = perm.to_vec();
+            sorted.sort_unstable();
+            sorted.dedup();
+            assert_eq!(sorted.len(), perm.len(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn assemble_btype_immediate() {
+        let assembler = cb_offset();
+
+        for raw in 0..=0xffu16 {
+            assert_eq!(
+                assembler.assemble(raw),
+                raw.inv_permute(&[8, 4, 3, 7, 6, 2, 1, 5])
+            );
+        }
+
+        // beqz a5, -4
+        assert_eq!(
+            sign_extend16(assembler.assemble(0b1111_1101_u16), 9),
+            -4i16 as u16
+        );
+    }
+
+    #[test]
+    fn assemble_jtype_immediate() {
+        let assembler = cj_offset();
+
+        for raw in 0..=0x7ffu16 {
+            assert_eq!(
+                assembler.assemble(raw),
+                raw.inv_permute(&[11, 4, 9, 8, 10, 6, 7, 3, 2, 1, 5])
+            );
+        }
+
+        // c.j 0x2c
+        assert_eq!(assembler.assemble(0b000_0000_1101_u16), 0x2c);
+    }
+}
@@ -0,0 +1,503 @@
+//! # Render decoded programs as RISC-V assembly
+
+use crate::{decode, instruction_length, iterators::EncodingIter, DecodedProgram, Instruction};
+use byteorder::{ByteOrder, LittleEndian};
+use std::{
+    collections::BTreeMap,
+    fmt::Write as _,
+    io::{self, Read, Write},
+};
+
+/// Layout of the instructions in a listing.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum DisasmStyle {
+    /// Operands follow the mnemonic after a single space, e.g. `addi a0, a0, 1`.
+    Compact,
+    /// Mnemonics are padded to the given width, so operands line up in a column, e.g.
+    /// `addi   a0, a0, 1` for a width of 6.
+    Aligned { mnemonic_width: usize },
+}
+
+impl Default for DisasmStyle {
+    fn default() -> Self {
+        DisasmStyle::Compact
+    }
+}
+
+/// An instruction in a structured disassembly listing.
+#[cfg(feature = "serde")]
+#[derive(Clone, Debug, Eq, PartialEq, serde::Serialize)]
+pub struct ListingEntry {
+    pub address: u64,
+    /// Length of the encoding in bytes.
+    pub width: usize,
+    pub raw: u32,
+    pub mnemonic: String,
+    pub operands: String,
+}
+
+impl DecodedProgram {
+    /// Write a listing of the code segment to `w`, one instruction per line.
+    ///
+    /// Every line consists of the address, the raw encoding in hex and the instruction in
+    /// assembly syntax. Encodings which can not be decoded are listed as `<unknown>`. Targets of
+    /// branches and jumps are shown as absolute addresses.
+    pub fn write_disassembly<W: Write>(&self, w: &mut W, style: DisasmStyle) -> io::Result<()> {
+        self.write_disassembly_with_symbols(w, &BTreeMap::new(), style)
+    }
+
+    /// The instructions of the code segment rendered in assembly syntax, each with its address.
+    /// Encodings which can not be decoded are rendered as `<unknown>`.
+    pub fn disassemble(&self) -> Vec<(u64, String)> {
+        self.disassemble_with_symbols(&BTreeMap::new())
+    }
+
+    /// Like [`DecodedProgram::disassemble`], but instructions at the address of a symbol in
+    /// `symbols` (address to name, e.g. from [`load_symbols`](crate::load_symbols)) are prefixed
+    /// with `<name>: ` and branch and jump targets are rendered relative to symbols.
+    pub fn disassemble_with_symbols(&self, symbols: &BTreeMap<u64, String>) -> Vec<(u64, String)> {
+        EncodingIter::new(&self.code.content, self.code.address)
+            .map(|(address, raw, _)| {
+                let mut text = match symbols.get(&address) {
+                    Some(name) => format!("<{}>: ", name),
+                    None => String::new(),
+                };
+
+                match decode(raw) {
+                    Ok(instruction) => text += &render_instruction(instruction, address, symbols),
+                    Err(_) => text += "<unknown>",
+                }
+
+                (address, text)
+            })
+            .collect()
+    }
+
+    /// A dump of the data segment in the style of `hexdump -C`, 16 bytes per line: the address,
+    /// the bytes in hex and the bytes as ASCII, with `.` for non-printable characters.
+    pub fn hexdump_data(&self) -> String {
+        let bytes = self
+            .data
+            .content
+            .iter()
+            .flat_map(|w| w.to_le_bytes())
+            .collect::<Vec<_>>();
+
+        let mut dump = String::new();
+
+        for (idx, line) in bytes.chunks(16).enumerate() {
+            let address = self.data.address + (idx * 16) as u64;
+
+            let hex = line
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect::<Vec<_>>()
+                .join(" ");
+
+            let ascii = line
+                .iter()
+                .map(|b| match b {
+                    b' '..=b'~' => *b as char,
+                    _ => '.',
+                })
+                .collect::<String>();
+
+            writeln!(dump, "{:#010x}: {:<47}  |{}|", address, hex, ascii)
+                .expect("writing to a string can not fail");
+        }
+
+        dump
+    }
+
+    /// A listing of the code segment as JSON array of [`ListingEntry`] objects. Encodings which
+    /// can not be decoded have the mnemonic `<unknown>`.
+    #[cfg(feature = "serde")]
+    pub fn to_json_listing(&self) -> String {
+        let entries = EncodingIter::new(&self.code.content, self.code.address)
+            .map(|(address, raw, width)| {
+                let text = match decode(raw) {
+                    Ok(instruction) => render_instruction(instruction, address, &BTreeMap::new()),
+                    Err(_) => String::from("<unknown>"),
+                };
+
+                let (mnemonic, operands) = text.split_once(' ').unwrap_or((&text, ""));
+
+                ListingEntry {
+                    address,
+                    width,
+                    raw,
+                    mnemonic: mnemonic.to_string(),
+                    operands: operands.to_string(),
+                }
+            })
+            .collect::<Vec<_>>();
+
+        serde_json::to_string(&entries).expect("listing entries can always be serialized")
+    }
+
+    /// Like [`DecodedProgram::write_disassembly`], but branch and jump targets are rendered
+    /// relative to the nearest preceding symbol in `symbols` (address to name), e.g.
+    /// `<main+0x8>`.
+    pub fn write_disassembly_with_symbols<W: Write>(
+        &self,
+        w: &mut W,
+        symbols: &BTreeMap<u64, String>,
+        style: DisasmStyle,
+    ) -> io::Result<()> {
+        for (address, raw, length) in EncodingIter::new(&self.code.content, self.code.address) {
+            write_entry(w, address, raw, length, symbols, style)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Write a listing of the instructions read from `r` to `w`, in the format of
+/// [`DecodedProgram::write_disassembly`], with the first instruction at address `base`.
+///
+/// Instructions are read and listed one at a time, so memory usage does not depend on the
+/// length of the input. An incomplete instruction at the end of the input is listed as
+/// `<truncated>`.
+pub fn disassemble_stream<R: Read, W: Write>(mut r: R, base: u64, mut w: W) -> io::Result<()> {
+    let mut buffer = [0; 4];
+    let mut address = base;
+
+    loop {
+        let mut read = read_up_to(&mut r, &mut buffer[..2])?;
+
+        if read == 0 {
+            return Ok(());
+        }
+
+        let length = match read {
+            2 if instruction_length(LittleEndian::read_u16(&buffer)) == 2 => 2,
+            _ => 4,
+        };
+
+        if read == 2 && length == 4 {
+            read += read_up_to(&mut r, &mut buffer[2..])?;
+        }
+
+        if read < length {
+            write!(w, "{:#010x}: ", address)?;
+            for byte in buffer[..read].iter().rev() {
+                write!(w, "{:02x}", byte)?;
+            }
+            return writeln!(w, "  <truncated>");
+        }
+
+        let raw = match length {
+            2 => u32::from(LittleEndian::read_u16(&buffer)),
+            _ => LittleEndian::read_u32(&buffer),
+        };
+
+        write_entry(
+            &mut w,
+            address,
+            raw,
+            length,
+            &BTreeMap::new(),
+            DisasmStyle::Compact,
+        )?;
+
+        address += length as u64;
+    }
+}
+
+/// Read from `r` until `buffer` is full or the end of the input is reached.
+fn read_up_to<R: Read>(r: &mut R, buffer: &mut [u8]) -> io::Result<usize> {
+    let mut read = 0;
+
+    while read < buffer.len() {
+        match r.read(&mut buffer[read..]) {
+            Ok(0) => break,
+            Ok(n) => read += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(read)
+}
+
+fn write_entry<W: Write>(
+    w: &mut W,
+    address: u64,
+    raw: u32,
+    length: usize,
+    symbols: &BTreeMap<u64, String>,
+    style: DisasmStyle,
+) -> io::Result<()> {
+    if length == 2 {
+        write!(w, "{:#010x}: {:04x}      ", address, raw)?;
+    } else {
+        write!(w, "{:#010x}: {:08x}  ", address, raw)?;
+    }
+
+    match decode(raw) {
+        Ok(instruction) => {
+            let text = render_instruction(instruction, address, symbols);
+
+            match (style, text.split_once(' ')) {
+                (DisasmStyle::Aligned { mnemonic_width }, Some((mnemonic, operands))) => write!(
+                    w,
+                    "{:<width$} {}",
+                    mnemonic,
+                    operands,
+                    width = mnemonic_width
+                )?,
+                _ => write!(w, "{}", text)?,
+            }
+        }
+        Err(_) => write!(w, "<unknown>")?,
+    }
+
+    writeln!(w)
+}
+
+fn render_instruction(
+    instruction: Instruction,
+    pc: u64,
+    symbols: &BTreeMap<u64, String>,
+) -> String {
+    let target = match instruction.branch_target(pc) {
+        Some(target) => target,
+        None => return instruction.to_string(),
+    };
+
+    let mut text = match instruction {
+        Instruction::Jal(j) => format!("{} {:?}, ", instruction.mnemonic(), j.rd()),
+        Instruction::Beq(b)
+        | Instruction::Bne(b)
+        | Instruction::Blt(b)
+        | Instruction::Bge(b)
+        | Instruction::Bltu(b)
+        | Instruction::Bgeu(b) => {
+            format!("{} {:?}, {:?}, ", instruction.mnemonic(), b.rs1(), b.rs2())
+        }
+        _ => unreachable!("only branches and jal have a target"),
+    };
+
+    write_label(&mut text, target, symbols).expect("writing to a string can not fail");
+
+    text
+}
+
+fn write_label(w: &mut String, address: u64, symbols: &BTreeMap<u64, String>) -> std::fmt::Result {
+    match symbols.range(..=address).next_back() {
+        Some((start, name)) if *start == address => write!(w, "<{}>", name),
+        Some((start, name)) => write!(w, "<{}+{:#x}>", name, address - start),
+        None => write!(w, "{:#x}", address),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DisasmStyle;
+    use crate::{analysis::tests::program, DecodedProgram, ProgramSegment};
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn write_disassembly() {
+        let program = DecodedProgram {
+            code: ProgramSegment {
+                address: 0x10000,
+                // addi a0, a1, 42; c.addi a5, -8
+                content: vec![0x13, 0x85, 0xa5, 0x02, 0xe1, 0x17],
+            },
+            data: ProgramSegment {
+                address: 0x11000,
+                content: vec![],
+            },
+            data_file_size: 0,
+            entry_address: 0x10000,
+        };
+
+        let mut listing = Vec::new();
+        program
+            .write_disassembly(&mut listing, DisasmStyle::default())
+            .unwrap();
+        let listing = String::from_utf8(listing).unwrap();
+
+        let mut lines = listing.lines();
+        assert_eq!(lines.next(), Some("0x00010000: 02a58513  addi a0, a1, 42"));
+        assert_eq!(lines.next(), Some("0x00010004: 17e1      addi a5, a5, -8"));
+        assert_eq!(lines.next(), None);
+    }
+
+    #[test]
+    fn disassemble() {
+        let program = program(&[
+            0x008000ef, // jal ra,0x10008
+            0x00000073, // ecall
+            0x02a58513, // addi a0,a1,42
+            0xfe050ae3, // beq a0,zero,0x10000
+            0xffffffff, // an undefined word
+        ]);
+
+        assert_eq!(
+            program.disassemble(),
+            vec![
+                (0x10000, String::from("jal ra, 0x10008")),
+                (0x10004, String::from("ecall")),
+                (0x10008, String::from("addi a0, a1, 42")),
+                (0x1000c, String::from("beq a0, zero, 0x10000")),
+                (0x10010, String::from("<unknown>")),
+            ]
+        );
+
+        let mut symbols = BTreeMap::new();
+        symbols.insert(0x10000, String::from("_start"));
+        symbols.insert(0x10008, String::from("main"));
+
+        let listing = program.disassemble_with_symbols(&symbols);
+        let lines = listing
+            .iter()
+            .map(|(_, line)| line.as_str())
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            lines,
+            vec![
+                "<_start>: jal ra, <main>",
+                "ecall",
+                "<main>: addi a0, a1, 42",
+                "beq a0, zero, <_start>",
+                "<unknown>",
+            ]
+        );
+    }
+
+    #[test]
+    fn write_disassembly_with_symbols() {
+        let program = program(&[
+            0x008000ef, // jal ra,0x10008
+            0xffdff06f, // j 0x10000
+            0x00000073, // ecall
+            0xfe050ce3, // beq a0,zero,0x10004
+        ]);
+
+        let mut symbols = BTreeMap::new();
+        symbols.insert(0x10000, String::from("_start"));
+        symbols.insert(0x10008, String::from("main"));
+
+        let mut listing = Vec::new();
+        program
+            .write_disassembly_with_symbols(&mut listing, &symbols, DisasmStyle::default())
+            .unwrap();
+        let listing = String::from_utf8(listing).unwrap();
+
+        let lines = listing.lines().collect::<Vec<_>>();
+        assert_eq!(lines[0], "0x00010000: 008000ef  jal ra, <main>");
+        assert_eq!(lines[1], "0x00010004: ffdff06f  jal zero, <_start>");
+        assert_eq!(lines[3], "0x0001000c: fe050ce3  beq a0, zero, <_start+0x4>");
+
+        let mut listing = Vec::new();
+        program
+            .write_disassembly(&mut listing, DisasmStyle::default())
+            .unwrap();
+        let listing = String::from_utf8(listing).unwrap();
+
+        assert_eq!(
+            listing.lines().next(),
+            Some("0x00010000: 008000ef  jal ra, 0x10008")
+        );
+    }
+
+    #[test]
+    fn write_aligned_disassembly() {
+        let program = program(&[
+            0x00150513, // addi a0,a0,1
+            0x00b50533, // add a0,a0,a1
+            0xfea5bc23, // sd a0,-8(a1)
+            0x00000073, // ecall
+        ]);
+
+        let mut listing = Vec::new();
+        program
+            .write_disassembly(&mut listing, DisasmStyle::Aligned { mnemonic_width: 6 })
+            .unwrap();
+        let listing = String::from_utf8(listing).unwrap();
+
+        let lines = listing.lines().collect::<Vec<_>>();
+        assert_eq!(lines[0], "0x00010000: 00150513  addi   a0, a0, 1");
+        assert_eq!(lines[1], "0x00010004: 00b50533  add    a0, a0, a1");
+        assert_eq!(lines[2], "0x00010008: fea5bc23  sd     a0, -8(a1)");
+        assert_eq!(lines[3], "0x0001000c: 00000073  ecall");
+
+        let operands = |line: &str| line.find("a0").unwrap();
+        assert!(lines[..3].iter().all(|l| operands(l) == operands(lines[0])));
+    }
+
+    #[test]
+    fn hexdump_data() {
+        let mut program = program(&[]);
+        program.data.content = vec![
+            u64::from_le_bytes(*b"Hello, w"),
+            u64::from_le_bytes(*b"orld!\n\0\0"),
+            0x2a,
+        ];
+
+        let dump = program.hexdump_data();
+        let lines = dump.lines().collect::<Vec<_>>();
+
+        assert_eq!(
+            lines,
+            [
+                "0x00020000: 48 65 6c 6c 6f 2c 20 77 6f 72 6c 64 21 0a 00 00  |Hello, world!...|",
+                "0x00020010: 2a 00 00 00 00 00 00 00                          |*.......|",
+            ]
+        );
+    }
+
+    #[test]
+    fn disassemble_stream() {
+        // addi a0,a1,42; c.addi a5,-8; ecall; half of an addi
+        let input: &[u8] = &[
+            0x13, 0x85, 0xa5, 0x02, 0xe1, 0x17, 0x73, 0x00, 0x00, 0x00, 0x13, 0x85,
+        ];
+
+        let mut listing = Vec::new();
+        super::disassemble_stream(input, 0x10000, &mut listing).unwrap();
+        let listing = String::from_utf8(listing).unwrap();
+
+        let lines = listing.lines().collect::<Vec<_>>();
+        assert_eq!(
+            lines,
+            [
+                "0x00010000: 02a58513  addi a0, a1, 42",
+                "0x00010004: 17e1      addi a5, a5, -8",
+                "0x00010006: 00000073  ecall",
+                "0x0001000a: 8513  <truncated>",
+            ]
+        );
+
+        let mut listing = Vec::new();
+        super::disassemble_stream(&input[..1], 0x10000, &mut listing).unwrap();
+        assert_eq!(listing, b"0x00010000: 13  <truncated>\n");
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn to_json_listing() {
+        let program = program(&[
+            0x00150513, // addi a0,a0,1
+            0x00000073, // ecall
+        ]);
+
+        let listing: serde_json::Value = serde_json::from_str(&program.to_json_listing()).unwrap();
+
+        assert_eq!(listing.as_array().unwrap().len(), 2);
+        assert_eq!(
+            listing[0],
+            serde_json::json!({
+                "address": 0x10000,
+                "width": 4,
+                "raw": 0x00150513,
+                "mnemonic": "addi",
+                "operands": "a0, a0, 1",
+            })
+        );
+        assert_eq!(listing[1]["operands"], "");
+    }
+}
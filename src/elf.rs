@@ -1,31 +1,196 @@
 //! # Load RISC-U ELF64 files
 
 use crate::{
-    iterators::{InstructionIter, LocationIter},
-    DecodingError,
+    decode, decode_one,
+    iterators::{EncodingIter, InstructionIter, LocationIter},
+    DecodingError, Instruction, InstructionWidth, Strictness,
+};
+use byteorder::{BigEndian, ByteOrder, LittleEndian};
+use goblin::elf::{
+    program_header::PT_LOAD,
+    section_header::{SectionHeader, SHT_NOBITS, SHT_PROGBITS},
+    sym::{STT_FUNC, STT_OBJECT},
+    Elf, ProgramHeader,
 };
-use byteorder::{ByteOrder, LittleEndian};
-use goblin::elf::{program_header::PT_LOAD, section_header::SHT_PROGBITS, Elf};
 use log::debug;
-use std::{fs, mem::size_of, ops::Range, path::Path};
+use std::{collections::BTreeMap, fs, mem::size_of, ops::Range, path::Path};
 use thiserror::Error;
 
+/// Page size used to align the initial program break.
+pub const PAGE_SIZE: u64 = 4096;
+
+/// Largest memory the loaded segments may occupy, the 4 GiB of virtual memory of a selfie machine.
+const MAX_MEMORY_SIZE: u64 = 1 << 32;
+
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ProgramSegment<T> {
     pub address: u64,
     pub content: Vec<T>,
 }
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Program {
     pub code: ProgramSegment<u8>,
     pub data: ProgramSegment<u8>,
     pub instruction_range: Range<u64>,
+    /// Number of bytes at the start of the data segment which are initialized from the file,
+    /// the rest of it is zero-initialized (BSS).
+    pub data_file_size: usize,
+    /// Address of the first instruction to execute.
+    pub entry_address: u64,
+    /// Contents of the `.comment` section, if the file has one.
+    pub comment: Option<String>,
+    /// Permissions of the code segment, see [`SegmentFlags`].
+    pub code_flags: SegmentFlags,
+    /// Permissions of the data segment, see [`SegmentFlags`].
+    pub data_flags: SegmentFlags,
+}
+
+/// A loaded program which borrows its segments from the ELF image, see
+/// [`load_object_file_view`].
+#[derive(Clone, Debug)]
+pub struct ProgramView<'a> {
+    pub code: SegmentView<'a>,
+    pub data: SegmentView<'a>,
+    pub instruction_range: Range<u64>,
+    /// Address of the first instruction to execute.
+    pub entry_address: u64,
+    /// Byte order of the file, in which the segments are viewed. [`ProgramView::decode`] reorders
+    /// them like [`Program`] does.
+    pub little_endian: bool,
+}
+
+/// A loaded program which owns a memory mapping of its file and borrows its segments from
+/// there, see [`load_object_file_mmap`].
+#[cfg(feature = "mmap")]
+#[derive(Debug)]
+pub struct MappedProgram {
+    mmap: memmap2::Mmap,
+    layout: ProgramLayout,
+    little_endian: bool,
+}
+
+/// A segment of a [`ProgramView`], the bytes stored in the file followed by zeros up to its
+/// size in memory.
+#[derive(Clone, Copy, Debug)]
+pub struct SegmentView<'a> {
+    pub address: u64,
+    pub content: &'a [u8],
+    pub memory_size: usize,
+    pub flags: SegmentFlags,
+}
+
+/// Permissions of a loaded segment, as given by the flags of its program header.
+///
+/// A RISC-U binary always has a readable and executable code segment and a readable and writable
+/// data segment. Segments which are merged or split by the more lenient loaders get the union of
+/// the flags of their program headers, so a consumer can still fault on writes to read-only
+/// memory.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SegmentFlags {
+    pub read: bool,
+    pub write: bool,
+    pub execute: bool,
+}
+
+impl SegmentFlags {
+    fn new(header: &ProgramHeader) -> Self {
+        Self {
+            read: header.is_read(),
+            write: header.is_write(),
+            execute: header.is_executable(),
+        }
+    }
+
+    fn union(self, other: Self) -> Self {
+        Self {
+            read: self.read || other.read,
+            write: self.write || other.write,
+            execute: self.execute || other.execute,
+        }
+    }
+}
+
+impl<'a> ProgramView<'a> {
+    /// The instructions in the code segment, see [`Program::instructions`].
+    ///
+    /// The bytes are in the byte order of the file.
+    pub fn instructions(&self) -> &'a [u8] {
+        let start = (self.instruction_range.start - self.code.address) as usize;
+        let end = (self.instruction_range.end - self.code.address) as usize;
+
+        // the loader checks that the instructions are stored in the file
+        &self.code.content[start..end]
+    }
+
+    /// Copy the instructions and data out of the view and decode them, with the same result as
+    /// [`Program::decode`] on the program loaded from the same file.
+    pub fn decode(&self) -> Result<DecodedProgram, RiscuError> {
+        check_word_multiples(
+            self.instructions().len(),
+            self.data.memory_size,
+            Strictness::Riscu,
+        )?;
+
+        let mut code = self.instructions().to_vec();
+        if !self.little_endian {
+            for word in code.chunks_exact_mut(size_of::<u32>()) {
+                let instruction = BigEndian::read_u32(word);
+                LittleEndian::write_u32(word, instruction);
+            }
+        }
+
+        let read_u64 = if self.little_endian {
+            LittleEndian::read_u64
+        } else {
+            BigEndian::read_u64
+        };
+
+        // the last word stored in the file may be completed by the zero-initialized part
+        let words = self.data.content.chunks(size_of::<u64>());
+        let mut data = words
+            .map(|word| {
+                let mut bytes = [0; size_of::<u64>()];
+                bytes[..word.len()].copy_from_slice(word);
+                read_u64(&bytes)
+            })
+            .collect::<Vec<_>>();
+        data.resize(self.data.memory_size / size_of::<u64>(), 0);
+
+        Ok(DecodedProgram {
+            code: ProgramSegment {
+                address: self.instruction_range.start,
+                content: code,
+            },
+            data: ProgramSegment {
+                address: self.data.address,
+                content: data,
+            },
+            data_file_size: self.data.content.len(),
+            entry_address: self.entry_address,
+        })
+    }
+}
+
+#[cfg(feature = "mmap")]
+impl MappedProgram {
+    /// The segments, borrowed from the mapping.
+    pub fn view(&self) -> ProgramView<'_> {
+        view_layout(&self.mmap, &self.layout, self.little_endian)
+    }
+
+    /// Decode the mapped program, see [`ProgramView::decode`].
+    pub fn decode(&self) -> Result<DecodedProgram, RiscuError> {
+        self.view().decode()
+    }
 }
 
 impl Program {
     pub fn decode(&self) -> Result<DecodedProgram, RiscuError> {
-        copy_and_decode(self)
+        copy_and_decode(self, Strictness::Riscu)
     }
 
     pub fn instructions(&self) -> &[u8] {
@@ -34,12 +199,121 @@ impl Program {
         let instr = instr_start..instr_end;
         &self.code.content[instr]
     }
+
+    /// The instructions as little-endian 32-bit words, without decoding them.
+    ///
+    /// A trailing part which does not fill a whole word is left out, see [`Program::decode`] to
+    /// reject such a code segment instead.
+    pub fn code_words(&self) -> impl Iterator<Item = u32> + '_ {
+        self.instructions()
+            .chunks_exact(size_of::<u32>())
+            .map(LittleEndian::read_u32)
+    }
+
+    /// The data segment, including its zero-initialized part, as little-endian 64-bit words.
+    ///
+    /// A trailing part which does not fill a whole word is left out like in
+    /// [`Program::code_words`].
+    pub fn data_words(&self) -> impl Iterator<Item = u64> + '_ {
+        self.data
+            .content
+            .chunks_exact(size_of::<u64>())
+            .map(LittleEndian::read_u64)
+    }
+
+    /// Read-only data which shares the code segment with the instructions and is placed behind
+    /// them, as emitted by toolchains producing a single R+X segment for `.text` and `.rodata`.
+    ///
+    /// Empty if the code segment holds nothing but instructions.
+    pub fn rodata(&self) -> ProgramSegment<u8> {
+        let start = (self.instruction_range.end - self.code.address) as usize;
+
+        ProgramSegment {
+            address: self.instruction_range.end,
+            content: self.code.content[start..].to_vec(),
+        }
+    }
+
+    /// Toolchain which produced the file, as recorded in the first entry of the `.comment`
+    /// section (e.g. `GCC: (GNU) 10.2.0`).
+    ///
+    /// `None` if the file has no `.comment` section or the section is empty.
+    pub fn producer_comment(&self) -> Option<String> {
+        self.comment
+            .as_deref()?
+            .split('\0')
+            .map(str::trim)
+            .find(|entry| !entry.is_empty())
+            .map(String::from)
+    }
 }
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct DecodedProgram {
     pub code: ProgramSegment<u8>,
     pub data: ProgramSegment<u64>,
+    /// See [`Program::data_file_size`].
+    pub data_file_size: usize,
+    /// Address of the first instruction to execute.
+    pub entry_address: u64,
+}
+
+/// Entry and segment sizes of an ELF file, as read by [`load_metadata`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ElfMetadata {
+    /// Address of the first instruction to execute.
+    pub entry_address: u64,
+    /// Size of the code segment in memory, see [`Program::code`].
+    pub code_length: usize,
+    /// Size of the data segment in memory (including its zero-initialized part), see
+    /// [`Program::data`].
+    pub data_length: usize,
+}
+
+/// A section of an ELF file, as read by [`load_sections`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SectionInfo {
+    pub name: String,
+    /// Virtual address of the section, 0 if it is not loaded.
+    pub addr: u64,
+    /// Offset of the section in the file.
+    pub offset: u64,
+    pub size: u64,
+    /// The `SHF_*` flags of the section header, e.g. `SHF_EXECINSTR` for `.text`.
+    pub flags: u64,
+}
+
+/// Lookup from addresses to the index of the instruction they belong to, which also works for
+/// code mixing compressed and full instructions. Built with
+/// [`DecodedProgram::build_address_index`].
+#[derive(Clone, Debug)]
+pub struct AddressIndex {
+    starts: BTreeMap<u64, usize>,
+    end: u64,
+}
+
+impl AddressIndex {
+    /// Index of the instruction covering `address`, in the order of
+    /// [`DecodedProgram::iter_instructions`]. Addresses within an instruction map to it as well.
+    pub fn lookup(&self, address: u64) -> Option<usize> {
+        if address >= self.end {
+            return None;
+        }
+
+        self.starts
+            .range(..=address)
+            .next_back()
+            .map(|(_, idx)| *idx)
+    }
+}
+
+/// Split of the data segment into the part initialized from the file and the zero-initialized
+/// part (BSS).
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DataLayout {
+    pub initialized: Range<u64>,
+    pub bss: Range<u64>,
 }
 
 impl DecodedProgram {
@@ -50,6 +324,123 @@ impl DecodedProgram {
     pub fn iter_instructions(&self) -> InstructionIter<'_> {
         InstructionIter::new(&self.code.content)
     }
+
+    /// Byte offset into the code segment, width and instruction of every encoding in the code
+    /// segment, in order. Encodings which can not be decoded are left out.
+    ///
+    /// Re-encoding a full instruction with `u32::from` reproduces its original bytes, while
+    /// compressed instructions re-encode to their 32-bit expansion.
+    pub fn layout(&self) -> Vec<(u64, InstructionWidth, Instruction)> {
+        EncodingIter::new(&self.code.content, 0)
+            .filter_map(|(offset, raw, length)| {
+                let width = match length {
+                    2 => InstructionWidth::Compressed,
+                    _ => InstructionWidth::Full,
+                };
+
+                decode(raw)
+                    .ok()
+                    .map(|instruction| (offset, width, instruction))
+            })
+            .collect()
+    }
+
+    /// Decode every instruction of the code segment, failing with the address of the first
+    /// encoding which can not be decoded instead of panicking like
+    /// [`iter_instructions`](Self::iter_instructions).
+    pub fn try_instructions(&self) -> Result<Vec<Instruction>, RiscuError> {
+        EncodingIter::new(&self.code.content, self.code.address)
+            .map(|(address, raw, _)| {
+                decode(raw).map_err(|error| RiscuError::DecodingError { address, error })
+            })
+            .collect()
+    }
+
+    /// Map the address of every instruction in the code segment to its index.
+    pub fn build_address_index(&self) -> AddressIndex {
+        let mut starts = BTreeMap::new();
+        let mut end = self.code.address;
+
+        for (idx, (address, _, length)) in
+            EncodingIter::new(&self.code.content, self.code.address).enumerate()
+        {
+            starts.insert(address, idx);
+            end = address + length as u64;
+        }
+
+        AddressIndex { starts, end }
+    }
+
+    /// Decode the instruction at `address`, like a processor fetching it with its program counter
+    /// at `address`.
+    ///
+    /// `None` if `address` is outside of the code segment, not aligned to a word or if the encoding
+    /// at `address` can not be decoded. Compressed instructions between two words are found with
+    /// [`build_address_index`](Self::build_address_index) instead.
+    pub fn instruction_at(&self, address: u64) -> Option<Instruction> {
+        if address % 4 != 0 || !self.code_range().contains(&address) {
+            return None;
+        }
+
+        let offset = (address - self.code.address) as usize;
+
+        decode_one(&self.code.content[offset..])
+            .ok()
+            .map(|(instruction, _)| instruction)
+    }
+
+    /// Virtual address range covered by the code segment.
+    pub fn code_range(&self) -> Range<u64> {
+        self.code.address..(self.code.address + self.code.content.len() as u64)
+    }
+
+    /// Virtual address range covered by the data segment.
+    pub fn data_range(&self) -> Range<u64> {
+        let size = (self.data.content.len() * size_of::<u64>()) as u64;
+
+        self.data.address..(self.data.address + size)
+    }
+
+    /// Virtual address ranges of the initialized and zero-initialized part of the data segment.
+    pub fn data_layout(&self) -> DataLayout {
+        let range = self.data_range();
+        let bss_start = range.start + self.data_file_size as u64;
+
+        DataLayout {
+            initialized: range.start..bss_start,
+            bss: bss_start..range.end,
+        }
+    }
+
+    /// Initial program break, i.e. the page-aligned end of the data segment (including its
+    /// zero-initialized part), where the heap starts growing.
+    pub fn program_break(&self) -> u64 {
+        let end = self.data_range().end;
+
+        (end + PAGE_SIZE - 1) & !(PAGE_SIZE - 1)
+    }
+
+    /// Hash of the entry and segment addresses, code and data, which is stable across runs and
+    /// platforms (64-bit FNV-1a) and thus suitable for caching and change detection.
+    pub fn content_hash(&self) -> u64 {
+        const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+        const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+        let entry = self.entry_address.to_le_bytes();
+        let code = self.code.address.to_le_bytes();
+        let data = self.data.address.to_le_bytes();
+
+        entry
+            .iter()
+            .chain(code.iter())
+            .chain(self.code.content.iter())
+            .chain(data.iter())
+            .copied()
+            .chain(self.data.content.iter().flat_map(|w| w.to_le_bytes()))
+            .fold(FNV_OFFSET_BASIS, |hash, byte| {
+                (hash ^ u64::from(byte)).wrapping_mul(FNV_PRIME)
+            })
+    }
 }
 
 #[derive(Error, Debug)]
@@ -57,36 +448,532 @@ pub enum RiscuError {
     #[error("Error while reading file: {0}")]
     CouldNotReadFile(std::io::Error),
 
+    #[error("Error while writing file: {0}")]
+    CouldNotWriteFile(std::io::Error),
+
     #[error("Error while parsing ELF: {0}")]
     InvalidElf(goblin::error::Error),
 
     #[error("ELF is not a valid RISC-U ELF file: {0}")]
     InvalidRiscu(&'static str),
 
-    #[error("Failure during decode: {0:?}")]
-    DecodingError(DecodingError),
+    #[error("Failure during decode at {address:#x}: {error:?}")]
+    DecodingError {
+        /// Virtual address of the offending encoding.
+        address: u64,
+        error: DecodingError,
+    },
+}
+
+/// Deviations from the conventions of selfie which do not prevent loading a binary.
+#[derive(Error, Clone, Copy, Debug, Eq, PartialEq)]
+pub enum LoaderWarning {
+    #[error("code segment is writable")]
+    WritableCode,
+
+    #[error("binary has {0} loadable segments instead of 2")]
+    ExtraSegments(usize),
+
+    #[error("segment at {0:#x} is not page aligned")]
+    UnalignedSegment(u64),
+
+    #[error("code segment is padded with {0} bytes of zero or illegal words")]
+    TrailingPadding(usize),
 }
 
 pub fn load_object_file<P>(object_file: P) -> Result<Program, RiscuError>
+where
+    P: AsRef<Path>,
+{
+    load_object_file_with_strictness(object_file, Strictness::Riscu)
+}
+
+/// Load an ELF file, checking its segments according to `strictness`.
+pub fn load_object_file_with_strictness<P>(
+    object_file: P,
+    strictness: Strictness,
+) -> Result<Program, RiscuError>
 where
     P: AsRef<Path>,
 {
     fs::read(object_file)
         .map_err(RiscuError::CouldNotReadFile)
-        .and_then(|buffer| {
-            Elf::parse(&buffer)
-                .map_err(RiscuError::InvalidElf)
-                .and_then(|elf| extract_program(&buffer, &elf))
+        .and_then(|buffer| parse_object_file(&buffer, strictness, &mut Vec::new()))
+}
+
+/// Load an ELF image which is already in memory, e.g. received over the network or embedded
+/// with `include_bytes!`, like [`load_object_file`].
+pub fn load_object_file_from_bytes(raw: &[u8]) -> Result<Program, RiscuError> {
+    parse_object_file(raw, Strictness::Riscu, &mut Vec::new())
+}
+
+/// Load and decode an ELF image which is already in memory, see
+/// [`load_object_file_from_bytes`].
+pub fn load_and_decode_object_file_from_bytes(raw: &[u8]) -> Result<DecodedProgram, RiscuError> {
+    load_object_file_from_bytes(raw)?.decode()
+}
+
+/// Load an ELF image which has already been parsed with goblin, e.g. to read other metadata,
+/// without parsing it again like [`load_object_file_from_bytes`] does.
+///
+/// `elf` has to be parsed from `raw`, which holds the contents of the segments. The goblin
+/// version of this crate is re-exported as [`crate::goblin`].
+pub fn load_object_file_from_elf(raw: &[u8], elf: &Elf) -> Result<Program, RiscuError> {
+    extract_program(raw, elf, Strictness::Riscu, &mut Vec::new())
+}
+
+/// Load and decode an ELF image which has already been parsed, see
+/// [`load_object_file_from_elf`].
+pub fn load_and_decode_object_file_from_elf(
+    raw: &[u8],
+    elf: &Elf,
+) -> Result<DecodedProgram, RiscuError> {
+    load_object_file_from_elf(raw, elf)?.decode()
+}
+
+/// Load an ELF image which is already in memory like [`load_object_file_from_bytes`], but
+/// borrow the segments from `raw` instead of copying them.
+///
+/// This avoids a copy of large binaries which are only read. The segments of big-endian files
+/// are viewed in the byte order of the file, see [`ProgramView::little_endian`].
+pub fn load_object_file_view(raw: &[u8]) -> Result<ProgramView<'_>, RiscuError> {
+    let elf = Elf::parse(raw).map_err(RiscuError::InvalidElf)?;
+    let layout = extract_layout(raw, &elf, Strictness::Riscu, &mut Vec::new())?;
+
+    Ok(view_layout(raw, &layout, elf.little_endian))
+}
+
+/// Memory-map an ELF file and load it like [`load_object_file_view`], without reading or copying
+/// the segments.
+///
+/// The returned program owns the mapping, which lives as long as the program. The file must not
+/// be modified while it is mapped.
+#[cfg(feature = "mmap")]
+pub fn load_object_file_mmap<P>(object_file: P) -> Result<MappedProgram, RiscuError>
+where
+    P: AsRef<Path>,
+{
+    let file = fs::File::open(object_file).map_err(RiscuError::CouldNotReadFile)?;
+
+    // SAFETY: the mapping is read-only and only valid while the file is not modified, as
+    // documented above
+    let mmap = unsafe { memmap2::Mmap::map(&file) }.map_err(RiscuError::CouldNotReadFile)?;
+
+    let elf = Elf::parse(&mmap).map_err(RiscuError::InvalidElf)?;
+    let layout = extract_layout(&mmap, &elf, Strictness::Riscu, &mut Vec::new())?;
+    let little_endian = elf.little_endian;
+
+    Ok(MappedProgram {
+        mmap,
+        layout,
+        little_endian,
+    })
+}
+
+fn view_layout<'a>(raw: &'a [u8], layout: &ProgramLayout, little_endian: bool) -> ProgramView<'a> {
+    let view = |segment: &SegmentLayout| SegmentView {
+        address: segment.address,
+        content: &raw[segment.file_range.clone()],
+        memory_size: segment.memory_size,
+        flags: segment.flags,
+    };
+
+    ProgramView {
+        code: view(&layout.code),
+        data: view(&layout.data),
+        instruction_range: layout.instruction_range.clone(),
+        entry_address: layout.entry_address,
+        little_endian,
+    }
+}
+
+/// Check an ELF file like [`load_object_file`] and read its entry and segment sizes, without
+/// copying the segments.
+pub fn load_metadata<P>(object_file: P) -> Result<ElfMetadata, RiscuError>
+where
+    P: AsRef<Path>,
+{
+    let buffer = fs::read(object_file).map_err(RiscuError::CouldNotReadFile)?;
+    let elf = Elf::parse(&buffer).map_err(RiscuError::InvalidElf)?;
+    let layout = extract_layout(&buffer, &elf, Strictness::Riscu, &mut Vec::new())?;
+
+    Ok(ElfMetadata {
+        entry_address: layout.entry_address,
+        code_length: layout.code.memory_size,
+        data_length: layout.data.memory_size,
+    })
+}
+
+/// Load and decode an ELF file to be placed `load_bias` bytes above its link-time addresses.
+///
+/// Only the entry and segment addresses are shifted, instructions are not rewritten. Hence, the
+/// code must not depend on absolute addresses.
+pub fn load_and_decode_object_file_relocated<P>(
+    object_file: P,
+    load_bias: u64,
+) -> Result<DecodedProgram, RiscuError>
+where
+    P: AsRef<Path>,
+{
+    let mut program = load_object_file(object_file)?.decode()?;
+
+    program.entry_address = program.entry_address.wrapping_add(load_bias);
+    program.code.address = program.code.address.wrapping_add(load_bias);
+    program.data.address = program.data.address.wrapping_add(load_bias);
+
+    Ok(program)
+}
+
+/// Leniently load and decode an ELF file, reporting deviations from the conventions of selfie
+/// as warnings instead of rejecting the binary.
+pub fn load_and_decode_object_file_with_warnings<P>(
+    object_file: P,
+) -> Result<(DecodedProgram, Vec<LoaderWarning>), RiscuError>
+where
+    P: AsRef<Path>,
+{
+    let buffer = fs::read(object_file).map_err(RiscuError::CouldNotReadFile)?;
+
+    let mut warnings = Vec::new();
+    let program = parse_object_file(&buffer, Strictness::Lenient, &mut warnings)?;
+    let program = copy_and_decode(&program, Strictness::Lenient)?;
+
+    let padding = program.code.content.len() - program.code_end_offset();
+    if padding > 0 {
+        warnings.push(LoaderWarning::TrailingPadding(padding));
+    }
+
+    Ok((program, warnings))
+}
+
+/// Load an ELF file with any number of loadable segments, as produced by GCC or LLVM, instead of
+/// the code and data segment of selfie.
+///
+/// All writable segments are merged into the data segment and all other segments, including
+/// read-only data, into the code segment, filling the gaps between them with zeros. The entry
+/// address has to lie within an executable segment.
+pub fn load_object_file_relaxed<P>(object_file: P) -> Result<Program, RiscuError>
+where
+    P: AsRef<Path>,
+{
+    let buffer = fs::read(object_file).map_err(RiscuError::CouldNotReadFile)?;
+    let elf = Elf::parse(&buffer).map_err(RiscuError::InvalidElf)?;
+
+    extract_relaxed_program(&buffer, &elf)
+}
+
+/// Load and decode an ELF file into a map from the address of every instruction in the code
+/// segment to the instruction, e.g. to look up instructions by address in a disassembler.
+///
+/// Fails with the address of the first encoding which can not be decoded, like
+/// [`DecodedProgram::try_instructions`].
+pub fn load_and_index_object_file<P>(
+    object_file: P,
+) -> Result<BTreeMap<u64, Instruction>, RiscuError>
+where
+    P: AsRef<Path>,
+{
+    let program = load_object_file(object_file)?.decode()?;
+
+    EncodingIter::new(&program.code.content, program.code.address)
+        .map(|(address, raw, _)| match decode(raw) {
+            Ok(instruction) => Ok((address, instruction)),
+            Err(error) => Err(RiscuError::DecodingError { address, error }),
+        })
+        .collect()
+}
+
+/// Read the function and object symbols of an ELF file from `.symtab`, keyed by their address,
+/// e.g. to label addresses in a disassembly.
+///
+/// Symbols without a name are skipped. Of several symbols at the same address, the last one in
+/// the symbol table is kept.
+pub fn load_symbols<P>(object_file: P) -> Result<BTreeMap<u64, String>, RiscuError>
+where
+    P: AsRef<Path>,
+{
+    let buffer = fs::read(object_file).map_err(RiscuError::CouldNotReadFile)?;
+    let elf = Elf::parse(&buffer).map_err(RiscuError::InvalidElf)?;
+
+    Ok(elf
+        .syms
+        .iter()
+        .filter(|sym| matches!(sym.st_type(), STT_FUNC | STT_OBJECT))
+        .filter_map(|sym| match elf.strtab.get(sym.st_name) {
+            Some(Ok(name)) if !name.is_empty() => Some((sym.st_value, name.to_string())),
+            _ => None,
+        })
+        .collect())
+}
+
+/// Read the section headers of an ELF file, e.g. to find the boundaries of `.text`, `.rodata` and
+/// `.data` which are merged into segments by the loader.
+///
+/// Sections without a name, like the null section at index 0, are skipped.
+pub fn load_sections<P>(object_file: P) -> Result<Vec<SectionInfo>, RiscuError>
+where
+    P: AsRef<Path>,
+{
+    let buffer = fs::read(object_file).map_err(RiscuError::CouldNotReadFile)?;
+    let elf = Elf::parse(&buffer).map_err(RiscuError::InvalidElf)?;
+
+    Ok(elf
+        .section_headers
+        .iter()
+        .filter_map(|sh| match elf.shdr_strtab.get(sh.sh_name) {
+            Some(Ok(name)) if !name.is_empty() => Some(SectionInfo {
+                name: name.to_string(),
+                addr: sh.sh_addr,
+                offset: sh.sh_offset,
+                size: sh.sh_size,
+                flags: sh.sh_flags,
+            }),
+            _ => None,
         })
+        .collect())
+}
+
+fn parse_object_file(
+    raw: &[u8],
+    strictness: Strictness,
+    warnings: &mut Vec<LoaderWarning>,
+) -> Result<Program, RiscuError> {
+    let elf = Elf::parse(raw).map_err(RiscuError::InvalidElf)?;
+
+    extract_program(raw, &elf, strictness, warnings)
+}
+
+fn extract_program(
+    raw: &[u8],
+    elf: &Elf,
+    strictness: Strictness,
+    warnings: &mut Vec<LoaderWarning>,
+) -> Result<Program, RiscuError> {
+    let layout = extract_layout(raw, elf, strictness, warnings)?;
+
+    let program = Program {
+        code: layout.code.load(raw),
+        data: layout.data.load(raw),
+        instruction_range: layout.instruction_range,
+        data_file_size: layout.data.file_range.len(),
+        entry_address: layout.entry_address,
+        comment: read_comment(raw, elf),
+        code_flags: layout.code.flags,
+        data_flags: layout.data.flags,
+    };
+
+    Ok(into_little_endian(program, elf))
+}
+
+/// Reorder the segments of a big-endian file to little endian, the byte order in which code and
+/// data are read everywhere else: the instructions as 32-bit words and the data as 64-bit words.
+///
+/// Read-only data sharing the code segment is left as it is, as is a trailing part which does
+/// not fill a whole word.
+fn into_little_endian(mut program: Program, elf: &Elf) -> Program {
+    if elf.little_endian {
+        return program;
+    }
+
+    let start = (program.instruction_range.start - program.code.address) as usize;
+    let end = (program.instruction_range.end - program.code.address) as usize;
+
+    for word in program.code.content[start..end].chunks_exact_mut(size_of::<u32>()) {
+        let instruction = BigEndian::read_u32(word);
+        LittleEndian::write_u32(word, instruction);
+    }
+
+    for word in program.data.content.chunks_exact_mut(size_of::<u64>()) {
+        let value = BigEndian::read_u64(word);
+        LittleEndian::write_u64(word, value);
+    }
+
+    program
+}
+
+fn read_comment(raw: &[u8], elf: &Elf) -> Option<String> {
+    let section = elf.section_headers.iter().find(|sh| {
+        sh.sh_type != SHT_NOBITS && matches!(elf.shdr_strtab.get(sh.sh_name), Some(Ok(".comment")))
+    })?;
+
+    raw.get(section.file_range())
+        .map(|content| String::from_utf8_lossy(content).into_owned())
+}
+
+/// Where a segment is stored in the file and how much memory it occupies once loaded.
+#[derive(Debug)]
+struct SegmentLayout {
+    address: u64,
+    file_range: Range<usize>,
+    memory_size: usize,
+    flags: SegmentFlags,
 }
 
-fn extract_program(raw: &[u8], elf: &Elf) -> Result<Program, RiscuError> {
-    if elf.is_lib || !elf.is_64 || !elf.little_endian {
+impl SegmentLayout {
+    fn new(header: &ProgramHeader) -> Result<Self, RiscuError> {
+        let file_range = header.file_range();
+        let zero_filled =
+            header
+                .p_memsz
+                .checked_sub(header.p_filesz)
+                .ok_or(RiscuError::InvalidRiscu(
+                    "segment memory size smaller than file size",
+                ))?;
+
+        Ok(Self {
+            address: header.p_vaddr,
+            memory_size: file_range.len() + zero_filled as usize,
+            file_range,
+            flags: SegmentFlags::new(header),
+        })
+    }
+
+    /// Copy the segment out of the file and zero-fill the rest of its memory.
+    fn load(&self, raw: &[u8]) -> ProgramSegment<u8> {
+        let mut content = raw[self.file_range.clone()].to_vec();
+        content.resize(self.memory_size, 0);
+
+        ProgramSegment {
+            address: self.address,
+            content,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct ProgramLayout {
+    code: SegmentLayout,
+    data: SegmentLayout,
+    instruction_range: Range<u64>,
+    entry_address: u64,
+}
+
+fn check_elf_header(elf: &Elf) -> Result<(), RiscuError> {
+    if elf.is_lib || !elf.is_64 {
         return Err(RiscuError::InvalidRiscu(
-            "has to be an executable, 64bit, static, little endian binary",
+            "has to be an executable, 64bit, static binary",
         ));
     }
 
+    Ok(())
+}
+
+/// Reject loadable segments whose contents do not lie within the file, as declared by a
+/// malformed or truncated file, before they are read.
+fn check_file_ranges(raw: &[u8], elf: &Elf) -> Result<(), RiscuError> {
+    let within_file = |ph: &ProgramHeader| match ph.p_offset.checked_add(ph.p_filesz) {
+        Some(end) => end <= raw.len() as u64,
+        None => false,
+    };
+
+    if elf
+        .program_headers
+        .iter()
+        .filter(|ph| ph.p_type == PT_LOAD)
+        .all(within_file)
+    {
+        Ok(())
+    } else {
+        Err(RiscuError::InvalidRiscu("segment range exceeds file size"))
+    }
+}
+
+/// Reject loadable segments which end beyond the address space or occupy more memory than a
+/// program may use, before their ranges are computed or their memory is allocated.
+fn check_memory_ranges(elf: &Elf) -> Result<(), RiscuError> {
+    let within_memory = |ph: &ProgramHeader| {
+        ph.p_memsz <= MAX_MEMORY_SIZE && ph.p_vaddr.checked_add(ph.p_memsz).is_some()
+    };
+
+    if elf
+        .program_headers
+        .iter()
+        .filter(|ph| ph.p_type == PT_LOAD)
+        .all(within_memory)
+    {
+        Ok(())
+    } else {
+        Err(RiscuError::InvalidRiscu(
+            "segment range exceeds memory size",
+        ))
+    }
+}
+
+fn extract_layout(
+    raw: &[u8],
+    elf: &Elf,
+    strictness: Strictness,
+    warnings: &mut Vec<LoaderWarning>,
+) -> Result<ProgramLayout, RiscuError> {
+    check_file_ranges(raw, elf)?;
+    check_memory_ranges(elf)?;
+
+    let layout = extract_segments(elf, strictness, warnings)?;
+
+    // compressed instructions are only aligned to half-words
+    let alignment = if strictness == Strictness::Lenient {
+        2
+    } else {
+        4
+    };
+    let code = &layout.code;
+
+    if layout.entry_address < code.address
+        || layout.entry_address >= code.address + code.memory_size as u64
+    {
+        return Err(RiscuError::InvalidRiscu(
+            "entry address not in code segment",
+        ));
+    }
+
+    if layout.entry_address % alignment != 0 {
+        return Err(RiscuError::InvalidRiscu(
+            "entry address not aligned to an instruction",
+        ));
+    }
+
+    // instructions are never zero-initialized, so they have to be stored in the file
+    let code_file_end = code.address + code.file_range.len() as u64;
+    check_instruction_range(&layout.instruction_range, code.address..code_file_end)?;
+
+    Ok(layout)
+}
+
+/// Memory range of a section, rejecting one which ends beyond the address space.
+fn section_range(section: &SectionHeader) -> Result<Range<u64>, RiscuError> {
+    match section.sh_addr.checked_add(section.sh_size) {
+        Some(end) => Ok(section.sh_addr..end),
+        None => Err(RiscuError::InvalidRiscu(
+            "instructions not within code segment",
+        )),
+    }
+}
+
+/// Reject instructions, as declared by the executable section, which do not lie within the code
+/// segment and could not be sliced out of it.
+fn check_instruction_range(
+    instruction_range: &Range<u64>,
+    code_range: Range<u64>,
+) -> Result<(), RiscuError> {
+    if instruction_range.start >= code_range.start
+        && instruction_range.start <= instruction_range.end
+        && instruction_range.end <= code_range.end
+    {
+        Ok(())
+    } else {
+        Err(RiscuError::InvalidRiscu(
+            "instructions not within code segment",
+        ))
+    }
+}
+
+fn extract_segments(
+    elf: &Elf,
+    strictness: Strictness,
+    warnings: &mut Vec<LoaderWarning>,
+) -> Result<ProgramLayout, RiscuError> {
+    check_elf_header(elf)?;
+
     let mut ph_iter = elf
         .program_headers
         .as_slice()
@@ -110,6 +997,16 @@ fn extract_program(raw: &[u8], elf: &Elf) -> Result<Program, RiscuError> {
         elf.header.e_phnum
     );
 
+    if let (Some(segment), None) = (ph_iter.clone().next(), ph_iter.clone().nth(1)) {
+        if strictness == Strictness::Lenient
+            && segment.is_read()
+            && segment.is_write()
+            && segment.is_executable()
+        {
+            return split_single_segment(segment, sh_iter, elf.entry, warnings);
+        }
+    }
+
     if elf.header.e_phnum < 2
         || ph_iter.clone().count() < 2
         || usize::from(elf.header.e_phnum) < ph_iter.clone().count()
@@ -119,15 +1016,25 @@ fn extract_program(raw: &[u8], elf: &Elf) -> Result<Program, RiscuError> {
         ));
     }
 
+    if ph_iter.clone().count() != 2 {
+        if strictness == Strictness::Strict {
+            return Err(RiscuError::InvalidRiscu(
+                "must have exactly 2 loadable program segments",
+            ));
+        }
+
+        warnings.push(LoaderWarning::ExtraSegments(ph_iter.clone().count()));
+    }
+
     // println!("{:#?}", ph_iter
     //   .clone()
     //   .find(|ph| !ph.is_write() && ph.is_read() && ph.is_executable())
     // );
 
-    let code_segment_header = match ph_iter
-        .clone()
-        .find(|ph| !ph.is_write() && ph.is_read() && ph.is_executable())
-    {
+    // a lenient load also accepts code segments which are writable
+    let code_segment_header = match ph_iter.clone().find(|ph| {
+        (strictness == Strictness::Lenient || !ph.is_write()) && ph.is_read() && ph.is_executable()
+    }) {
         Some(segment) => segment,
         None => {
             return Err(RiscuError::InvalidRiscu(
@@ -151,30 +1058,41 @@ fn extract_program(raw: &[u8], elf: &Elf) -> Result<Program, RiscuError> {
             }
         };
 
-    let code_start = code_segment_header.p_vaddr;
-    let code_segment = &raw[code_segment_header.file_range()];
-    let code_padding = (code_segment_header.p_memsz - code_segment_header.p_filesz) as usize;
+    if code_segment_header.vm_range().start < data_segment_header.vm_range().end
+        && data_segment_header.vm_range().start < code_segment_header.vm_range().end
+    {
+        return Err(RiscuError::InvalidRiscu("code and data segments overlap"));
+    }
+
+    if code_segment_header.is_write() {
+        warnings.push(LoaderWarning::WritableCode);
+    }
+
+    for header in [code_segment_header, data_segment_header].iter() {
+        if header.p_vaddr % PAGE_SIZE != 0 {
+            warnings.push(LoaderWarning::UnalignedSegment(header.p_vaddr));
+        }
+    }
 
-    let data_start = data_segment_header.p_vaddr;
-    let data_segment = &raw[data_segment_header.file_range()];
-    let data_padding = (data_segment_header.p_memsz - data_segment_header.p_filesz) as usize;
+    let code = SegmentLayout::new(code_segment_header)?;
+    let data = SegmentLayout::new(data_segment_header)?;
 
     let instruction_range = match sh_iter.find(|sh| !sh.is_writable() && sh.is_executable()) {
-        Some(section) => (section.vm_range().start as u64)..(section.vm_range().end as u64),
-        None => code_start..(code_start + code_segment.len() as u64),
+        Some(section) => section_range(section)?,
+        None => code.address..(code.address + code.file_range.len() as u64),
     };
 
     debug!(
         "Code : start={:#010x} size={:#010x} padding={:#010x}",
-        code_start,
-        code_segment.len(),
-        code_padding
+        code.address,
+        code.file_range.len(),
+        code.memory_size - code.file_range.len()
     );
     debug!(
         "Data : start={:#010x} size={:#010x} padding={:#010x}",
-        data_start,
-        data_segment.len(),
-        data_padding
+        data.address,
+        data.file_range.len(),
+        data.memory_size - data.file_range.len()
     );
     debug!(
         "Instr: start={:#010x} size={:#010x}",
@@ -182,20 +1100,200 @@ fn extract_program(raw: &[u8], elf: &Elf) -> Result<Program, RiscuError> {
         instruction_range.end - instruction_range.start
     );
 
-    Ok(Program {
-        code: ProgramSegment {
-            address: code_start,
-            content: [code_segment.to_vec(), vec![0; code_padding]].concat(),
+    Ok(ProgramLayout {
+        code,
+        data,
+        instruction_range,
+        entry_address: elf.entry,
+    })
+}
+
+/// Split a single readable, writable and executable segment, as found in flat images, into code
+/// (up to the end of the executable section) and data (the rest of the segment).
+fn split_single_segment<'a>(
+    segment: &ProgramHeader,
+    mut sections: impl Iterator<Item = &'a SectionHeader>,
+    entry_address: u64,
+    warnings: &mut Vec<LoaderWarning>,
+) -> Result<ProgramLayout, RiscuError> {
+    let text = match sections.find(|sh| sh.is_executable()) {
+        Some(section) => section_range(section)?,
+        None => {
+            return Err(RiscuError::InvalidRiscu(
+                "executable section of single segment is missing",
+            ))
+        }
+    };
+
+    if segment.p_memsz < segment.p_filesz {
+        return Err(RiscuError::InvalidRiscu(
+            "segment memory size smaller than file size",
+        ));
+    }
+
+    let start = segment.p_vaddr;
+    let end = segment.p_vaddr + segment.p_memsz;
+    let split = text.end;
+
+    if text.start < start || split > end {
+        return Err(RiscuError::InvalidRiscu(
+            "executable section not within single segment",
+        ));
+    }
+
+    warnings.push(LoaderWarning::WritableCode);
+
+    let file = segment.file_range();
+    let code_size = (split - start) as usize;
+    let file_split = file.start + code_size.min(file.len());
+    let flags = SegmentFlags::new(segment);
+
+    debug!(
+        "Single segment: start={:#010x} split={:#010x} end={:#010x}",
+        start, split, end
+    );
+
+    Ok(ProgramLayout {
+        code: SegmentLayout {
+            address: start,
+            file_range: file.start..file_split,
+            memory_size: code_size,
+            flags,
         },
-        data: ProgramSegment {
-            address: data_start,
-            content: [data_segment.to_vec(), vec![0; data_padding]].concat(),
+        data: SegmentLayout {
+            address: split,
+            file_range: file_split..file.end,
+            memory_size: (end - split) as usize,
+            flags,
         },
-        instruction_range,
+        instruction_range: text.start..split,
+        entry_address,
     })
 }
 
-fn copy_and_decode(program: &Program) -> Result<DecodedProgram, RiscuError> {
+fn extract_relaxed_program(raw: &[u8], elf: &Elf) -> Result<Program, RiscuError> {
+    check_elf_header(elf)?;
+    check_file_ranges(raw, elf)?;
+    check_memory_ranges(elf)?;
+
+    let (data_headers, code_headers): (Vec<_>, Vec<_>) = elf
+        .program_headers
+        .iter()
+        .filter(|ph| ph.p_type == PT_LOAD)
+        .partition(|ph| ph.is_write());
+
+    let executable = code_headers
+        .iter()
+        .filter(|ph| ph.is_executable())
+        .collect::<Vec<_>>();
+
+    if !executable
+        .iter()
+        .any(|ph| ph.vm_range().contains(&(elf.entry as usize)))
+    {
+        return Err(RiscuError::InvalidRiscu(
+            "entry address is not within an executable segment",
+        ));
+    }
+
+    let (code, _, code_flags) = merge_segments(raw, &code_headers)?;
+    let (data, data_file_size, data_flags) = merge_segments(raw, &data_headers)?;
+
+    let code_range = code.address..(code.address + code.content.len() as u64);
+    let data_range = data.address..(data.address + data.content.len() as u64);
+
+    if code_range.start < data_range.end && data_range.start < code_range.end {
+        return Err(RiscuError::InvalidRiscu("code and data segments overlap"));
+    }
+
+    let text = elf.section_headers.iter().find(|sh| {
+        sh.sh_type == SHT_PROGBITS
+            && !sh.is_writable()
+            && sh.is_executable()
+            && code_range.contains(&sh.sh_addr)
+    });
+
+    let instruction_range = match text {
+        Some(section) => section_range(section)?,
+        None => {
+            let start = executable.iter().map(|ph| ph.p_vaddr).min().unwrap_or(0);
+            let end = executable
+                .iter()
+                .map(|ph| ph.p_vaddr + ph.p_filesz.min(ph.p_memsz))
+                .max();
+
+            start..end.unwrap_or(0)
+        }
+    };
+
+    check_instruction_range(&instruction_range, code_range)?;
+
+    let program = Program {
+        code,
+        data,
+        instruction_range,
+        data_file_size,
+        entry_address: elf.entry,
+        comment: read_comment(raw, elf),
+        code_flags,
+        data_flags,
+    };
+
+    Ok(into_little_endian(program, elf))
+}
+
+/// Merge segments into one, placing each of them at its address and filling the gaps with zeros.
+/// Also returns the number of bytes up to the end of the last part loaded from the file and the
+/// union of the flags of all segments.
+fn merge_segments(
+    raw: &[u8],
+    headers: &[&ProgramHeader],
+) -> Result<(ProgramSegment<u8>, usize, SegmentFlags), RiscuError> {
+    let start = headers.iter().map(|ph| ph.p_vaddr).min().unwrap_or(0);
+    let end = headers.iter().map(|ph| ph.p_vaddr + ph.p_memsz).max();
+    let size = end.unwrap_or(0) - start;
+
+    // segments far apart would be merged into a gap of zeros
+    if size > MAX_MEMORY_SIZE {
+        return Err(RiscuError::InvalidRiscu(
+            "segment range exceeds memory size",
+        ));
+    }
+
+    let mut content = vec![0; size as usize];
+    let mut initialized = 0;
+    let mut flags = SegmentFlags::default();
+
+    for header in headers {
+        let offset = (header.p_vaddr - start) as usize;
+        let file = &raw[header.file_range()];
+        let file = &file[..file.len().min(header.p_memsz as usize)];
+
+        content[offset..offset + file.len()].copy_from_slice(file);
+        initialized = initialized.max(offset + file.len());
+        flags = flags.union(SegmentFlags::new(header));
+    }
+
+    Ok((
+        ProgramSegment {
+            address: start,
+            content,
+        },
+        initialized,
+        flags,
+    ))
+}
+
+fn copy_and_decode(
+    program: &Program,
+    strictness: Strictness,
+) -> Result<DecodedProgram, RiscuError> {
+    check_word_multiples(
+        program.instructions().len(),
+        program.data.content.len(),
+        strictness,
+    )?;
+
     let code = ProgramSegment {
         address: program.instruction_range.start,
         content: program.instructions().to_vec(),
@@ -203,13 +1301,310 @@ fn copy_and_decode(program: &Program) -> Result<DecodedProgram, RiscuError> {
 
     let data = ProgramSegment {
         address: program.data.address,
-        content: program
-            .data
-            .content
-            .chunks_exact(size_of::<u64>())
-            .map(LittleEndian::read_u64)
-            .collect::<Vec<_>>(),
+        content: program.data_words().collect::<Vec<_>>(),
     };
 
-    Ok(DecodedProgram { code, data })
+    Ok(DecodedProgram {
+        code,
+        data,
+        data_file_size: program.data_file_size,
+        entry_address: program.entry_address,
+    })
+}
+
+/// Check that the instructions and the data segment, including its zero-initialized part, hold
+/// whole words. A lenient load also accepts compressed instructions, which only fill half-words.
+fn check_word_multiples(
+    code_len: usize,
+    data_len: usize,
+    strictness: Strictness,
+) -> Result<(), RiscuError> {
+    if strictness == Strictness::Lenient {
+        // a truncated 32-bit encoding at the end is reported as trailing padding
+        if code_len % size_of::<u16>() != 0 {
+            return Err(RiscuError::InvalidRiscu(
+                "code segment not a multiple of 2 bytes",
+            ));
+        }
+    } else if code_len % size_of::<u32>() != 0 {
+        // instructions are read word by word, a partial word at the end would be dropped silently
+        return Err(RiscuError::InvalidRiscu(
+            "code segment not a multiple of 4 bytes",
+        ));
+    }
+
+    // the data segment already includes the zero-initialized part, so any remainder means that
+    // the segment does not hold whole double words
+    if data_len % size_of::<u64>() != 0 {
+        return Err(RiscuError::InvalidRiscu(
+            "data segment not a multiple of 8 bytes",
+        ));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decoded_program() -> DecodedProgram {
+        DecodedProgram {
+            code: ProgramSegment {
+                address: 0x10000,
+                content: vec![0x13, 0x00, 0x00, 0x00, 0x73, 0x00, 0x00, 0x00], // nop; ecall
+            },
+            data: ProgramSegment {
+                address: 0x11000,
+                content: vec![0, 1, 2],
+            },
+            data_file_size: 24,
+            entry_address: 0x10000,
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip() {
+        let program = decoded_program();
+
+        let json = serde_json::to_string(&program).unwrap();
+        let restored: DecodedProgram = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(restored.content_hash(), program.content_hash());
+        assert_eq!(restored.data_file_size, program.data_file_size);
+
+        let error = serde_json::to_string(&DecodingError::Illegal(0xffff)).unwrap();
+        assert_eq!(error, r#"{"Illegal":65535}"#);
+        assert_eq!(
+            serde_json::from_str::<DecodingError>(&error).unwrap(),
+            DecodingError::Illegal(0xffff)
+        );
+    }
+
+    #[test]
+    fn data_segment_needs_whole_words() {
+        let program = |data_length| Program {
+            code: ProgramSegment {
+                address: 0x10000,
+                content: vec![0x73, 0x00, 0x00, 0x00], // ecall
+            },
+            data: ProgramSegment {
+                address: 0x11000,
+                content: vec![0; data_length],
+            },
+            instruction_range: 0x10000..0x10004,
+            data_file_size: data_length,
+            entry_address: 0x10000,
+            comment: None,
+            code_flags: SegmentFlags::default(),
+            data_flags: SegmentFlags::default(),
+        };
+
+        assert!(matches!(
+            program(12).decode(),
+            Err(RiscuError::InvalidRiscu(_))
+        ));
+        assert_eq!(program(16).decode().unwrap().data.content, vec![0, 0]);
+    }
+
+    #[test]
+    fn code_segment_needs_whole_words() {
+        let program = |code_length: usize| Program {
+            code: ProgramSegment {
+                address: 0x10000,
+                content: vec![0x13, 0x00, 0x00, 0x00, 0x73, 0x00, 0x00, 0x00][..code_length]
+                    .to_vec(),
+            },
+            data: ProgramSegment {
+                address: 0x11000,
+                content: vec![0; 8],
+            },
+            instruction_range: 0x10000..(0x10000 + code_length as u64),
+            data_file_size: 8,
+            entry_address: 0x10000,
+            comment: None,
+            code_flags: SegmentFlags::default(),
+            data_flags: SegmentFlags::default(),
+        };
+
+        for code_length in 5..8 {
+            assert!(matches!(
+                program(code_length).decode(),
+                Err(RiscuError::InvalidRiscu(
+                    "code segment not a multiple of 4 bytes"
+                ))
+            ));
+        }
+        assert_eq!(program(8).decode().unwrap().code.content.len(), 8);
+    }
+
+    #[test]
+    fn raw_words() {
+        let program = Program {
+            code: ProgramSegment {
+                address: 0x10000,
+                // nop; ecall; a trailing half-word
+                content: vec![0x13, 0x00, 0x00, 0x00, 0x73, 0x00, 0x00, 0x00, 0x01, 0x00],
+            },
+            data: ProgramSegment {
+                address: 0x11000,
+                content: vec![42, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x80],
+            },
+            instruction_range: 0x10000..0x1000a,
+            data_file_size: 8,
+            entry_address: 0x10000,
+            comment: None,
+            code_flags: SegmentFlags::default(),
+            data_flags: SegmentFlags::default(),
+        };
+
+        assert_eq!(
+            program.code_words().collect::<Vec<_>>(),
+            vec![0x00000013, 0x00000073]
+        );
+        assert_eq!(
+            program.data_words().collect::<Vec<_>>(),
+            vec![42, 0x8000_0000_0000_0000]
+        );
+    }
+
+    #[test]
+    fn segment_ranges() {
+        let program = decoded_program();
+
+        assert_eq!(program.code_range(), 0x10000..0x10008);
+        assert!(program.code_range().contains(&program.code.address));
+        assert!(!program.code_range().contains(&0x10008));
+
+        assert_eq!(program.data_range(), 0x11000..0x11018);
+    }
+
+    #[test]
+    fn program_break() {
+        let mut program = decoded_program();
+
+        assert_eq!(program.program_break(), 0x12000);
+        assert!(program.program_break() >= program.data_range().end);
+
+        program.data.content = vec![0; 512];
+        assert_eq!(program.program_break(), 0x12000);
+    }
+
+    #[test]
+    fn address_index() {
+        let mut program = decoded_program();
+        // addi a0, a1, 42; c.addi a5, -8; ecall; c.mv a0, a5
+        program.code.content = vec![
+            0x13, 0x85, 0xa5, 0x02, 0xe1, 0x17, 0x73, 0x00, 0x00, 0x00, 0x3e, 0x85,
+        ];
+
+        let index = program.build_address_index();
+
+        assert_eq!(index.lookup(0x10000), Some(0));
+        assert_eq!(index.lookup(0x10004), Some(1));
+        assert_eq!(index.lookup(0x10006), Some(2));
+        assert_eq!(index.lookup(0x1000a), Some(3));
+
+        // inside of an instruction and out of the code segment
+        assert_eq!(index.lookup(0x10008), Some(2));
+        assert_eq!(index.lookup(0x1000c), None);
+        assert_eq!(index.lookup(0xfffe), None);
+    }
+
+    #[test]
+    fn instruction_at() {
+        let mut program = decoded_program();
+        // addi a0, a1, 42; ecall; c.addi a5, -8; c.addi a5, -8; an undefined word
+        program.code.content = vec![
+            0x13, 0x85, 0xa5, 0x02, 0x73, 0x00, 0x00, 0x00, 0xe1, 0x17, 0xe1, 0x17, 0xff, 0xff,
+            0xff, 0xff,
+        ];
+
+        assert_eq!(
+            program.instruction_at(0x10000),
+            Some(Instruction::new_addi(
+                crate::Register::A0,
+                crate::Register::A1,
+                42
+            ))
+        );
+        assert_eq!(
+            program.instruction_at(0x10004),
+            Some(Instruction::new_ecall())
+        );
+        assert_eq!(program.instruction_at(0x10008), decode(0x17e1).ok());
+
+        // inside of the addi and a compressed instruction which is not aligned to a word
+        assert_eq!(program.instruction_at(program.code.address + 2), None);
+        assert_eq!(program.instruction_at(0x1000a), None);
+
+        // misaligned, undecodable and out of the code segment
+        assert_eq!(program.instruction_at(0x10001), None);
+        assert_eq!(program.instruction_at(0x1000c), None);
+        assert_eq!(program.instruction_at(0x10010), None);
+        assert_eq!(program.instruction_at(0xfffc), None);
+        assert_eq!(program.instruction_at(u64::MAX - 3), None);
+
+        // a 32-bit instruction cut off by the end of the code segment
+        program.code.content.truncate(14);
+        assert_eq!(program.instruction_at(0x1000c), None);
+    }
+
+    #[test]
+    fn try_instructions() {
+        let mut program = decoded_program();
+        // addi a0, a1, 42; c.addi a5, -8; an undefined word
+        program.code.content = vec![0x13, 0x85, 0xa5, 0x02, 0xe1, 0x17, 0xff, 0xff, 0xff, 0xff];
+
+        assert!(matches!(
+            program.try_instructions(),
+            Err(RiscuError::DecodingError {
+                address: 0x10006,
+                error: DecodingError::Illegal(0xffff_ffff),
+            })
+        ));
+
+        program.code.content.truncate(6);
+        assert_eq!(
+            program.try_instructions().unwrap(),
+            program.iter_instructions().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn layout() {
+        let mut program = decoded_program();
+        // addi a0, a1, 42; c.addi a5, -8; ecall; c.mv a0, a5
+        program.code.content = vec![
+            0x13, 0x85, 0xa5, 0x02, 0xe1, 0x17, 0x73, 0x00, 0x00, 0x00, 0x3e, 0x85,
+        ];
+
+        let layout = program.layout();
+
+        let offsets = layout
+            .iter()
+            .map(|(offset, ..)| *offset)
+            .collect::<Vec<_>>();
+        assert_eq!(offsets, vec![0, 4, 6, 10]);
+
+        let mut bytes = Vec::new();
+        for (offset, width, instruction) in layout {
+            let original = &program.code.content[offset as usize..offset as usize + width.bytes()];
+
+            match width {
+                InstructionWidth::Full => {
+                    bytes.extend_from_slice(&u32::from(instruction).to_le_bytes())
+                }
+                InstructionWidth::Compressed => {
+                    assert_eq!(
+                        decode(u32::from(LittleEndian::read_u16(original))),
+                        Ok(instruction)
+                    );
+                    bytes.extend_from_slice(original)
+                }
+            }
+        }
+        assert_eq!(bytes, program.code.content);
+    }
 }
@@ -0,0 +1,134 @@
+//! # Encode instructions
+//!
+//! The molds in this module place already truncated fields at their position in the 32-bit
+//! instruction formats. They are shared by the constructors of [`types`](crate::types) and the
+//! expansion of compressed instructions.
+
+use crate::{bits::Permutable, Instruction};
+
+/// Encode an instruction, the inverse of [`decode`](crate::decode) for every instruction it
+/// produces: `decode(encode(&i)) == Ok(i)`.
+///
+/// Compressed instructions are encoded as their 32-bit expansion.
+pub fn encode(instruction: &Instruction) -> u32 {
+    u32::from(*instruction)
+}
+
+/// Bits of a B-type offset in the order of the instruction, `imm[12|10:5]` in bits 31 to 25 and
+/// `imm[4:1|11]` in bits 11 to 7, see [`bits`](crate::bits).
+pub(crate) const B_TYPE_IMM: [usize; 12] = [12, 10, 9, 8, 7, 6, 5, 4, 3, 2, 1, 11];
+
+/// Bits of a J-type offset in the order of the instruction, `imm[20|10:1|11|19:12]` in bits 31
+/// to 12.
+pub(crate) const J_TYPE_IMM: [usize; 20] = [
+    20, 10, 9, 8, 7, 6, 5, 4, 3, 2, 1, 11, 19, 18, 17, 16, 15, 14, 13, 12,
+];
+
+pub(crate) fn r_type(funct7: u32, rs2: u32, rs1: u32, funct3: u32, rd: u32, opcode: u32) -> u32 {
+    (funct7 << 25) | (rs2 << 20) | (rs1 << 15) | (funct3 << 12) | (rd << 7) | opcode
+}
+
+/// `imm` holds the 12-bit immediate in its low bits.
+pub(crate) fn i_type(imm: u32, rs1: u32, funct3: u32, rd: u32, opcode: u32) -> u32 {
+    ((imm & 0xfff) << 20) | (rs1 << 15) | (funct3 << 12) | (rd << 7) | opcode
+}
+
+/// `imm` holds the 12-bit immediate in its low bits.
+pub(crate) fn s_type(imm: u32, rs2: u32, rs1: u32, funct3: u32, opcode: u32) -> u32 {
+    (((imm >> 5) & 0x7f) << 25)
+        | (rs2 << 20)
+        | (rs1 << 15)
+        | (funct3 << 12)
+        | ((imm & 0x1f) << 7)
+        | opcode
+}
+
+/// `imm` holds the 13-bit offset in its low bits, bit 0 is ignored.
+pub(crate) fn b_type(imm: u32, rs2: u32, rs1: u32, funct3: u32, opcode: u32) -> u32 {
+    let imm = imm.permute(&B_TYPE_IMM);
+
+    ((imm >> 5) << 25) | (rs2 << 20) | (rs1 << 15) | (funct3 << 12) | ((imm & 0x1f) << 7) | opcode
+}
+
+/// `imm` holds the 20-bit upper immediate in its low bits.
+pub(crate) fn u_type(imm: u32, rd: u32, opcode: u32) -> u32 {
+    ((imm & 0xf_ffff) << 12) | (rd << 7) | opcode
+}
+
+/// `imm` holds the 21-bit offset in its low bits, bit 0 is ignored.
+pub(crate) fn j_type(imm: u32, rd: u32, opcode: u32) -> u32 {
+    let imm = imm.permute(&J_TYPE_IMM);
+
+    (imm << 12) | (rd << 7) | opcode
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{decode, decompress::decompress, Register::*};
+
+    #[test]
+    fn round_trip() {
+        let corpus = [
+            0x00c58633, // add a2,a1,a2
+            0x40b50533, // sub a0,a0,a1
+            0x02b55533, // divu a0,a0,a1
+            0x00f6b423, // sd a5,8(a3)
+            0xfea13c23, // sd a0,-8(sp)
+            0xff010113, // addi sp,sp,-16
+            0x4045d51b, // sraiw a0,a1,4
+            0x43f5d513, // srai a0,a1,63
+            0x0007b783, // ld a5,0(a5)
+            0xfe050ae3, // beq a0,zero,-12
+            0x00b51463, // bne a0,a1,8
+            0xfffff7b7, // lui a5,0xfffff
+            0x00001097, // auipc ra,0x1
+            0xf89ff06f, // j -120
+            0x008000ef, // jal ra,8
+            0x00008067, // ret
+            0x00000073, // ecall
+            0x00100073, // ebreak
+            0x0ff0000f, // fence
+            0x00c5a52f, // amoadd.w a0,a2,(a1)
+        ];
+
+        for word in corpus.iter() {
+            let instruction = decode(*word).unwrap();
+
+            assert_eq!(encode(&instruction), *word, "{}", instruction);
+            assert_eq!(decode(encode(&instruction)), Ok(instruction));
+        }
+
+        // compressed instructions encode to their expansion
+        let c_addi = decode(0x17e1).unwrap(); // c.addi a5,-8
+        assert_eq!(encode(&c_addi), 0xff878793);
+        assert_eq!(Ok(encode(&c_addi)), decompress(0x17e1));
+    }
+
+    #[test]
+    fn shared_immediate_layouts() {
+        use crate::types::{BType, JType};
+
+        // the decoding side undoes the scatter for every offset
+        for imm in (-4096..4096).step_by(2) {
+            let word = b_type(imm as u32, 0, 0, 0, 0b1100011);
+            assert_eq!(BType(word).imm(), imm);
+        }
+
+        let strided = (-(1 << 20)..(1 << 20)).step_by(2 * 127);
+        for imm in strided.chain(Some((1 << 20) - 2)) {
+            let word = j_type(imm as u32, 0, 0b1101111);
+            assert_eq!(JType(word).imm(), imm);
+        }
+    }
+
+    #[test]
+    fn constructors() {
+        assert_eq!(encode(&Instruction::new_add(A2, A1, A2)), 0x00c58633);
+        assert_eq!(encode(&Instruction::new_addi(Sp, Sp, -16)), 0xff010113);
+        assert_eq!(encode(&Instruction::new_sd(Sp, A0, -8)), 0xfea13c23);
+        assert_eq!(encode(&Instruction::new_beq(A0, Zero, -12)), 0xfe050ae3);
+        assert_eq!(encode(&Instruction::new_lui(A5, -1)), 0xfffff7b7);
+        assert_eq!(encode(&Instruction::new_jal(Zero, -120)), 0xf89ff06f);
+    }
+}
@@ -1,7 +1,10 @@
 use crate::types::*;
-use crate::Register;
+use crate::{DecodingError, Register};
+use alloc::{format, string::String, vec::Vec};
+use core::{convert::TryFrom, fmt};
 
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Instruction {
     // LUI
     Lui(UType),
@@ -40,6 +43,7 @@ pub enum Instruction {
 
     // Fence
     Fence(IType),
+    FenceI(IType),
 
     // OP-imm
     Addi(IType),
@@ -117,6 +121,35 @@ pub enum Instruction {
     Amomaxd(RType),
     Amominud(RType),
     Amomaxud(RType),
+
+    // Hint (raw encoding of a computational instruction writing to zero, the half-word for
+    // compressed instructions)
+    Hint(u32),
+
+    // OP or OP-32 with an unknown combination of funct7 and funct3, e.g. a custom ALU operation
+    UnknownRType(RType),
+}
+
+/// The opcode and function fields which together select an instruction's operation.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct EncodingFields {
+    pub opcode: u32,
+    /// `None` for U- and J-type instructions, which have no funct3 field.
+    pub funct3: Option<u32>,
+    /// Only present for R-type instructions.
+    pub funct7: Option<u32>,
+}
+
+/// The base encodings of 32-bit instructions, which place the operands and immediate in
+/// different fields.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum InstructionFormat {
+    R,
+    I,
+    S,
+    B,
+    U,
+    J,
 }
 
 // opcodes
@@ -188,6 +221,7 @@ const F3_SYSTEM: u32 = 0; // 000
 const F3_AMO32: u32 = 2; //010
 const F3_AMO64: u32 = 3; //011
 const F3_FENCE: u32 = 0; // 000
+const F3_FENCE_I: u32 = 1; // 001
 
 // f7-codes
 const F7_ADD_ADDW: u32 = 0; // 0000000
@@ -221,6 +255,28 @@ const F7_AMOMINUW_AMOMINUD: u32 = 24; // 11000 aq rl
 const F7_AMOMAXUW_AMOMAXUD: u32 = 28; // 11100 aq rl
 
 impl Instruction {
+    /// Return the opcode, funct3 and funct7 fields as they are encoded in this instruction.
+    pub fn encoding_fields(&self) -> EncodingFields {
+        let raw = u32::from(*self);
+        let opcode = raw & 0b111_1111;
+
+        let funct3 = match opcode {
+            OP_LUI | OP_AUIPC | OP_JAL => None,
+            _ => Some((raw >> 12) & 0b111),
+        };
+
+        let funct7 = match opcode {
+            OP_OP | OP_OP32 | OP_AMO => Some(raw >> 25),
+            _ => None,
+        };
+
+        EncodingFields {
+            opcode,
+            funct3,
+            funct7,
+        }
+    }
+
     pub fn new_nop() -> Instruction {
         Self::new_addi(Register::Zero, Register::Zero, 0)
     }
@@ -359,7 +415,7 @@ impl Instruction {
     }
     pub fn new_srli(rd: Register, rs1: Register, immediate: i32) -> Instruction {
         Instruction::Srli(IType::new(
-            immediate | (F7_SRL_SRLW << 25) as i32,
+            immediate | (F7_SRL_SRLW << 5) as i32,
             F3_SRLI_SRAI,
             OP_IMM,
             rd,
@@ -368,7 +424,7 @@ impl Instruction {
     }
     pub fn new_srai(rd: Register, rs1: Register, immediate: i32) -> Instruction {
         Instruction::Srai(IType::new(
-            immediate | (F7_SRA_SRAW << 25) as i32,
+            immediate | (F7_SRA_SRAW << 5) as i32,
             F3_SRLI_SRAI,
             OP_IMM,
             rd,
@@ -385,7 +441,13 @@ impl Instruction {
         Instruction::Srliw(IType::new(immediate, F3_SRLIW, OP_IMM32, rd, rs1))
     }
     pub fn new_sraiw(rd: Register, rs1: Register, immediate: i32) -> Instruction {
-        Instruction::Sraiw(IType::new(immediate, F3_SRAIW, OP_IMM32, rd, rs1))
+        Instruction::Sraiw(IType::new(
+            immediate | (F7_SRA_SRAW << 5) as i32,
+            F3_SRAIW,
+            OP_IMM32,
+            rd,
+            rs1,
+        ))
     }
     pub fn new_lb(rd: Register, rs1: Register, immediate: i32) -> Instruction {
         Instruction::Lb(IType::new(immediate, F3_LB, OP_LD, rd, rs1))
@@ -419,11 +481,11 @@ impl Instruction {
     }
     pub fn new_ebreak() -> Instruction {
         Instruction::Ebreak(IType::new(
-            0,
+            1, // 000000000001
             F3_SYSTEM,
             OP_SYSTEM,
             Register::Zero,
-            Register::from(1), // 000000000001
+            Register::Zero,
         ))
     }
     pub fn new_jalr(rd: Register, rs1: Register, immediate: i32) -> Instruction {
@@ -651,6 +713,544 @@ impl Instruction {
         // TODO: Implement me properly (imm[11:0] split into fm, pred, succ)!
         Instruction::Fence(IType::new(immediate, F3_FENCE, OP_FENCE, rd, rs1))
     }
+    pub fn new_fence_i() -> Instruction {
+        Instruction::FenceI(IType::new(
+            0,
+            F3_FENCE_I,
+            OP_FENCE,
+            Register::Zero,
+            Register::Zero,
+        ))
+    }
+}
+
+impl Instruction {
+    /// Two instructions have the same shape if they are the same kind of instruction operating
+    /// on the same registers, regardless of their immediates.
+    pub fn same_shape(&self, other: &Instruction) -> bool {
+        core::mem::discriminant(self) == core::mem::discriminant(other)
+            && self.registers() == other.registers()
+    }
+
+    /// Return a copy of this instruction with every register operand mapped through `map`.
+    ///
+    /// The register fields of `fence`, `fence.i`, `ecall`, `ebreak` and HINTs are not operands
+    /// and thus left untouched.
+    pub fn rename_registers(&self, map: &dyn Fn(Register) -> Register) -> Instruction {
+        use Instruction::*;
+
+        if let Hint(_) = self {
+            return *self;
+        }
+
+        let (rd, rs1, rs2) = self.operands();
+        let mut raw = u32::from(*self);
+
+        for (register, shift) in [(rd, 7), (rs1, 15), (rs2, 20)].iter() {
+            if let Some(register) = register {
+                raw = (raw & !(0x1f << shift)) | (u32::from(map(*register)) << shift);
+            }
+        }
+
+        if let UnknownRType(_) = self {
+            return UnknownRType(RType(raw));
+        }
+
+        crate::decode(raw).expect("register fields do not affect the validity of an encoding")
+    }
+
+    /// Registers read by this instruction.
+    pub fn reads(&self) -> Vec<Register> {
+        let (_, rs1, rs2) = self.operands();
+
+        rs1.into_iter().chain(rs2).collect()
+    }
+
+    /// Register written by this instruction.
+    pub fn writes(&self) -> Option<Register> {
+        self.operands().0
+    }
+
+    /// Destination register of this instruction, `None` if its format has no `rd` field.
+    pub fn rd(&self) -> Option<Register> {
+        self.registers().0
+    }
+
+    /// First source register of this instruction, `None` if its format has no `rs1` field.
+    pub fn rs1(&self) -> Option<Register> {
+        self.registers().1
+    }
+
+    /// Second source register of this instruction, `None` if its format has no `rs2` field.
+    pub fn rs2(&self) -> Option<Register> {
+        self.registers().2
+    }
+
+    /// The immediate of this instruction, `None` if it has none.
+    ///
+    /// Unlike [`Instruction::effective_immediate`], the upper immediate of `lui` and `auipc` is
+    /// not shifted.
+    pub fn immediate(&self) -> Option<i64> {
+        match *self {
+            Instruction::Lui(u) | Instruction::Auipc(u) => Some(i64::from(u.imm())),
+            _ => self.effective_immediate(),
+        }
+    }
+
+    /// Like [`Instruction::registers`], but without the register fields which are not operands
+    /// (those of `fence`, `fence.i`, `ecall`, `ebreak` and HINTs).
+    fn operands(&self) -> (Option<Register>, Option<Register>, Option<Register>) {
+        use Instruction::*;
+
+        match self {
+            Fence(_) | FenceI(_) | Ecall(_) | Ebreak(_) | Hint(_) => (None, None, None),
+            _ => self.registers(),
+        }
+    }
+
+    /// The `rd`, `rs1` and `rs2` fields of this instruction, where present in its format.
+    fn registers(&self) -> (Option<Register>, Option<Register>, Option<Register>) {
+        use Instruction::*;
+
+        match *self {
+            Add(r) | Sub(r) | Sll(r) | Slt(r) | Sltu(r) | Xor(r) | Srl(r) | Sra(r) | Or(r)
+            | And(r) | Mul(r) | Mulh(r) | Mulhsu(r) | Mulhu(r) | Div(r) | Divu(r) | Rem(r)
+            | Remu(r) | Addw(r) | Subw(r) | Sllw(r) | Srlw(r) | Sraw(r) | Mulw(r) | Divw(r)
+            | Divuw(r) | Remw(r) | Remuw(r) | Lrw(r) | Scw(r) | Amoswapw(r) | Amoaddw(r)
+            | Amoxorw(r) | Amoandw(r) | Amoorw(r) | Amominw(r) | Amomaxw(r) | Amominuw(r)
+            | Amomaxuw(r) | Lrd(r) | Scd(r) | Amoswapd(r) | Amoaddd(r) | Amoxord(r)
+            | Amoandd(r) | Amoord(r) | Amomind(r) | Amomaxd(r) | Amominud(r) | Amomaxud(r)
+            | UnknownRType(r) => (Some(r.rd()), Some(r.rs1()), Some(r.rs2())),
+            Jalr(i) | Lb(i) | Lh(i) | Lw(i) | Ld(i) | Lbu(i) | Lhu(i) | Lwu(i) | Fence(i)
+            | FenceI(i) | Addi(i) | Slti(i) | Sltiu(i) | Xori(i) | Ori(i) | Andi(i) | Slli(i)
+            | Srli(i) | Srai(i) | Addiw(i) | Slliw(i) | Srliw(i) | Sraiw(i) | Ecall(i)
+            | Ebreak(i) => (Some(i.rd()), Some(i.rs1()), None),
+            Sb(s) | Sh(s) | Sw(s) | Sd(s) => (None, Some(s.rs1()), Some(s.rs2())),
+            Beq(b) | Bne(b) | Blt(b) | Bge(b) | Bltu(b) | Bgeu(b) => {
+                (None, Some(b.rs1()), Some(b.rs2()))
+            }
+            Lui(u) | Auipc(u) => (Some(u.rd()), None, None),
+            Jal(j) => (Some(j.rd()), None, None),
+            Hint(_) => (None, None, None),
+        }
+    }
+
+    /// The base encoding of this instruction.
+    pub fn format(&self) -> InstructionFormat {
+        use Instruction::*;
+
+        match *self {
+            Add(_) | Sub(_) | Sll(_) | Slt(_) | Sltu(_) | Xor(_) | Srl(_) | Sra(_) | Or(_)
+            | And(_) | Mul(_) | Mulh(_) | Mulhsu(_) | Mulhu(_) | Div(_) | Divu(_) | Rem(_)
+            | Remu(_) | Addw(_) | Subw(_) | Sllw(_) | Srlw(_) | Sraw(_) | Mulw(_) | Divw(_)
+            | Divuw(_) | Remw(_) | Remuw(_) | Lrw(_) | Scw(_) | Amoswapw(_) | Amoaddw(_)
+            | Amoxorw(_) | Amoandw(_) | Amoorw(_) | Amominw(_) | Amomaxw(_) | Amominuw(_)
+            | Amomaxuw(_) | Lrd(_) | Scd(_) | Amoswapd(_) | Amoaddd(_) | Amoxord(_)
+            | Amoandd(_) | Amoord(_) | Amomind(_) | Amomaxd(_) | Amominud(_) | Amomaxud(_)
+            | UnknownRType(_) => InstructionFormat::R,
+            Jalr(_) | Lb(_) | Lh(_) | Lw(_) | Ld(_) | Lbu(_) | Lhu(_) | Lwu(_) | Fence(_)
+            | FenceI(_) | Addi(_) | Slti(_) | Sltiu(_) | Xori(_) | Ori(_) | Andi(_) | Slli(_)
+            | Srli(_) | Srai(_) | Addiw(_) | Slliw(_) | Srliw(_) | Sraiw(_) | Ecall(_)
+            | Ebreak(_) => InstructionFormat::I,
+            Sb(_) | Sh(_) | Sw(_) | Sd(_) => InstructionFormat::S,
+            Beq(_) | Bne(_) | Blt(_) | Bge(_) | Bltu(_) | Bgeu(_) => InstructionFormat::B,
+            Lui(_) | Auipc(_) => InstructionFormat::U,
+            Jal(_) => InstructionFormat::J,
+            // the computational instructions of HINTs, compressed ones by their expansion
+            Hint(raw) => {
+                let word = if raw & 0b11 != 0b11 {
+                    crate::decompress::decompress(raw as u16).unwrap_or(raw)
+                } else {
+                    raw
+                };
+
+                match word & 0b111_1111 {
+                    OP_OP | OP_OP32 => InstructionFormat::R,
+                    OP_LUI | OP_AUIPC => InstructionFormat::U,
+                    _ => InstructionFormat::I,
+                }
+            }
+        }
+    }
+
+    /// Absolute target of a PC-relative branch or `jal` located at `pc`, `None` for everything
+    /// else including `jalr`, whose target depends on a register.
+    pub fn branch_target(&self, pc: u64) -> Option<u64> {
+        use Instruction::*;
+
+        let offset = match *self {
+            Beq(b) | Bne(b) | Blt(b) | Bge(b) | Bltu(b) | Bgeu(b) => b.imm(),
+            Jal(j) => j.imm(),
+            _ => return None,
+        };
+
+        Some(pc.wrapping_add(offset as i64 as u64))
+    }
+
+    /// Whether this is a branch or a jump, i.e. `ecall` aside one of the instructions of
+    /// [`is_control_transfer`](Self::is_control_transfer).
+    pub fn is_control_flow(&self) -> bool {
+        use Instruction::*;
+
+        matches!(
+            self,
+            Beq(_) | Bne(_) | Blt(_) | Bge(_) | Bltu(_) | Bgeu(_) | Jal(_) | Jalr(_)
+        )
+    }
+
+    /// Whether this instruction may transfer control elsewhere than the next instruction, i.e.
+    /// branches, jumps and `ecall`.
+    pub fn is_control_transfer(&self) -> bool {
+        use Instruction::*;
+
+        matches!(
+            self,
+            Beq(_) | Bne(_) | Blt(_) | Bge(_) | Bltu(_) | Bgeu(_) | Jal(_) | Jalr(_) | Ecall(_)
+        )
+    }
+
+    #[cfg(feature = "std")]
+    pub(crate) fn is_unconditional_jump(&self) -> bool {
+        match *self {
+            Instruction::Jal(j) => j.rd() == Register::Zero,
+            Instruction::Jalr(i) => i.rd() == Register::Zero,
+            _ => false,
+        }
+    }
+
+    /// Whether this is a multiplication or division of the M extension.
+    pub(crate) fn is_m_extension(&self) -> bool {
+        let fields = self.encoding_fields();
+
+        matches!(fields.opcode, OP_OP | OP_OP32) && fields.funct7 == Some(F7_MUL_MULW)
+    }
+
+    /// Whether this is an atomic memory operation of the A extension.
+    pub(crate) fn is_atomic(&self) -> bool {
+        self.encoding_fields().opcode == OP_AMO
+    }
+
+    /// Whether this is `ret`, i.e. `jalr zero, 0(ra)`.
+    #[cfg(feature = "std")]
+    pub(crate) fn is_return(&self) -> bool {
+        match *self {
+            Instruction::Jalr(i) => {
+                i.rd() == Register::Zero && i.rs1() == Register::Ra && i.imm() == 0
+            }
+            _ => false,
+        }
+    }
+
+    /// The value of the immediate as it is used by the instruction, as opposed to the encoded
+    /// field: the byte offset of branches and jumps, `imm << 12` for `lui` and `auipc` and the
+    /// shift amount of immediate shifts.
+    pub fn effective_immediate(&self) -> Option<i64> {
+        use Instruction::*;
+
+        let imm = match *self {
+            Lui(u) | Auipc(u) => (u.imm() << 12) as i32,
+            Jal(j) => j.imm(),
+            Beq(b) | Bne(b) | Blt(b) | Bge(b) | Bltu(b) | Bgeu(b) => b.imm(),
+            Sb(s) | Sh(s) | Sw(s) | Sd(s) => s.imm(),
+            Slli(i) | Srli(i) | Srai(i) => i.imm() & 0b11_1111,
+            Slliw(i) | Srliw(i) | Sraiw(i) => i.imm() & 0b1_1111,
+            Jalr(i) | Lb(i) | Lh(i) | Lw(i) | Ld(i) | Lbu(i) | Lhu(i) | Lwu(i) | Addi(i)
+            | Slti(i) | Sltiu(i) | Xori(i) | Ori(i) | Andi(i) | Addiw(i) => i.imm(),
+            _ => return None,
+        };
+
+        Some(i64::from(imm))
+    }
+
+    /// Whether this is one of the 14 instructions of RISC-U.
+    pub fn is_riscu(&self) -> bool {
+        matches!(
+            self,
+            Instruction::Lui(_)
+                | Instruction::Addi(_)
+                | Instruction::Ld(_)
+                | Instruction::Sd(_)
+                | Instruction::Add(_)
+                | Instruction::Sub(_)
+                | Instruction::Mul(_)
+                | Instruction::Divu(_)
+                | Instruction::Remu(_)
+                | Instruction::Sltu(_)
+                | Instruction::Beq(_)
+                | Instruction::Jal(_)
+                | Instruction::Jalr(_)
+                | Instruction::Ecall(_)
+        )
+    }
+
+    /// Number of bytes accessed in memory by a load or store, e.g. 1 for `lb` and `sb`.
+    pub fn access_size(&self) -> Option<usize> {
+        match self {
+            Instruction::Lb(_) | Instruction::Lbu(_) | Instruction::Sb(_) => Some(1),
+            Instruction::Lh(_) | Instruction::Lhu(_) | Instruction::Sh(_) => Some(2),
+            Instruction::Lw(_) | Instruction::Lwu(_) | Instruction::Sw(_) => Some(4),
+            Instruction::Ld(_) | Instruction::Sd(_) => Some(8),
+            _ => None,
+        }
+    }
+
+    /// Whether a load sign-extends the value read from memory (`lb`, `lh`, `lw` and `ld`) or
+    /// zero-extends it (`lbu`, `lhu` and `lwu`).
+    pub fn is_signed_load(&self) -> Option<bool> {
+        match self {
+            Instruction::Lb(_) | Instruction::Lh(_) | Instruction::Lw(_) | Instruction::Ld(_) => {
+                Some(true)
+            }
+            Instruction::Lbu(_) | Instruction::Lhu(_) | Instruction::Lwu(_) => Some(false),
+            _ => None,
+        }
+    }
+
+    /// The assembler mnemonic of this instruction, e.g. `"addi"` or `"amoswap.w"`.
+    pub fn mnemonic(&self) -> &'static str {
+        match self {
+            Instruction::Lui(_) => "lui",
+            Instruction::Auipc(_) => "auipc",
+            Instruction::Jal(_) => "jal",
+            Instruction::Jalr(_) => "jalr",
+            Instruction::Beq(_) => "beq",
+            Instruction::Bne(_) => "bne",
+            Instruction::Blt(_) => "blt",
+            Instruction::Bge(_) => "bge",
+            Instruction::Bltu(_) => "bltu",
+            Instruction::Bgeu(_) => "bgeu",
+            Instruction::Lb(_) => "lb",
+            Instruction::Lh(_) => "lh",
+            Instruction::Lw(_) => "lw",
+            Instruction::Ld(_) => "ld",
+            Instruction::Lbu(_) => "lbu",
+            Instruction::Lhu(_) => "lhu",
+            Instruction::Lwu(_) => "lwu",
+            Instruction::Sb(_) => "sb",
+            Instruction::Sh(_) => "sh",
+            Instruction::Sw(_) => "sw",
+            Instruction::Sd(_) => "sd",
+            Instruction::Fence(_) => "fence",
+            Instruction::FenceI(_) => "fence.i",
+            Instruction::Addi(_) => "addi",
+            Instruction::Slti(_) => "slti",
+            Instruction::Sltiu(_) => "sltiu",
+            Instruction::Xori(_) => "xori",
+            Instruction::Ori(_) => "ori",
+            Instruction::Andi(_) => "andi",
+            Instruction::Slli(_) => "slli",
+            Instruction::Srli(_) => "srli",
+            Instruction::Srai(_) => "srai",
+            Instruction::Addiw(_) => "addiw",
+            Instruction::Slliw(_) => "slliw",
+            Instruction::Srliw(_) => "srliw",
+            Instruction::Sraiw(_) => "sraiw",
+            Instruction::Add(_) => "add",
+            Instruction::Sub(_) => "sub",
+            Instruction::Sll(_) => "sll",
+            Instruction::Slt(_) => "slt",
+            Instruction::Sltu(_) => "sltu",
+            Instruction::Xor(_) => "xor",
+            Instruction::Srl(_) => "srl",
+            Instruction::Sra(_) => "sra",
+            Instruction::Or(_) => "or",
+            Instruction::And(_) => "and",
+            Instruction::Mul(_) => "mul",
+            Instruction::Mulh(_) => "mulh",
+            Instruction::Mulhsu(_) => "mulhsu",
+            Instruction::Mulhu(_) => "mulhu",
+            Instruction::Div(_) => "div",
+            Instruction::Divu(_) => "divu",
+            Instruction::Rem(_) => "rem",
+            Instruction::Remu(_) => "remu",
+            Instruction::Addw(_) => "addw",
+            Instruction::Subw(_) => "subw",
+            Instruction::Sllw(_) => "sllw",
+            Instruction::Srlw(_) => "srlw",
+            Instruction::Sraw(_) => "sraw",
+            Instruction::Mulw(_) => "mulw",
+            Instruction::Divw(_) => "divw",
+            Instruction::Divuw(_) => "divuw",
+            Instruction::Remw(_) => "remw",
+            Instruction::Remuw(_) => "remuw",
+            Instruction::Ecall(_) => "ecall",
+            Instruction::Ebreak(_) => "ebreak",
+            Instruction::Lrw(_) => "lr.w",
+            Instruction::Scw(_) => "sc.w",
+            Instruction::Amoswapw(_) => "amoswap.w",
+            Instruction::Amoaddw(_) => "amoadd.w",
+            Instruction::Amoxorw(_) => "amoxor.w",
+            Instruction::Amoandw(_) => "amoand.w",
+            Instruction::Amoorw(_) => "amoor.w",
+            Instruction::Amominw(_) => "amomin.w",
+            Instruction::Amomaxw(_) => "amomax.w",
+            Instruction::Amominuw(_) => "amominu.w",
+            Instruction::Amomaxuw(_) => "amomaxu.w",
+            Instruction::Lrd(_) => "lr.d",
+            Instruction::Scd(_) => "sc.d",
+            Instruction::Amoswapd(_) => "amoswap.d",
+            Instruction::Amoaddd(_) => "amoadd.d",
+            Instruction::Amoxord(_) => "amoxor.d",
+            Instruction::Amoandd(_) => "amoand.d",
+            Instruction::Amoord(_) => "amoor.d",
+            Instruction::Amomind(_) => "amomin.d",
+            Instruction::Amomaxd(_) => "amomax.d",
+            Instruction::Amominud(_) => "amominu.d",
+            Instruction::Amomaxud(_) => "amomaxu.d",
+            Instruction::Hint(_) => "hint",
+            Instruction::UnknownRType(_) => "unknown",
+        }
+    }
+}
+
+impl Instruction {
+    /// Render this instruction located at `pc` like [`fmt::Display`], but with registers named
+    /// by `reg_name` and targets of branches and jumps as absolute addresses.
+    pub fn display_with(&self, pc: u64, reg_name: impl Fn(Register) -> String) -> String {
+        let mut text = String::new();
+
+        self.write_with(&mut text, &reg_name, Some(pc))
+            .expect("writing to a string can not fail");
+
+        text
+    }
+
+    fn write_with(
+        &self,
+        w: &mut dyn fmt::Write,
+        reg: &dyn Fn(Register) -> String,
+        pc: Option<u64>,
+    ) -> fmt::Result {
+        use Instruction::*;
+
+        let mnemonic = self.mnemonic();
+
+        match *self {
+            Lui(u) | Auipc(u) => write!(w, "{} {}, {:#x}", mnemonic, reg(u.rd()), u.imm()),
+            Jal(j) => {
+                write!(w, "{} {}, ", mnemonic, reg(j.rd()))?;
+                write_offset(w, pc, j.imm())
+            }
+            Jalr(i) | Lb(i) | Lh(i) | Lw(i) | Ld(i) | Lbu(i) | Lhu(i) | Lwu(i) => {
+                write!(
+                    w,
+                    "{} {}, {}({})",
+                    mnemonic,
+                    reg(i.rd()),
+                    i.imm(),
+                    reg(i.rs1())
+                )
+            }
+            Beq(b) | Bne(b) | Blt(b) | Bge(b) | Bltu(b) | Bgeu(b) => {
+                write!(w, "{} {}, {}, ", mnemonic, reg(b.rs1()), reg(b.rs2()))?;
+                write_offset(w, pc, b.imm())
+            }
+            Sb(s) | Sh(s) | Sw(s) | Sd(s) => {
+                write!(
+                    w,
+                    "{} {}, {}({})",
+                    mnemonic,
+                    reg(s.rs2()),
+                    s.imm(),
+                    reg(s.rs1())
+                )
+            }
+            Fence(i) => {
+                write!(w, "{} ", mnemonic)?;
+                write_fence_set(w, i.0 >> 24)?;
+                write!(w, ",")?;
+                write_fence_set(w, i.0 >> 20)
+            }
+            Slli(i) | Srli(i) | Srai(i) => write!(
+                w,
+                "{} {}, {}, {}",
+                mnemonic,
+                reg(i.rd()),
+                reg(i.rs1()),
+                i.imm() & 0b11_1111
+            ),
+            Slliw(i) | Srliw(i) | Sraiw(i) => write!(
+                w,
+                "{} {}, {}, {}",
+                mnemonic,
+                reg(i.rd()),
+                reg(i.rs1()),
+                i.imm() & 0b1_1111
+            ),
+            Addi(i) | Slti(i) | Sltiu(i) | Xori(i) | Ori(i) | Andi(i) | Addiw(i) => {
+                write!(
+                    w,
+                    "{} {}, {}, {}",
+                    mnemonic,
+                    reg(i.rd()),
+                    reg(i.rs1()),
+                    i.imm()
+                )
+            }
+            Ecall(_) | Ebreak(_) | FenceI(_) => write!(w, "{}", mnemonic),
+            Lrw(r) | Lrd(r) => write!(w, "{} {}, ({})", mnemonic, reg(r.rd()), reg(r.rs1())),
+            Scw(r) | Amoswapw(r) | Amoaddw(r) | Amoxorw(r) | Amoandw(r) | Amoorw(r)
+            | Amominw(r) | Amomaxw(r) | Amominuw(r) | Amomaxuw(r) | Scd(r) | Amoswapd(r)
+            | Amoaddd(r) | Amoxord(r) | Amoandd(r) | Amoord(r) | Amomind(r) | Amomaxd(r)
+            | Amominud(r) | Amomaxud(r) => write!(
+                w,
+                "{} {}, {}, ({})",
+                mnemonic,
+                reg(r.rd()),
+                reg(r.rs2()),
+                reg(r.rs1())
+            ),
+            Add(r) | Sub(r) | Sll(r) | Slt(r) | Sltu(r) | Xor(r) | Srl(r) | Sra(r) | Or(r)
+            | And(r) | Mul(r) | Mulh(r) | Mulhsu(r) | Mulhu(r) | Div(r) | Divu(r) | Rem(r)
+            | Remu(r) | Addw(r) | Subw(r) | Sllw(r) | Srlw(r) | Sraw(r) | Mulw(r) | Divw(r)
+            | Divuw(r) | Remw(r) | Remuw(r) => {
+                write!(
+                    w,
+                    "{} {}, {}, {}",
+                    mnemonic,
+                    reg(r.rd()),
+                    reg(r.rs1()),
+                    reg(r.rs2())
+                )
+            }
+            Hint(x) => write!(w, "{} {:#010x}", mnemonic, x),
+            UnknownRType(r) => write!(
+                w,
+                "{} {}, {}, {} (funct7 {:#09b}, funct3 {:#05b})",
+                mnemonic,
+                reg(r.rd()),
+                reg(r.rs1()),
+                reg(r.rs2()),
+                r.0 >> 25,
+                (r.0 >> 12) & 0b111
+            ),
+        }
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write_with(f, &|r| format!("{:?}", r), None)
+    }
+}
+
+/// Write a branch or jump offset, as absolute target if the `pc` of the instruction is known.
+fn write_offset(w: &mut dyn fmt::Write, pc: Option<u64>, offset: i32) -> fmt::Result {
+    match pc {
+        Some(pc) => write!(w, "{:#x}", pc.wrapping_add(offset as i64 as u64)),
+        None => write!(w, "{}", offset),
+    }
+}
+
+/// Write the predecessor or successor set of a fence in the low 4 bits of `bits`.
+fn write_fence_set(w: &mut dyn fmt::Write, bits: u32) -> fmt::Result {
+    for (flag, name) in [(0b1000, 'i'), (0b0100, 'o'), (0b0010, 'r'), (0b0001, 'w')] {
+        if bits & flag != 0 {
+            write!(w, "{}", name)?;
+        }
+    }
+
+    Ok(())
 }
 
 impl From<Instruction> for u32 {
@@ -678,6 +1278,7 @@ impl From<Instruction> for u32 {
             Instruction::Sw(SType(x)) => x,
             Instruction::Sd(SType(x)) => x,
             Instruction::Fence(IType(x)) => x,
+            Instruction::FenceI(IType(x)) => x,
             Instruction::Addi(IType(x)) => x,
             Instruction::Slti(IType(x)) => x,
             Instruction::Sltiu(IType(x)) => x,
@@ -743,6 +1344,395 @@ impl From<Instruction> for u32 {
             Instruction::Amomaxd(RType(x)) => x,
             Instruction::Amominud(RType(x)) => x,
             Instruction::Amomaxud(RType(x)) => x,
+            Instruction::Hint(x) => x,
+            Instruction::UnknownRType(RType(x)) => x,
+        }
+    }
+}
+
+/// Decode a 32-bit word, see [`decode`](crate::decode).
+impl TryFrom<u32> for Instruction {
+    type Error = DecodingError;
+
+    fn try_from(word: u32) -> Result<Instruction, DecodingError> {
+        crate::decode(word)
+    }
+}
+
+/// Decode a compressed instruction, see [`decompress`](crate::decompress::decompress).
+impl TryFrom<u16> for Instruction {
+    type Error = DecodingError;
+
+    fn try_from(halfword: u16) -> Result<Instruction, DecodingError> {
+        crate::decompress::decompress(halfword).and_then(crate::decode)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Instruction::*;
+
+    #[test]
+    fn sub_word_loads() {
+        let loads = [
+            (0xfff10503, Lb(IType(0xfff10503)), 1, true, "lb a0, -1(sp)"),
+            (0x00251583, Lh(IType(0x00251583)), 2, true, "lh a1, 2(a0)"),
+            (
+                0x0035c603,
+                Lbu(IType(0x0035c603)),
+                1,
+                false,
+                "lbu a2, 3(a1)",
+            ),
+            (
+                0x00465683,
+                Lhu(IType(0x00465683)),
+                2,
+                false,
+                "lhu a3, 4(a2)",
+            ),
+        ];
+
+        for (raw, instruction, size, signed, text) in loads.iter() {
+            assert_eq!(crate::decode(*raw).unwrap(), *instruction);
+            assert_eq!(u32::from(*instruction), *raw);
+            assert_eq!(instruction.access_size(), Some(*size));
+            assert_eq!(instruction.is_signed_load(), Some(*signed));
+            assert_eq!(instruction.to_string(), *text);
+        }
+
+        assert_eq!(
+            Instruction::new_lb(Register::A0, Register::Sp, -1),
+            loads[0].1
+        );
+        assert_eq!(
+            Instruction::new_lhu(Register::A3, Register::A2, 4),
+            loads[3].1
+        );
+        assert_eq!(Instruction::new_nop().access_size(), None);
+    }
+
+    #[test]
+    fn sub_word_stores() {
+        let stores = [
+            (0xfea10fa3, Sb(SType(0xfea10fa3)), 1, "sb a0, -1(sp)"),
+            (0x00b61323, Sh(SType(0x00b61323)), 2, "sh a1, 6(a2)"),
+        ];
+
+        for (raw, instruction, size, text) in stores.iter() {
+            assert_eq!(crate::decode(*raw).unwrap(), *instruction);
+            assert_eq!(u32::from(*instruction), *raw);
+            assert_eq!(instruction.access_size(), Some(*size));
+            assert_eq!(instruction.is_signed_load(), None);
+            assert_eq!(instruction.to_string(), *text);
+        }
+
+        assert_eq!(
+            Instruction::new_sb(Register::Sp, Register::A0, -1),
+            stores[0].1
+        );
+        assert_eq!(
+            Instruction::new_sh(Register::A2, Register::A1, 6),
+            stores[1].1
+        );
+    }
+
+    #[test]
+    fn effective_immediate() {
+        // beq a0,zero,-12
+        assert_eq!(Beq(BType(0xfe050ae3)).effective_immediate(), Some(-12));
+        // lui a5,0x1
+        assert_eq!(Lui(UType(0x000017b7)).effective_immediate(), Some(0x1000));
+        // lui a5,0xfffff
+        assert_eq!(Lui(UType(0xfffff7b7)).effective_immediate(), Some(-0x1000));
+        // srai s0,s0,0x3
+        assert_eq!(Srai(IType(0x40345413)).effective_immediate(), Some(3));
+        // add a2,a2,a0
+        assert_eq!(Add(RType(0x00a60633)).effective_immediate(), None);
+    }
+
+    #[test]
+    fn field_accessors() {
+        use Register::*;
+
+        let instructions = [
+            (Add(RType(0x00c58633)), Some(A2), Some(A1), Some(A2), None), // add a2,a1,a2
+            (Addi(IType(0xff010113)), Some(Sp), Some(Sp), None, Some(-16)), // addi sp,sp,-16
+            (Srai(IType(0x43f5d513)), Some(A0), Some(A1), None, Some(63)), // srai a0,a1,63
+            (Ld(IType(0x0007b783)), Some(A5), Some(A5), None, Some(0)),   // ld a5,0(a5)
+            (Sd(SType(0xfea13c23)), None, Some(Sp), Some(A0), Some(-8)),  // sd a0,-8(sp)
+            (
+                Beq(BType(0xfe050ae3)),
+                None,
+                Some(A0),
+                Some(Zero),
+                Some(-12),
+            ), // beq a0,zero,-12
+            (Lui(UType(0xfffff7b7)), Some(A5), None, None, Some(0xfffff)), // lui a5,0xfffff
+            (Auipc(UType(0x00001097)), Some(Ra), None, None, Some(1)),    // auipc ra,0x1
+            (Jal(JType(0x008000ef)), Some(Ra), None, None, Some(8)),      // jal ra,8
+            (Jalr(IType(0x00008067)), Some(Zero), Some(Ra), None, Some(0)), // ret
+            (Ecall(IType(0x00000073)), Some(Zero), Some(Zero), None, None), // ecall
+            (Hint(0x00100013), None, None, None, None),
+        ];
+
+        for (instruction, rd, rs1, rs2, imm) in instructions.iter() {
+            assert_eq!(instruction.rd(), *rd, "{:?}", instruction);
+            assert_eq!(instruction.rs1(), *rs1, "{:?}", instruction);
+            assert_eq!(instruction.rs2(), *rs2, "{:?}", instruction);
+            assert_eq!(instruction.immediate(), *imm, "{:?}", instruction);
+        }
+    }
+
+    #[test]
+    fn try_from_words() {
+        use core::convert::TryInto;
+
+        // addi sp,sp,-16
+        assert_eq!(
+            Instruction::try_from(0xff010113_u32),
+            Ok(Addi(IType(0xff010113)))
+        );
+        // c.addi sp,-16
+        assert_eq!(
+            Instruction::try_from(0x1141_u16),
+            Ok(Addi(IType(0xff010113)))
+        );
+        // c.addi sp,-16 in the lower half of a word
+        assert_eq!(
+            Instruction::try_from(0x1141_u32),
+            Ok(Addi(IType(0xff010113)))
+        );
+
+        // the lower half of a 32-bit instruction is no compressed instruction
+        assert_eq!(
+            Instruction::try_from(0x0113_u16),
+            Err(DecodingError::Illegal(0x0113))
+        );
+        assert_eq!(
+            Instruction::try_from(0xffff_ffff_u32),
+            Err(DecodingError::Illegal(0xffff_ffff))
+        );
+
+        let words = [0x00100513_u32, 0x00000073];
+        let decoded: Result<Vec<Instruction>, _> = words.iter().map(|w| (*w).try_into()).collect();
+        assert_eq!(decoded.unwrap()[1], Ecall(IType(0x00000073)));
+    }
+
+    #[test]
+    fn branch_target() {
+        // beq a0,zero,-12
+        let beq = crate::decode(0xfe050ae3).unwrap();
+        assert!(beq.is_control_flow());
+        assert_eq!(beq.branch_target(0x1000c), Some(0x10000));
+
+        // bge a0,a1,-4096
+        assert_eq!(Bge(BType(0x8005d063)).branch_target(0x11000), Some(0x10000));
+        // bne a0,a1,4094
+        assert_eq!(Bne(BType(0x7eb51fe3)).branch_target(0x10000), Some(0x10ffe));
+        // jal zero,-1048576
+        assert_eq!(Jal(JType(0x8000006f)).branch_target(0x100000), Some(0));
+        // ret
+        let ret = Jalr(IType(0x00008067));
+        assert!(ret.is_control_flow());
+        assert_eq!(ret.branch_target(0x10000), None);
+
+        // ecall, addi a0,a0,1
+        for instruction in [Ecall(IType(0x00000073)), Addi(IType(0x00150513))].iter() {
+            assert!(!instruction.is_control_flow(), "{:?}", instruction);
+            assert_eq!(
+                instruction.branch_target(0x10000),
+                None,
+                "{:?}",
+                instruction
+            );
+        }
+    }
+
+    #[test]
+    fn rename_registers() {
+        let rename = |r| if r == Register::A0 { Register::A1 } else { r };
+
+        // add a0,a0,a2
+        let renamed = Add(RType(0x00c50533)).rename_registers(&rename);
+        assert_eq!(renamed.to_string(), "add a1, a1, a2");
+        assert_eq!(renamed, Add(RType(0x00c585b3))); // add a1,a1,a2
+
+        // sd a0,-8(a0)
+        assert_eq!(
+            Sd(SType(0xfea53c23)).rename_registers(&rename).to_string(),
+            "sd a1, -8(a1)"
+        );
+        assert_eq!(
+            Instruction::new_ecall().rename_registers(&rename),
+            Instruction::new_ecall()
+        );
+    }
+
+    #[test]
+    fn display_with() {
+        let name = |r| match r {
+            Register::A0 => String::from("result"),
+            r => format!("{:?}", r),
+        };
+
+        // addi a0,a0,1
+        assert_eq!(
+            Addi(IType(0x00150513)).display_with(0x10000, name),
+            "addi result, result, 1"
+        );
+        // beq a0,zero,-12
+        assert_eq!(
+            Beq(BType(0xfe050ae3)).display_with(0x10010, name),
+            "beq result, zero, 0x10004"
+        );
+    }
+
+    #[test]
+    fn display() {
+        let display = |instruction: Instruction| instruction.to_string();
+
+        assert_eq!(display(Addi(IType(0x02a58513))), "addi a0, a1, 42"); // addi x10,x11,42
+        assert_eq!(display(Lw(IType(0x00812503))), "lw a0, 8(sp)"); // lw x10,8(x2)
+        assert_eq!(display(Sd(SType(0x00f6b423))), "sd a5, 8(a3)"); // sd x15,8(x13)
+        assert_eq!(display(Beq(BType(0xfe050ae3))), "beq a0, zero, -12"); // beq x10,x0,-12
+        assert_eq!(display(Lui(UType(0x000017b7))), "lui a5, 0x1"); // lui x15,0x1
+        assert_eq!(display(Srai(IType(0x40345413))), "srai fp, fp, 3"); // srai x8,x8,3
+        assert_eq!(display(Fence(IType(0x0ff0000f))), "fence iorw,iorw"); // fence
+        assert_eq!(display(FenceI(IType(0x0000100f))), "fence.i"); // fence.i
+        assert_eq!(display(Instruction::new_ecall()), "ecall");
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip() {
+        let add = Add(RType(0x00c58633)); // add x12,x11,x12
+        assert_eq!(serde_json::to_string(&add).unwrap(), r#"{"Add":12944947}"#);
+
+        // every compressed encoding and its expansion, which cover most of the variants
+        let instructions = (0..=u16::MAX)
+            .filter_map(|i| crate::decode(u32::from(i)).ok())
+            .chain(Some(Instruction::new_fence_i()))
+            .chain(Some(Instruction::new_ebreak()))
+            .chain(Some(Instruction::Hint(0x00100033)));
+
+        for instruction in instructions {
+            let json = serde_json::to_string(&instruction).unwrap();
+            assert_eq!(
+                serde_json::from_str::<Instruction>(&json).unwrap(),
+                instruction
+            );
+        }
+
+        let json = serde_json::to_string(&Register::Sp).unwrap();
+        assert_eq!(json, r#""Sp""#);
+        assert_eq!(
+            serde_json::from_str::<Register>(&json).unwrap(),
+            Register::Sp
+        );
+    }
+
+    #[test]
+    fn display_signed_immediates() {
+        let display = |raw| crate::decode(raw).unwrap().to_string();
+
+        assert_eq!(display(0xff010113), "addi sp, sp, -16"); // addi x2,x2,-16
+        assert_eq!(display(0xff85b503), "ld a0, -8(a1)"); // ld x10,-8(x11)
+        assert_eq!(display(0xfea13c23), "sd a0, -8(sp)"); // sd x10,-8(x2)
+        assert_eq!(display(0xfeb51ee3), "bne a0, a1, -4"); // bne x10,x11,-4
+        assert_eq!(display(0xf89ff06f), "jal zero, -120"); // jal x0,-120
+        assert_eq!(display(0xfe0080e7), "jalr ra, -32(ra)"); // jalr x1,-32(x1)
+        assert_eq!(display(0xfffff7b7), "lui a5, 0xfffff"); // lui x15,0xfffff
+    }
+
+    #[test]
+    fn same_shape() {
+        let addi_sp_16 = Addi(IType(0x01010113)); // addi x2,x2,16
+        let addi_sp_m32 = Addi(IType(0xfe010113)); // addi x2,x2,-32
+        let addi_a0 = Addi(IType(0x01050513)); // addi x10,x10,16
+        let addiw_sp = Addiw(IType(0x0101011b)); // addiw x2,x2,16
+
+        assert!(addi_sp_16.same_shape(&addi_sp_m32));
+        assert!(!addi_sp_16.same_shape(&addi_a0));
+        assert!(!addi_sp_16.same_shape(&addiw_sp));
+    }
+
+    #[test]
+    fn format() {
+        use std::{collections::HashSet, mem::discriminant};
+        use InstructionFormat::*;
+
+        // formats by opcode as listed in the specification
+        let format_of_opcode = |opcode| match opcode {
+            OP_OP | OP_OP32 | OP_AMO => R,
+            OP_LD | OP_IMM | OP_IMM32 | OP_JALR | OP_SYSTEM | OP_FENCE => I,
+            OP_SD => S,
+            OP_BRANCH => B,
+            OP_LUI | OP_AUIPC => U,
+            OP_JAL => J,
+            _ => unreachable!(),
+        };
+
+        let random_words = crate::test_rng::Rng::new().words().take(1_000_000);
+
+        // ecall and ebreak have a single encoding each
+        let mut variants = HashSet::new();
+        for word in random_words.chain([0x00000073, 0x00100073].iter().copied()) {
+            if let Ok(instruction) = crate::decode(word) {
+                assert_eq!(
+                    instruction.format(),
+                    format_of_opcode(word & 0b111_1111),
+                    "{:?}",
+                    instruction
+                );
+                variants.insert(discriminant(&instruction));
+            }
         }
+
+        // every variant but the ones which `decode` never returns
+        assert_eq!(variants.len(), 88);
+
+        assert_eq!(Hint(0x00100013).format(), I); // addi zero,zero,1
+        assert_eq!(Hint(0x00b00033).format(), R); // add zero,zero,a1
+        assert_eq!(Hint(0x00001037).format(), U); // lui zero,0x1
+        assert_eq!(Hint(0x0005).format(), I); // c.addi zero,1
+        assert_eq!(UnknownRType(RType(0x0cb50533)).format(), R);
+    }
+
+    #[test]
+    fn encoding_fields() {
+        assert_eq!(
+            Add(RType(0x00c58633)).encoding_fields(), // add x12,x11,x12
+            EncodingFields {
+                opcode: 0b0110011,
+                funct3: Some(0b000),
+                funct7: Some(0b0000000),
+            }
+        );
+        assert_eq!(
+            Instruction::Sub(RType(0x40b50533)).encoding_fields(), // sub x10,x10,x11
+            EncodingFields {
+                opcode: 0b0110011,
+                funct3: Some(0b000),
+                funct7: Some(0b0100000),
+            }
+        );
+        assert_eq!(
+            Instruction::Ld(IType(0x01853683)).encoding_fields(), // ld x13,24(x10)
+            EncodingFields {
+                opcode: 0b0000011,
+                funct3: Some(0b011),
+                funct7: None,
+            }
+        );
+        assert_eq!(
+            Instruction::Lui(UType(0x00001a37)).encoding_fields(), // lui x20,0x1
+            EncodingFields {
+                opcode: 0b0110111,
+                funct3: None,
+                funct7: None,
+            }
+        );
     }
 }
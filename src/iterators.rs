@@ -111,3 +111,51 @@ impl Iterator for InstructionIter<'_> {
         )
     }
 }
+
+/// An iterator over the raw encodings in the program, yielding the address, the encoding and
+/// its length in bytes without decoding it. Stops at a truncated 32-bit encoding at the end.
+#[cfg(feature = "std")]
+pub(crate) struct EncodingIter<'a> {
+    memory_view: &'a [u8],
+    current_index: usize,
+    address: u64,
+}
+
+#[cfg(feature = "std")]
+impl EncodingIter<'_> {
+    pub(crate) fn new(memory_view: &[u8], address: u64) -> EncodingIter<'_> {
+        EncodingIter {
+            memory_view,
+            current_index: 0,
+            address,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Iterator for EncodingIter<'_> {
+    type Item = (u64, u32, usize);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let rest = &self.memory_view[self.current_index..];
+
+        if rest.len() < 2 {
+            return None;
+        }
+
+        let hword = LittleEndian::read_u16(rest);
+
+        let (raw, length) = if instruction_length(hword) == 2 {
+            (u32::from(hword), 2)
+        } else if rest.len() >= 4 {
+            (LittleEndian::read_u32(rest), 4)
+        } else {
+            return None;
+        };
+
+        let address = self.address + self.current_index as u64;
+        self.current_index += length;
+
+        Some((address, raw, length))
+    }
+}
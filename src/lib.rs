@@ -1,12 +1,35 @@
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
+
+#[cfg(feature = "std")]
+pub mod analysis;
+pub mod bits;
+pub mod build;
 pub mod decode;
 pub mod decompress;
+#[cfg(feature = "std")]
+pub mod disassembly;
+#[cfg(feature = "std")]
 pub mod elf;
+pub mod encode;
 pub mod instruction;
 pub mod iterators;
 pub mod register;
+#[cfg(test)]
+mod test_rng;
+pub mod three_address;
 pub mod types;
+#[cfg(feature = "std")]
+pub mod writer;
 
 pub use decode::*;
+#[cfg(feature = "std")]
 pub use elf::*;
+pub use encode::encode;
+#[cfg(feature = "std")]
+pub use goblin;
 pub use instruction::Instruction;
 pub use register::Register;
+#[cfg(feature = "std")]
+pub use writer::{write_object_file, write_object_file_to_vec};
@@ -1,6 +1,7 @@
-use core::fmt;
+use core::{convert::TryFrom, fmt};
 
 #[derive(Clone, Copy, Eq, PartialOrd, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(u32)]
 pub enum Register {
     Zero = 0,
@@ -37,17 +38,91 @@ pub enum Register {
     T6,
 }
 
+const ABI_NAMES: [&str; 32] = [
+    "zero", "ra", "sp", "gp", "tp", "t0", "t1", "t2", "fp", "s1", "a0", "a1", "a2", "a3", "a4",
+    "a5", "a6", "a7", "s2", "s3", "s4", "s5", "s6", "s7", "s8", "s9", "s10", "s11", "t3", "t4",
+    "t5", "t6",
+];
+
+impl Register {
+    /// The ABI name of the register, e.g. `a0` for x10. x8 is named `fp` rather than `s0`.
+    pub fn abi_name(&self) -> &'static str {
+        ABI_NAMES[u32::from(*self) as usize]
+    }
+
+    /// Parse the ABI name of a register, accepting `s0` as an alias of `fp`.
+    pub fn from_abi_name(name: &str) -> Option<Register> {
+        if name == "s0" {
+            return Some(Register::Fp);
+        }
+
+        ABI_NAMES
+            .iter()
+            .position(|abi_name| *abi_name == name)
+            .map(|idx| Register::from(idx as u32))
+    }
+
+    /// Whether a callee may overwrite the register without restoring it, as assigned by the
+    /// standard calling convention: `ra`, the temporaries and the arguments.
+    pub fn is_caller_saved(&self) -> bool {
+        *self == Register::Ra || self.is_temporary() || self.is_argument()
+    }
+
+    /// Whether a callee has to restore the register before returning: `sp` and `s0`-`s11`.
+    ///
+    /// `zero`, `gp` and `tp` are neither caller- nor callee-saved.
+    pub fn is_callee_saved(&self) -> bool {
+        use Register::*;
+
+        matches!(
+            self,
+            Sp | Fp | S1 | S2 | S3 | S4 | S5 | S6 | S7 | S8 | S9 | S10 | S11
+        )
+    }
+
+    /// Whether the register passes function arguments and return values: `a0`-`a7`.
+    pub fn is_argument(&self) -> bool {
+        use Register::*;
+
+        matches!(self, A0 | A1 | A2 | A3 | A4 | A5 | A6 | A7)
+    }
+
+    /// Whether the register is one of the temporaries `t0`-`t6`.
+    pub fn is_temporary(&self) -> bool {
+        use Register::*;
+
+        matches!(self, T0 | T1 | T2 | T3 | T4 | T5 | T6)
+    }
+}
+
 impl fmt::Debug for Register {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        const LUT: [&str; 32] = [
-            "zero", "ra", "sp", "gp", "tp", "t0", "t1", "t2", "fp", "s1", "a0", "a1", "a2", "a3",
-            "a4", "a5", "a6", "a7", "s2", "s3", "s4", "s5", "s6", "s7", "s8", "s9", "s10", "s11",
-            "t3", "t4", "t5", "t6",
-        ];
+        write!(f, "{}", self.abi_name())
+    }
+}
+
+/// Error of converting a number outside of 0..32 to a [`Register`].
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct InvalidRegister(pub u16);
+
+impl fmt::Display for InvalidRegister {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} is not the number of a register", self.0)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for InvalidRegister {}
 
-        let idx: u32 = (*self).into();
+impl TryFrom<u16> for Register {
+    type Error = InvalidRegister;
 
-        write!(f, "{}", LUT[idx as usize])
+    fn try_from(raw: u16) -> Result<Register, InvalidRegister> {
+        if raw < 32 {
+            Ok(Register::from(u32::from(raw)))
+        } else {
+            Err(InvalidRegister(raw))
+        }
     }
 }
 
@@ -62,3 +137,75 @@ impl From<Register> for u32 {
         unsafe { core::mem::transmute(reg) }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn abi_names() {
+        for raw in 0..32u16 {
+            let register = Register::try_from(raw).unwrap();
+
+            assert_eq!(u32::from(register), u32::from(raw));
+            assert_eq!(Register::from_abi_name(register.abi_name()), Some(register));
+            assert_eq!(format!("{:?}", register), register.abi_name());
+        }
+
+        assert_eq!(Register::A0.abi_name(), "a0");
+        assert_eq!(Register::from_abi_name("s0"), Some(Register::Fp));
+        assert_eq!(Register::from_abi_name("t6"), Some(Register::T6));
+        assert_eq!(Register::from_abi_name("x10"), None);
+        assert_eq!(Register::try_from(32u16), Err(InvalidRegister(32)));
+    }
+
+    #[test]
+    fn abi_classification() {
+        // caller-saved, callee-saved, argument and temporary, from the calling convention of the
+        // RISC-V ELF psABI
+        let table = [
+            ("zero", false, false, false, false),
+            ("ra", true, false, false, false),
+            ("sp", false, true, false, false),
+            ("gp", false, false, false, false),
+            ("tp", false, false, false, false),
+            ("t0", true, false, false, true),
+            ("t1", true, false, false, true),
+            ("t2", true, false, false, true),
+            ("fp", false, true, false, false),
+            ("s1", false, true, false, false),
+            ("a0", true, false, true, false),
+            ("a1", true, false, true, false),
+            ("a2", true, false, true, false),
+            ("a3", true, false, true, false),
+            ("a4", true, false, true, false),
+            ("a5", true, false, true, false),
+            ("a6", true, false, true, false),
+            ("a7", true, false, true, false),
+            ("s2", false, true, false, false),
+            ("s3", false, true, false, false),
+            ("s4", false, true, false, false),
+            ("s5", false, true, false, false),
+            ("s6", false, true, false, false),
+            ("s7", false, true, false, false),
+            ("s8", false, true, false, false),
+            ("s9", false, true, false, false),
+            ("s10", false, true, false, false),
+            ("s11", false, true, false, false),
+            ("t3", true, false, false, true),
+            ("t4", true, false, false, true),
+            ("t5", true, false, false, true),
+            ("t6", true, false, false, true),
+        ];
+
+        for (raw, (name, caller, callee, argument, temporary)) in table.iter().enumerate() {
+            let register = Register::from(raw as u32);
+
+            assert_eq!(register.abi_name(), *name);
+            assert_eq!(register.is_caller_saved(), *caller, "{}", name);
+            assert_eq!(register.is_callee_saved(), *callee, "{}", name);
+            assert_eq!(register.is_argument(), *argument, "{}", name);
+            assert_eq!(register.is_temporary(), *temporary, "{}", name);
+        }
+    }
+}
@@ -0,0 +1,25 @@
+//! Deterministic pseudo-random numbers for the tests, which need no dependency this way.
+
+/// Linear congruential generator with the constants of PCG.
+pub(crate) struct Rng(u64);
+
+impl Rng {
+    pub(crate) fn new() -> Self {
+        Self(0x2545_f491_4f6c_dd1d)
+    }
+
+    /// The upper, more random half of the next state.
+    pub(crate) fn next_u32(&mut self) -> u32 {
+        self.0 = self
+            .0
+            .wrapping_mul(6_364_136_223_846_793_005)
+            .wrapping_add(1_442_695_040_888_963_407);
+
+        (self.0 >> 32) as u32
+    }
+
+    /// An endless stream of words with the lowest two bits set, i.e. of 32-bit instructions.
+    pub(crate) fn words(mut self) -> impl Iterator<Item = u32> {
+        core::iter::repeat_with(move || self.next_u32() | 0b11)
+    }
+}
@@ -0,0 +1,294 @@
+//! # Normalize instructions into three-address form
+//!
+//! Every instruction is represented as `dst = op(src1, src2)`, where register-immediate
+//! instructions share the operation of their register-register counterpart, e.g. `addi` and
+//! `add` both become [`ThreeAddrOp::Add`].
+
+use crate::{Instruction, Register};
+
+/// The second source of an operation.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Operand {
+    Register(Register),
+    Immediate(i64),
+}
+
+/// Operation of an instruction in three-address form.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum ThreeAddrOp {
+    Add,
+    Sub,
+    Sll,
+    Slt,
+    Sltu,
+    Xor,
+    Srl,
+    Sra,
+    Or,
+    And,
+    Mul,
+    Mulh,
+    Mulhsu,
+    Mulhu,
+    Div,
+    Divu,
+    Rem,
+    Remu,
+    Addw,
+    Subw,
+    Sllw,
+    Srlw,
+    Sraw,
+    Mulw,
+    Divw,
+    Divuw,
+    Remw,
+    Remuw,
+    /// `dst = memory[src1 + src2]`, sign- or zero-extended from `size` bytes.
+    Load {
+        size: usize,
+        signed: bool,
+    },
+    /// `memory[src1 + offset] = src2`, truncated to `size` bytes.
+    Store {
+        size: usize,
+        offset: i64,
+    },
+    /// `pc = pc + offset` if `src1 == src2`.
+    Beq {
+        offset: i64,
+    },
+    /// `pc = pc + offset` if `src1 != src2`.
+    Bne {
+        offset: i64,
+    },
+    /// `pc = pc + offset` if `src1 < src2` (signed).
+    Blt {
+        offset: i64,
+    },
+    /// `pc = pc + offset` if `src1 >= src2` (signed).
+    Bge {
+        offset: i64,
+    },
+    /// `pc = pc + offset` if `src1 < src2` (unsigned).
+    Bltu {
+        offset: i64,
+    },
+    /// `pc = pc + offset` if `src1 >= src2` (unsigned).
+    Bgeu {
+        offset: i64,
+    },
+    /// `dst = pc + 4; pc = pc + src2`.
+    Jal,
+    /// `dst = pc + 4; pc = (src1 + src2) & !1`.
+    Jalr,
+    /// `dst = src2`, with `src2` already shifted into the upper bits.
+    Lui,
+    /// `dst = pc + src2`, with `src2` already shifted into the upper bits.
+    Auipc,
+}
+
+/// An instruction in three-address form: `dst = op(src1, src2)`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ThreeAddr {
+    pub dst: Option<Register>,
+    pub op: ThreeAddrOp,
+    pub src1: Option<Register>,
+    pub src2: Operand,
+}
+
+impl Instruction {
+    /// Normalize this instruction into three-address form.
+    ///
+    /// Returns `None` for instructions without a data flow between registers, i.e. `fence`,
+    /// `ecall`, `ebreak`, atomics and HINTs.
+    pub fn as_three_address(&self) -> Option<ThreeAddr> {
+        use Instruction::*;
+        use ThreeAddrOp as Op;
+
+        let reg = Operand::Register;
+        let imm = self.effective_immediate().map(Operand::Immediate);
+
+        let (dst, op, src1, src2) = match *self {
+            Lui(u) => (Some(u.rd()), Op::Lui, None, imm?),
+            Auipc(u) => (Some(u.rd()), Op::Auipc, None, imm?),
+            Jal(j) => (Some(j.rd()), Op::Jal, None, imm?),
+            Jalr(i) => (Some(i.rd()), Op::Jalr, Some(i.rs1()), imm?),
+            Beq(b) | Bne(b) | Blt(b) | Bge(b) | Bltu(b) | Bgeu(b) => {
+                let offset = i64::from(b.imm());
+                let op = match *self {
+                    Beq(_) => Op::Beq { offset },
+                    Bne(_) => Op::Bne { offset },
+                    Blt(_) => Op::Blt { offset },
+                    Bge(_) => Op::Bge { offset },
+                    Bltu(_) => Op::Bltu { offset },
+                    _ => Op::Bgeu { offset },
+                };
+
+                (None, op, Some(b.rs1()), reg(b.rs2()))
+            }
+            Lb(i) | Lh(i) | Lw(i) | Ld(i) | Lbu(i) | Lhu(i) | Lwu(i) => {
+                let op = Op::Load {
+                    size: self.access_size()?,
+                    signed: self.is_signed_load()?,
+                };
+
+                (Some(i.rd()), op, Some(i.rs1()), imm?)
+            }
+            Sb(s) | Sh(s) | Sw(s) | Sd(s) => {
+                let op = Op::Store {
+                    size: self.access_size()?,
+                    offset: i64::from(s.imm()),
+                };
+
+                (None, op, Some(s.rs1()), reg(s.rs2()))
+            }
+            Addi(i) | Slti(i) | Sltiu(i) | Xori(i) | Ori(i) | Andi(i) | Addiw(i) => {
+                let op = match *self {
+                    Addi(_) => Op::Add,
+                    Slti(_) => Op::Slt,
+                    Sltiu(_) => Op::Sltu,
+                    Xori(_) => Op::Xor,
+                    Ori(_) => Op::Or,
+                    Andi(_) => Op::And,
+                    _ => Op::Addw,
+                };
+
+                (Some(i.rd()), op, Some(i.rs1()), imm?)
+            }
+            Slli(i) | Srli(i) | Srai(i) => {
+                let op = match *self {
+                    Slli(_) => Op::Sll,
+                    Srli(_) => Op::Srl,
+                    _ => Op::Sra,
+                };
+
+                (Some(i.rd()), op, Some(i.rs1()), imm?)
+            }
+            Slliw(i) | Srliw(i) | Sraiw(i) => {
+                let op = match *self {
+                    Slliw(_) => Op::Sllw,
+                    Srliw(_) => Op::Srlw,
+                    _ => Op::Sraw,
+                };
+
+                (Some(i.rd()), op, Some(i.rs1()), imm?)
+            }
+            Add(r) | Sub(r) | Sll(r) | Slt(r) | Sltu(r) | Xor(r) | Srl(r) | Sra(r) | Or(r)
+            | And(r) | Mul(r) | Mulh(r) | Mulhsu(r) | Mulhu(r) | Div(r) | Divu(r) | Rem(r)
+            | Remu(r) | Addw(r) | Subw(r) | Sllw(r) | Srlw(r) | Sraw(r) | Mulw(r) | Divw(r)
+            | Divuw(r) | Remw(r) | Remuw(r) => {
+                let op = match *self {
+                    Add(_) => Op::Add,
+                    Sub(_) => Op::Sub,
+                    Sll(_) => Op::Sll,
+                    Slt(_) => Op::Slt,
+                    Sltu(_) => Op::Sltu,
+                    Xor(_) => Op::Xor,
+                    Srl(_) => Op::Srl,
+                    Sra(_) => Op::Sra,
+                    Or(_) => Op::Or,
+                    And(_) => Op::And,
+                    Mul(_) => Op::Mul,
+                    Mulh(_) => Op::Mulh,
+                    Mulhsu(_) => Op::Mulhsu,
+                    Mulhu(_) => Op::Mulhu,
+                    Div(_) => Op::Div,
+                    Divu(_) => Op::Divu,
+                    Rem(_) => Op::Rem,
+                    Remu(_) => Op::Remu,
+                    Addw(_) => Op::Addw,
+                    Subw(_) => Op::Subw,
+                    Sllw(_) => Op::Sllw,
+                    Srlw(_) => Op::Srlw,
+                    Sraw(_) => Op::Sraw,
+                    Mulw(_) => Op::Mulw,
+                    Divw(_) => Op::Divw,
+                    Divuw(_) => Op::Divuw,
+                    Remw(_) => Op::Remw,
+                    _ => Op::Remuw,
+                };
+
+                (Some(r.rd()), op, Some(r.rs1()), reg(r.rs2()))
+            }
+            _ => return None,
+        };
+
+        Some(ThreeAddr {
+            dst,
+            op,
+            src1,
+            src2,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decode;
+
+    fn three_address(i: u32) -> ThreeAddr {
+        decode(i).unwrap().as_three_address().unwrap()
+    }
+
+    #[test]
+    fn register_operations() {
+        // sub a0,a1,a2
+        assert_eq!(
+            three_address(0x40c58533),
+            ThreeAddr {
+                dst: Some(Register::A0),
+                op: ThreeAddrOp::Sub,
+                src1: Some(Register::A1),
+                src2: Operand::Register(Register::A2),
+            }
+        );
+    }
+
+    #[test]
+    fn immediate_operations() {
+        // addi a0,a1,-1
+        assert_eq!(
+            three_address(0xfff58513),
+            ThreeAddr {
+                dst: Some(Register::A0),
+                op: ThreeAddrOp::Add,
+                src1: Some(Register::A1),
+                src2: Operand::Immediate(-1),
+            }
+        );
+
+        // lui a5,0xfffff
+        assert_eq!(three_address(0xfffff7b7).src2, Operand::Immediate(-4096));
+
+        // srai s0,s0,0x3
+        assert_eq!(three_address(0x40345413).src2, Operand::Immediate(3));
+    }
+
+    #[test]
+    fn memory_and_control() {
+        // sd a0,-8(a1)
+        assert_eq!(
+            three_address(0xfea5bc23),
+            ThreeAddr {
+                dst: None,
+                op: ThreeAddrOp::Store {
+                    size: 8,
+                    offset: -8
+                },
+                src1: Some(Register::A1),
+                src2: Operand::Register(Register::A0),
+            }
+        );
+
+        // beq a0,zero,-12
+        assert_eq!(
+            three_address(0xfe050ae3).op,
+            ThreeAddrOp::Beq { offset: -12 }
+        );
+
+        // ecall
+        assert_eq!(decode(0x00000073).unwrap().as_three_address(), None);
+    }
+}
@@ -16,28 +16,36 @@
 
 use core::fmt;
 
-use crate::Register;
+use crate::{
+    bits::Permutable,
+    encode::{self, B_TYPE_IMM, J_TYPE_IMM},
+    Register,
+};
 
 #[derive(Clone, Copy, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct RType(pub u32);
 impl RType {
     pub(crate) fn new(
         funct7: u32,
         funct3: u32,
         opcode: u32,
-        rd: Register,
         rs1: Register,
         rs2: Register,
+        rd: Register,
     ) -> Self {
         assert!(funct7 < 2_u32.pow(7));
         assert!(funct3 < 2_u32.pow(3));
         assert!(opcode < 2_u32.pow(7));
 
-        let rs2: u32 = rs2.into();
-        let rs1: u32 = rs1.into();
-        let rd: u32 = rd.into();
-
-        Self((((((((((funct7 << 5) + rs2) << 5) + rs1) << 3) + funct3) << 5) + rd) << 7) + opcode)
+        Self(encode::r_type(
+            funct7,
+            rs2.into(),
+            rs1.into(),
+            funct3,
+            rd.into(),
+            opcode,
+        ))
     }
     pub fn rs2(&self) -> Register {
         Register::from((self.0 >> 20) & 0x1f)
@@ -63,6 +71,7 @@ impl fmt::Debug for RType {
 }
 
 #[derive(Clone, Copy, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct IType(pub u32);
 impl IType {
     pub(crate) fn new(
@@ -76,12 +85,15 @@ impl IType {
         assert!(funct3 < 2_u32.pow(3));
         assert!(opcode < 2_u32.pow(7));
 
-        let rd: u32 = rd.into();
-        let rs1: u32 = rs1.into();
-
         let immediate = sign_shrink(immediate, 12);
 
-        Self((((((((immediate << 5) + rs1) << 3) + funct3) << 5) + rd) << 7) + opcode)
+        Self(encode::i_type(
+            immediate,
+            rs1.into(),
+            funct3,
+            rd.into(),
+            opcode,
+        ))
     }
     pub fn imm(&self) -> i32 {
         sign_extend(self.0 >> 20, 12)
@@ -107,6 +119,7 @@ impl fmt::Debug for IType {
 }
 
 #[derive(Clone, Copy, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SType(pub u32);
 impl SType {
     pub(crate) fn new(
@@ -120,15 +133,15 @@ impl SType {
         assert!(funct3 < 2_u32.pow(3));
         assert!(opcode < 2_u32.pow(7));
 
-        let rs1: u32 = rs1.into();
-        let rs2: u32 = rs2.into();
-
         let immediate = sign_shrink(immediate, 12);
 
-        let imm1 = get_bits(immediate, 5, 7);
-        let imm2 = get_bits(immediate, 0, 5);
-
-        Self((((((((((imm1 << 5) + rs2) << 5) + rs1) << 3) + funct3) << 5) + imm2) << 7) + opcode)
+        Self(encode::s_type(
+            immediate,
+            rs2.into(),
+            rs1.into(),
+            funct3,
+            opcode,
+        ))
     }
     pub fn imm(&self) -> i32 {
         sign_extend(((self.0 >> 20) & 0xfe0) | ((self.0 >> 7) & 0x1f), 12)
@@ -154,6 +167,7 @@ impl fmt::Debug for SType {
 }
 
 #[derive(Clone, Copy, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct BType(pub u32);
 impl BType {
     pub(crate) fn new(
@@ -167,33 +181,20 @@ impl BType {
         assert!(funct3 < 2_u32.pow(3));
         assert!(opcode < 2_u32.pow(7));
 
-        let rs1: u32 = rs1.into();
-        let rs2: u32 = rs2.into();
-
         let immediate = sign_shrink(immediate, 13);
 
-        let imm1 = get_bits(immediate, 12, 1);
-        let imm2 = get_bits(immediate, 5, 6);
-        let imm3 = get_bits(immediate, 1, 4);
-        let imm4 = get_bits(immediate, 11, 1);
-
-        Self(
-            (((((((((((((imm1 << 6) + imm2) << 5) + rs2) << 5) + rs1) << 3) + funct3) << 4)
-                + imm3)
-                << 1)
-                + imm4)
-                << 7)
-                + opcode,
-        )
+        Self(encode::b_type(
+            immediate,
+            rs2.into(),
+            rs1.into(),
+            funct3,
+            opcode,
+        ))
     }
     pub fn imm(&self) -> i32 {
-        sign_extend(
-            ((self.0 & 0x8000_0000) >> 19)
-                | ((self.0 & 0x7e00_0000) >> 20)
-                | ((self.0 & 0x0000_0f00) >> 7)
-                | ((self.0 & 0x0000_0080) << 4),
-            13,
-        )
+        let packed = ((self.0 >> 25) << 5) | ((self.0 >> 7) & 0x1f);
+
+        sign_extend(packed.inv_permute(&B_TYPE_IMM), 13)
     }
     pub fn rs1(&self) -> Register {
         Register::from((self.0 >> 15) & 0x1f)
@@ -216,17 +217,16 @@ impl fmt::Debug for BType {
 }
 
 #[derive(Clone, Copy, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct UType(pub u32);
 impl UType {
     pub(crate) fn new(immediate: i32, opcode: u32, rd: Register) -> Self {
-        assert!(immediate > 0 && immediate < 2_i32.pow(21));
+        assert!(-(2_i32.pow(19)) <= immediate && immediate < 2_i32.pow(20));
         assert!(opcode < 2_u32.pow(7));
 
-        let rd: u32 = rd.into();
-
         let immediate = sign_shrink(immediate, 20);
 
-        Self((((immediate << 5) + rd) << 7) + opcode)
+        Self(encode::u_type(immediate, rd.into(), opcode))
     }
     pub fn imm(&self) -> u32 {
         (self.0 & 0xfffff000) >> 12
@@ -243,31 +243,19 @@ impl fmt::Debug for UType {
 }
 
 #[derive(Clone, Copy, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct JType(pub u32);
 impl JType {
     pub(crate) fn new(immediate: i32, opcode: u32, rd: Register) -> Self {
         assert!(-(2_i32.pow(20)) <= immediate && immediate < 2_i32.pow(20));
         assert!(opcode < 2_u32.pow(7));
 
-        let rd: u32 = rd.into();
-
         let immediate = sign_shrink(immediate, 21);
 
-        let imm1 = get_bits(immediate, 20, 1);
-        let imm2 = get_bits(immediate, 1, 10);
-        let imm3 = get_bits(immediate, 11, 1);
-        let imm4 = get_bits(immediate, 12, 8);
-
-        Self((((((((((imm1 << 10) + imm2) << 1) + imm3) << 8) + imm4) << 5) + rd) << 7) + opcode)
+        Self(encode::j_type(immediate, rd.into(), opcode))
     }
     pub fn imm(&self) -> i32 {
-        sign_extend(
-            ((self.0 & 0x8000_0000) >> 11)
-                | ((self.0 & 0x7fe0_0000) >> 20)
-                | ((self.0 & 0x0010_0000) >> 9)
-                | (self.0 & 0x000f_f000),
-            21,
-        )
+        sign_extend((self.0 >> 12).inv_permute(&J_TYPE_IMM), 21)
     }
     pub fn rd(&self) -> Register {
         Register::from((self.0 >> 7) & 0x1f)
@@ -297,15 +285,11 @@ fn sign_extend(n: u32, b: u32) -> i32 {
     assert!(n <= 2_u32.pow(b));
     assert!(0 < b && b < 32);
 
-    unsafe {
-        core::mem::transmute({
-            if n < 2_u32.pow(b - 1) {
-                n
-            } else {
-                n.wrapping_sub(2_u32.pow(b))
-            }
-        })
-    }
+    (if n < 2_u32.pow(b - 1) {
+        n
+    } else {
+        n.wrapping_sub(2_u32.pow(b))
+    }) as i32
 }
 
 fn sign_shrink(immediate: i32, sign: u32) -> u32 {
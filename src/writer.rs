@@ -0,0 +1,203 @@
+//! # Write ELF files
+//!
+//! The inverse of [`load_object_file`](crate::load_object_file): a static, little endian ELF64
+//! executable with the read-only code and the writable data segment expected by the loader.
+
+use crate::{Program, RiscuError, PAGE_SIZE};
+use goblin::elf::{
+    header::{ELFCLASS64, ELFDATA2LSB, ELFMAG, EM_RISCV, ET_EXEC, EV_CURRENT},
+    program_header::{PF_R, PF_W, PF_X, PT_LOAD},
+    section_header::{SHF_ALLOC, SHF_EXECINSTR, SHF_MERGE, SHF_STRINGS, SHT_PROGBITS, SHT_STRTAB},
+};
+use std::{fs, path::Path};
+
+const EHDR_SIZE: u64 = 64;
+const PHDR_SIZE: u64 = 56;
+const SHDR_SIZE: u64 = 64;
+
+/// Write `program` as ELF file, such that [`load_object_file`](crate::load_object_file) yields
+/// an equivalent program.
+pub fn write_object_file<P>(program: &Program, object_file: P) -> Result<(), RiscuError>
+where
+    P: AsRef<Path>,
+{
+    let image = write_object_file_to_vec(program)?;
+
+    fs::write(object_file, image).map_err(RiscuError::CouldNotWriteFile)
+}
+
+/// Build the ELF image of `program` in memory, see [`write_object_file`].
+pub fn write_object_file_to_vec(program: &Program) -> Result<Vec<u8>, RiscuError> {
+    check_program(program)?;
+
+    let code = &program.code;
+    let data = &program.data;
+
+    // file offsets have to be congruent to the addresses modulo the page size
+    let code_offset = congruent_offset(EHDR_SIZE + 2 * PHDR_SIZE, code.address);
+    let data_offset = congruent_offset(code_offset + code.content.len() as u64, data.address);
+    let data_end = data_offset + program.data_file_size as u64;
+
+    let mut sections = vec![Section {
+        name: ".text",
+        sh_type: SHT_PROGBITS,
+        flags: u64::from(SHF_ALLOC | SHF_EXECINSTR),
+        address: program.instruction_range.start,
+        offset: code_offset + (program.instruction_range.start - code.address),
+        size: program.instruction_range.end - program.instruction_range.start,
+        entry_size: 0,
+    }];
+
+    let mut extra = Vec::new();
+    if let Some(comment) = &program.comment {
+        sections.push(Section {
+            name: ".comment",
+            sh_type: SHT_PROGBITS,
+            flags: u64::from(SHF_MERGE | SHF_STRINGS),
+            address: 0,
+            offset: data_end,
+            size: comment.len() as u64,
+            entry_size: 1,
+        });
+        extra.extend(comment.as_bytes());
+    }
+
+    let mut shstrtab = vec![0];
+    let mut names = Vec::new();
+    for name in sections.iter().map(|s| s.name).chain(Some(".shstrtab")) {
+        names.push(shstrtab.len() as u32);
+        shstrtab.extend(name.as_bytes());
+        shstrtab.push(0);
+    }
+
+    sections.push(Section {
+        name: ".shstrtab",
+        sh_type: SHT_STRTAB,
+        flags: 0,
+        address: 0,
+        offset: data_end + extra.len() as u64,
+        size: shstrtab.len() as u64,
+        entry_size: 0,
+    });
+    extra.extend(&shstrtab);
+
+    let shoff = (data_end + extra.len() as u64 + 7) & !7;
+    let shnum = sections.len() as u16 + 1;
+
+    let mut elf = Vec::new();
+
+    // ELF header
+    elf.extend(ELFMAG);
+    elf.extend(&[ELFCLASS64, ELFDATA2LSB, EV_CURRENT]);
+    elf.resize(16, 0);
+    elf.extend(&ET_EXEC.to_le_bytes());
+    elf.extend(&EM_RISCV.to_le_bytes());
+    elf.extend(&u32::from(EV_CURRENT).to_le_bytes());
+    elf.extend(&program.entry_address.to_le_bytes());
+    elf.extend(&EHDR_SIZE.to_le_bytes());
+    elf.extend(&shoff.to_le_bytes());
+    elf.extend(&0u32.to_le_bytes());
+    elf.extend(&(EHDR_SIZE as u16).to_le_bytes());
+    elf.extend(&(PHDR_SIZE as u16).to_le_bytes());
+    elf.extend(&2u16.to_le_bytes());
+    elf.extend(&(SHDR_SIZE as u16).to_le_bytes());
+    elf.extend(&shnum.to_le_bytes());
+    elf.extend(&(shnum - 1).to_le_bytes());
+
+    // program headers
+    let segments = [
+        (
+            PF_R | PF_X,
+            code_offset,
+            code.address,
+            code.content.len(),
+            code.content.len(),
+        ),
+        (
+            PF_R | PF_W,
+            data_offset,
+            data.address,
+            program.data_file_size,
+            data.content.len(),
+        ),
+    ];
+    for (flags, offset, address, file_size, memory_size) in segments.iter() {
+        elf.extend(&PT_LOAD.to_le_bytes());
+        elf.extend(&flags.to_le_bytes());
+        elf.extend(&offset.to_le_bytes());
+        elf.extend(&address.to_le_bytes());
+        elf.extend(&address.to_le_bytes());
+        elf.extend(&(*file_size as u64).to_le_bytes());
+        elf.extend(&(*memory_size as u64).to_le_bytes());
+        elf.extend(&PAGE_SIZE.to_le_bytes());
+    }
+
+    elf.resize(code_offset as usize, 0);
+    elf.extend(&code.content);
+    elf.resize(data_offset as usize, 0);
+    elf.extend(&data.content[..program.data_file_size]);
+    elf.extend(&extra);
+    elf.resize(shoff as usize, 0);
+
+    // section headers, starting with the null section
+    elf.resize(elf.len() + SHDR_SIZE as usize, 0);
+    for (section, name) in sections.iter().zip(names) {
+        elf.extend(&name.to_le_bytes());
+        elf.extend(&section.sh_type.to_le_bytes());
+        elf.extend(&section.flags.to_le_bytes());
+        elf.extend(&section.address.to_le_bytes());
+        elf.extend(&section.offset.to_le_bytes());
+        elf.extend(&section.size.to_le_bytes());
+        elf.extend(&0u32.to_le_bytes());
+        elf.extend(&0u32.to_le_bytes());
+        elf.extend(&1u64.to_le_bytes());
+        elf.extend(&section.entry_size.to_le_bytes());
+    }
+
+    Ok(elf)
+}
+
+struct Section {
+    name: &'static str,
+    sh_type: u32,
+    flags: u64,
+    address: u64,
+    offset: u64,
+    size: u64,
+    entry_size: u64,
+}
+
+/// The first offset from `offset` on which is congruent to `address` modulo the page size.
+fn congruent_offset(offset: u64, address: u64) -> u64 {
+    offset + (address.wrapping_sub(offset) % PAGE_SIZE)
+}
+
+/// Reject programs which the loader would not accept.
+fn check_program(program: &Program) -> Result<(), RiscuError> {
+    let code = program.code.address..(program.code.address + program.code.content.len() as u64);
+    let data = program.data.address..(program.data.address + program.data.content.len() as u64);
+
+    if code.start < data.end && data.start < code.end {
+        return Err(RiscuError::InvalidRiscu("code and data segments overlap"));
+    }
+
+    if !code.contains(&program.entry_address) {
+        return Err(RiscuError::InvalidRiscu(
+            "entry address not in code segment",
+        ));
+    }
+
+    if program.instruction_range.start < code.start || program.instruction_range.end > code.end {
+        return Err(RiscuError::InvalidRiscu(
+            "instructions not within code segment",
+        ));
+    }
+
+    if program.data_file_size > program.data.content.len() {
+        return Err(RiscuError::InvalidRiscu(
+            "initialized data exceeds data segment",
+        ));
+    }
+
+    Ok(())
+}
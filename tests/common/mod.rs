@@ -0,0 +1,398 @@
+//! Builder for small ELF64 files used as loader test fixtures.
+
+#![allow(dead_code)]
+
+use std::{convert::TryInto, fs, ops::Range, path::PathBuf};
+use tempfile::TempDir;
+
+pub const PF_X: u32 = 1;
+pub const PF_W: u32 = 2;
+pub const PF_R: u32 = 4;
+
+pub const SHT_PROGBITS: u32 = 1;
+pub const SHT_SYMTAB: u32 = 2;
+pub const SHT_STRTAB: u32 = 3;
+pub const SHF_WRITE: u64 = 1;
+pub const SHF_ALLOC: u64 = 2;
+pub const SHF_EXECINSTR: u64 = 4;
+
+const EHDR_SIZE: usize = 64;
+const PHDR_SIZE: usize = 56;
+const SHDR_SIZE: usize = 64;
+const SYM_SIZE: usize = 24;
+
+/// Instructions [`ElfBuilder::large_riscu`] cycles through, covering every RISC-U format.
+pub const INSTRUCTION_MIX: [u32; 8] = [
+    0x00150513, // addi a0,a0,1
+    0x0007b783, // ld a5,0(a5)
+    0x00f6b423, // sd a5,8(a3)
+    0x00c58633, // add a2,a1,a2
+    0x02b50533, // mul a0,a0,a1
+    0xfe050ae3, // beq a0,zero,-12
+    0x000017b7, // lui a5,0x1
+    0x00000073, // ecall
+];
+
+pub struct Segment {
+    pub vaddr: u64,
+    pub flags: u32,
+    pub content: Vec<u8>,
+    pub memsz: u64,
+}
+
+pub struct Section {
+    pub name: String,
+    pub sh_type: u32,
+    pub flags: u64,
+    pub addr: u64,
+    pub size: u64,
+    /// Contents of sections which are not part of a segment.
+    pub content: Option<Vec<u8>>,
+    pub link: u32,
+    pub entsize: u64,
+}
+
+pub struct ElfBuilder {
+    pub entry: u64,
+    pub segments: Vec<Segment>,
+    pub sections: Vec<Section>,
+}
+
+impl ElfBuilder {
+    pub fn new(entry: u64) -> Self {
+        Self {
+            entry,
+            segments: Vec::new(),
+            sections: Vec::new(),
+        }
+    }
+
+    /// A minimal selfie-like binary: an R+X code segment and an R+W data segment.
+    pub fn riscu(code: &[u32], data: &[u64]) -> Self {
+        Self::new(0x10000)
+            .segment(0x10000, PF_R | PF_X, words(code))
+            .segment(
+                0x20000,
+                PF_R | PF_W,
+                data.iter().flat_map(|w| w.to_le_bytes()).collect(),
+            )
+    }
+
+    /// A selfie-like binary with `instructions` instructions of [`INSTRUCTION_MIX`] and as many
+    /// words of data, to measure the throughput of the loader.
+    pub fn large_riscu(instructions: usize) -> Self {
+        let code: Vec<u32> = INSTRUCTION_MIX
+            .iter()
+            .copied()
+            .cycle()
+            .take(instructions)
+            .collect();
+        let data: Vec<u64> = (0..instructions as u64).collect();
+
+        // place the data segment on the first page behind the code
+        let data_address = (0x10000 + 4 * instructions as u64 + 0xfff) & !0xfff;
+
+        Self::new(0x10000)
+            .segment(0x10000, PF_R | PF_X, words(&code))
+            .segment(
+                data_address,
+                PF_R | PF_W,
+                data.iter().flat_map(|w| w.to_le_bytes()).collect(),
+            )
+    }
+
+    pub fn segment(mut self, vaddr: u64, flags: u32, content: Vec<u8>) -> Self {
+        let memsz = content.len() as u64;
+
+        self.segments.push(Segment {
+            vaddr,
+            flags,
+            content,
+            memsz,
+        });
+        self
+    }
+
+    /// Set the memory size of the last added segment.
+    pub fn memsz(mut self, memsz: u64) -> Self {
+        self.segments.last_mut().unwrap().memsz = memsz;
+        self
+    }
+
+    /// Add a section describing `size` bytes of segment memory at `addr`.
+    pub fn section(mut self, name: &str, sh_type: u32, flags: u64, addr: u64, size: u64) -> Self {
+        self.sections.push(Section {
+            name: name.to_string(),
+            sh_type,
+            flags,
+            addr,
+            size,
+            content: None,
+            link: 0,
+            entsize: 0,
+        });
+        self
+    }
+
+    /// Add a non-allocated section with its own contents.
+    pub fn extra_section(mut self, name: &str, sh_type: u32, content: Vec<u8>) -> Self {
+        self.sections.push(Section {
+            name: name.to_string(),
+            sh_type,
+            flags: 0,
+            addr: 0,
+            size: content.len() as u64,
+            content: Some(content),
+            link: 0,
+            entsize: 0,
+        });
+        self
+    }
+
+    /// Add a `.symtab` with the symbols `(name, value, st_info)` and its `.strtab`.
+    pub fn symbols(self, symbols: &[(&str, u64, u8)]) -> Self {
+        let mut strtab = vec![0u8];
+        let mut symtab = vec![0u8; SYM_SIZE];
+
+        for (name, value, info) in symbols {
+            symtab.extend(&(strtab.len() as u32).to_le_bytes());
+            symtab.push(*info);
+            symtab.push(0);
+            symtab.extend(&0u16.to_le_bytes());
+            symtab.extend(&value.to_le_bytes());
+            symtab.extend(&0u64.to_le_bytes());
+
+            strtab.extend(name.as_bytes());
+            strtab.push(0);
+        }
+
+        // the null section precedes all others
+        let strtab_index = self.sections.len() as u32 + 1;
+        let mut builder = self
+            .extra_section(".strtab", SHT_STRTAB, strtab)
+            .extra_section(".symtab", SHT_SYMTAB, symtab);
+        let section = builder.sections.last_mut().unwrap();
+        section.link = strtab_index;
+        section.entsize = SYM_SIZE as u64;
+
+        builder
+    }
+
+    pub fn build(&self) -> Vec<u8> {
+        let phoff = EHDR_SIZE;
+        let mut offset = phoff + PHDR_SIZE * self.segments.len();
+
+        let mut segment_offsets = Vec::new();
+        for segment in &self.segments {
+            segment_offsets.push(offset);
+            offset += segment.content.len();
+        }
+
+        let mut shstrtab = vec![0u8];
+        let mut name_offsets = Vec::new();
+        for section in &self.sections {
+            name_offsets.push(shstrtab.len());
+            shstrtab.extend(section.name.as_bytes());
+            shstrtab.push(0);
+        }
+        let shstrtab_name = shstrtab.len();
+        shstrtab.extend(b".shstrtab\0");
+
+        let mut section_offsets = Vec::new();
+        let mut extra = Vec::new();
+        for section in &self.sections {
+            match &section.content {
+                Some(content) => {
+                    section_offsets.push(offset + extra.len());
+                    extra.extend(content);
+                }
+                None => {
+                    let (idx, segment) = self
+                        .segments
+                        .iter()
+                        .enumerate()
+                        .find(|(_, s)| {
+                            s.vaddr <= section.addr && section.addr < s.vaddr + s.memsz.max(1)
+                        })
+                        .expect("section has to lie within a segment");
+
+                    section_offsets
+                        .push(segment_offsets[idx] + (section.addr - segment.vaddr) as usize);
+                }
+            }
+        }
+        let shstrtab_offset = offset + extra.len();
+        let shoff = shstrtab_offset + shstrtab.len();
+        let shnum = if self.sections.is_empty() {
+            0
+        } else {
+            self.sections.len() + 2
+        };
+
+        let mut elf = Vec::new();
+
+        // ELF header
+        elf.extend(&[0x7f, b'E', b'L', b'F', 2, 1, 1, 0]);
+        elf.extend(&[0; 8]);
+        elf.extend(&2u16.to_le_bytes()); // ET_EXEC
+        elf.extend(&243u16.to_le_bytes()); // EM_RISCV
+        elf.extend(&1u32.to_le_bytes());
+        elf.extend(&self.entry.to_le_bytes());
+        elf.extend(&(phoff as u64).to_le_bytes());
+        elf.extend(&(if shnum == 0 { 0 } else { shoff as u64 }).to_le_bytes());
+        elf.extend(&0u32.to_le_bytes());
+        elf.extend(&(EHDR_SIZE as u16).to_le_bytes());
+        elf.extend(&(PHDR_SIZE as u16).to_le_bytes());
+        elf.extend(&(self.segments.len() as u16).to_le_bytes());
+        elf.extend(&(SHDR_SIZE as u16).to_le_bytes());
+        elf.extend(&(shnum as u16).to_le_bytes());
+        elf.extend(&(if shnum == 0 { 0 } else { shnum as u16 - 1 }).to_le_bytes());
+
+        // program headers
+        for (segment, offset) in self.segments.iter().zip(&segment_offsets) {
+            elf.extend(&1u32.to_le_bytes()); // PT_LOAD
+            elf.extend(&segment.flags.to_le_bytes());
+            elf.extend(&(*offset as u64).to_le_bytes());
+            elf.extend(&segment.vaddr.to_le_bytes());
+            elf.extend(&segment.vaddr.to_le_bytes());
+            elf.extend(&(segment.content.len() as u64).to_le_bytes());
+            elf.extend(&segment.memsz.to_le_bytes());
+            elf.extend(&8u64.to_le_bytes());
+        }
+
+        for segment in &self.segments {
+            elf.extend(&segment.content);
+        }
+        elf.extend(&extra);
+        elf.extend(&shstrtab);
+
+        // section headers
+        if shnum > 0 {
+            elf.extend(&[0; SHDR_SIZE]);
+
+            for ((section, name), offset) in self
+                .sections
+                .iter()
+                .zip(&name_offsets)
+                .zip(&section_offsets)
+            {
+                section_header(&mut elf, *name, *offset, section);
+            }
+
+            let shstrtab_section = Section {
+                name: ".shstrtab".to_string(),
+                sh_type: SHT_STRTAB,
+                flags: 0,
+                addr: 0,
+                size: shstrtab.len() as u64,
+                content: None,
+                link: 0,
+                entsize: 0,
+            };
+            section_header(&mut elf, shstrtab_name, shstrtab_offset, &shstrtab_section);
+        }
+
+        elf
+    }
+
+    /// Build the file with big-endian headers. The instructions of executable segments, as
+    /// declared by their executable section or else the whole segment, are swapped as 32-bit
+    /// words and the contents of writable segments as 64-bit words. Everything else, such as
+    /// read-only data and extra sections, is left as it is.
+    pub fn build_big_endian(&self) -> Vec<u8> {
+        let mut elf = self.build();
+        let shoff = u64::from_le_bytes(elf[40..48].try_into().unwrap()) as usize;
+        let shnum = u16::from_le_bytes([elf[60], elf[61]]) as usize;
+
+        elf[5] = 2; // ELFDATA2MSB
+        swap_fields(
+            &mut elf[16..EHDR_SIZE],
+            &[2, 2, 4, 8, 8, 8, 4, 2, 2, 2, 2, 2, 2],
+        );
+
+        let mut offset = EHDR_SIZE + PHDR_SIZE * self.segments.len();
+        for (idx, segment) in self.segments.iter().enumerate() {
+            let phdr = EHDR_SIZE + idx * PHDR_SIZE;
+            swap_fields(&mut elf[phdr..phdr + PHDR_SIZE], &[4, 4, 8, 8, 8, 8, 8, 8]);
+
+            let end = offset + segment.content.len();
+            let (swapped, word_size) = if segment.flags & PF_X != 0 {
+                (self.instruction_offsets(segment), 4)
+            } else if segment.flags & PF_W != 0 {
+                (0..segment.content.len(), 8)
+            } else {
+                (0..0, 8)
+            };
+            let swapped = &mut elf[offset + swapped.start..offset + swapped.end];
+            for word in swapped.chunks_exact_mut(word_size) {
+                word.reverse();
+            }
+            offset = end;
+        }
+
+        for idx in 0..shnum {
+            let shdr = shoff + idx * SHDR_SIZE;
+            swap_fields(
+                &mut elf[shdr..shdr + SHDR_SIZE],
+                &[4, 4, 8, 8, 8, 8, 4, 4, 8, 8],
+            );
+        }
+
+        elf
+    }
+
+    /// Offsets of the instructions within an executable segment: those of its executable section
+    /// or else the whole segment.
+    fn instruction_offsets(&self, segment: &Segment) -> Range<usize> {
+        let size = segment.content.len();
+
+        self.sections
+            .iter()
+            .find(|s| {
+                s.flags & SHF_EXECINSTR != 0
+                    && segment.vaddr <= s.addr
+                    && s.addr < segment.vaddr + size as u64
+            })
+            .map(|s| {
+                let start = (s.addr - segment.vaddr) as usize;
+                start..start.saturating_add(s.size as usize).min(size)
+            })
+            .unwrap_or(0..size)
+    }
+
+    /// Write the ELF file into `dir` and return its path.
+    pub fn write(&self, dir: &TempDir) -> PathBuf {
+        let path = dir.path().join("fixture.elf");
+
+        fs::write(&path, self.build()).unwrap();
+
+        path
+    }
+}
+
+fn section_header(elf: &mut Vec<u8>, name: usize, offset: usize, section: &Section) {
+    elf.extend(&(name as u32).to_le_bytes());
+    elf.extend(&section.sh_type.to_le_bytes());
+    elf.extend(&section.flags.to_le_bytes());
+    elf.extend(&section.addr.to_le_bytes());
+    elf.extend(&(offset as u64).to_le_bytes());
+    elf.extend(&section.size.to_le_bytes());
+    elf.extend(&section.link.to_le_bytes());
+    elf.extend(&0u32.to_le_bytes());
+    elf.extend(&1u64.to_le_bytes());
+    elf.extend(&section.entsize.to_le_bytes());
+}
+
+/// Reverse the byte order of consecutive fields of the given sizes.
+fn swap_fields(bytes: &mut [u8], sizes: &[usize]) {
+    let mut start = 0;
+
+    for size in sizes {
+        bytes[start..start + size].reverse();
+        start += size;
+    }
+}
+
+pub fn words(code: &[u32]) -> Vec<u8> {
+    code.iter().flat_map(|w| w.to_le_bytes()).collect()
+}
@@ -0,0 +1,1032 @@
+mod common;
+
+use common::*;
+use riscu::{
+    goblin::elf::Elf, load_and_decode_object_file_from_bytes, load_and_decode_object_file_from_elf,
+    load_and_decode_object_file_relocated, load_and_decode_object_file_with_warnings,
+    load_and_index_object_file, load_metadata, load_object_file, load_object_file_from_bytes,
+    load_object_file_from_elf, load_object_file_relaxed, load_object_file_view,
+    load_object_file_with_strictness, load_sections, load_symbols, write_object_file,
+    write_object_file_to_vec, DataLayout, DecodedProgram, ElfMetadata, Instruction, LoaderWarning,
+    Program, ProgramSegment, Register, RiscuError, SectionInfo, SegmentFlags, Strictness,
+};
+use tempfile::tempdir;
+
+const READ_EXECUTE: SegmentFlags = SegmentFlags {
+    read: true,
+    write: false,
+    execute: true,
+};
+
+const READ_WRITE: SegmentFlags = SegmentFlags {
+    read: true,
+    write: true,
+    execute: false,
+};
+
+#[test]
+fn split_rodata_from_code_segment() {
+    let mut code = words(&[
+        0x00100513, // addi a0,zero,1
+        0x00000073, // ecall
+    ]);
+    code.extend(b"hello\0\0\0");
+
+    let dir = tempdir().unwrap();
+    let fixture = |text_size| {
+        ElfBuilder::new(0x10000)
+            .segment(0x10000, PF_R | PF_X, code.clone())
+            .segment(0x20000, PF_R | PF_W, vec![0; 8])
+            .section(
+                ".text",
+                SHT_PROGBITS,
+                SHF_ALLOC | SHF_EXECINSTR,
+                0x10000,
+                text_size,
+            )
+            .section(".rodata", SHT_PROGBITS, SHF_ALLOC, 0x10008, 8)
+            .section(".data", SHT_PROGBITS, SHF_ALLOC | SHF_WRITE, 0x20000, 8)
+    };
+
+    let program = load_object_file(fixture(8).write(&dir)).unwrap();
+
+    assert_eq!(program.instruction_range, 0x10000..0x10008);
+    assert_eq!(program.instructions().len(), 8);
+
+    let rodata = program.rodata();
+    assert_eq!(rodata.address, 0x10008);
+    assert_eq!(rodata.content, b"hello\0\0\0");
+
+    assert_eq!(program.decode().unwrap().iter_instructions().count(), 2);
+
+    // a .text which runs past the code segment leaves no rodata to split off
+    assert!(matches!(
+        load_object_file(fixture(24).write(&dir)),
+        Err(RiscuError::InvalidRiscu(
+            "instructions not within code segment"
+        ))
+    ));
+}
+
+#[test]
+fn content_hash_is_stable() {
+    let fixture = ElfBuilder::riscu(&[0x00100513, 0x00000073], &[42, 7]);
+
+    let hash = |fixture: &ElfBuilder| {
+        let dir = tempdir().unwrap();
+
+        load_object_file(fixture.write(&dir))
+            .unwrap()
+            .decode()
+            .unwrap()
+            .content_hash()
+    };
+
+    assert_eq!(hash(&fixture), hash(&fixture));
+    assert_ne!(
+        hash(&fixture),
+        hash(&ElfBuilder::riscu(&[0x00100513, 0x00000073], &[42, 8]))
+    );
+}
+
+#[test]
+fn strictness_of_segment_checks() {
+    let dir = tempdir().unwrap();
+    let load = |fixture: ElfBuilder, strictness| {
+        load_object_file_with_strictness(fixture.write(&dir), strictness).is_ok()
+    };
+
+    // an additional read-only segment
+    let fixture =
+        || ElfBuilder::riscu(&[0x00000073], &[0]).segment(0x30000, PF_R, b"rodata\0\0".to_vec());
+    assert!(!load(fixture(), Strictness::Strict));
+    assert!(load(fixture(), Strictness::Riscu));
+    assert!(load(fixture(), Strictness::Lenient));
+
+    // a writable code segment
+    let fixture = || {
+        ElfBuilder::new(0x10000)
+            .segment(0x10000, PF_R | PF_W | PF_X, words(&[0x00000073]))
+            .segment(0x20000, PF_R | PF_W, vec![0; 8])
+    };
+    assert!(!load(fixture(), Strictness::Strict));
+    assert!(!load(fixture(), Strictness::Riscu));
+    assert!(load(fixture(), Strictness::Lenient));
+
+    let program =
+        load_object_file_with_strictness(fixture().write(&dir), Strictness::Lenient).unwrap();
+    assert!(program.code_flags.write);
+    assert_eq!(program.data_flags, READ_WRITE);
+}
+
+#[test]
+fn load_with_warnings() {
+    let dir = tempdir().unwrap();
+
+    let path = ElfBuilder::riscu(&[0x00100513, 0x00000073], &[0]).write(&dir);
+    let (_, warnings) = load_and_decode_object_file_with_warnings(path).unwrap();
+    assert_eq!(warnings, vec![]);
+
+    let path = ElfBuilder::riscu(&[0x00100513, 0x00000073, 0, 0], &[0]).write(&dir);
+    let (program, warnings) = load_and_decode_object_file_with_warnings(path).unwrap();
+    assert_eq!(warnings, vec![LoaderWarning::TrailingPadding(8)]);
+    assert_eq!(program.code.content.len(), 16);
+    assert_eq!(program.ecall_sites(), vec![0x10004]);
+    assert!(program
+        .summary()
+        .starts_with("instructions: 2 (0 compressed)\n"));
+
+    // compressed instructions only fill half-words
+    let compressed = |code: &[u8]| {
+        ElfBuilder::new(0x10000)
+            .segment(0x10000, PF_R | PF_X, code.to_vec())
+            .segment(0x20000, PF_R | PF_W, vec![0; 8])
+            .write(&dir)
+    };
+
+    let c_li = [0x05, 0x45]; // c.li a0,1
+    let ecall = [0x73, 0x00, 0x00, 0x00];
+    let path = compressed(&[&c_li[..], &ecall[..]].concat());
+    let (program, warnings) = load_and_decode_object_file_with_warnings(path).unwrap();
+    assert_eq!(warnings, vec![]);
+    assert_eq!(program.code.content.len(), 6);
+    assert_eq!(program.ecall_sites(), vec![0x10002]);
+
+    // the first half of a 32-bit instruction at the end
+    let path = compressed(&[&ecall[..], &ecall[..2]].concat());
+    let (_, warnings) = load_and_decode_object_file_with_warnings(path).unwrap();
+    assert_eq!(warnings, vec![LoaderWarning::TrailingPadding(2)]);
+
+    let path = compressed(&[&c_li[..], &ecall[..], &[0]].concat());
+    assert!(matches!(
+        load_and_decode_object_file_with_warnings(path),
+        Err(RiscuError::InvalidRiscu(
+            "code segment not a multiple of 2 bytes"
+        ))
+    ));
+}
+
+#[test]
+fn data_layout_with_bss() {
+    let dir = tempdir().unwrap();
+    let path = ElfBuilder::riscu(&[0x00000073], &[42])
+        .memsz(0x20)
+        .write(&dir);
+
+    let program = load_object_file(path).unwrap().decode().unwrap();
+
+    assert_eq!(program.data.content, vec![42, 0, 0, 0]);
+    assert_eq!(
+        program.data_layout(),
+        DataLayout {
+            initialized: 0x20000..0x20008,
+            bss: 0x20008..0x20020,
+        }
+    );
+}
+
+#[test]
+fn reject_memory_smaller_than_file() {
+    let dir = tempdir().unwrap();
+    let path = ElfBuilder::riscu(&[0x00000073], &[42, 43])
+        .memsz(8)
+        .write(&dir);
+
+    assert!(matches!(
+        load_object_file(&path),
+        Err(RiscuError::InvalidRiscu(
+            "segment memory size smaller than file size"
+        ))
+    ));
+    assert!(load_metadata(&path).is_err());
+}
+
+#[test]
+fn split_single_rwx_segment() {
+    let mut memory = words(&[
+        0x00100513, // addi a0,zero,1
+        0x00000073, // ecall
+    ]);
+    memory.extend(&42u64.to_le_bytes());
+
+    let dir = tempdir().unwrap();
+    let fixture = || {
+        ElfBuilder::new(0x10000)
+            .segment(0x10000, PF_R | PF_W | PF_X, memory.clone())
+            .memsz(0x18)
+            .section(".text", SHT_PROGBITS, SHF_ALLOC | SHF_EXECINSTR, 0x10000, 8)
+            .section(".data", SHT_PROGBITS, SHF_ALLOC | SHF_WRITE, 0x10008, 8)
+    };
+
+    let program =
+        load_object_file_with_strictness(fixture().write(&dir), Strictness::Lenient).unwrap();
+
+    assert_eq!(program.code.address, 0x10000);
+    assert_eq!(program.instructions().len(), 8);
+    assert_eq!(program.data.address, 0x10008);
+    assert_eq!(program.data_file_size, 8);
+
+    let all = SegmentFlags {
+        read: true,
+        write: true,
+        execute: true,
+    };
+    assert_eq!(program.code_flags, all);
+    assert_eq!(program.data_flags, all);
+
+    let program = program.decode().unwrap();
+    assert_eq!(program.data.content, vec![42, 0]);
+    assert_eq!(program.iter_instructions().count(), 2);
+
+    assert!(load_object_file(fixture().write(&dir)).is_err());
+    assert!(load_object_file_with_strictness(fixture().write(&dir), Strictness::Strict).is_err());
+}
+
+#[test]
+fn reject_overlapping_segments() {
+    let dir = tempdir().unwrap();
+    let path = ElfBuilder::new(0x10000)
+        .segment(0x10000, PF_R | PF_X, words(&[0x00000073, 0x00000073]))
+        .segment(0x10004, PF_R | PF_W, vec![0; 8])
+        .write(&dir);
+
+    assert!(matches!(
+        load_object_file(path),
+        Err(RiscuError::InvalidRiscu("code and data segments overlap"))
+    ));
+
+    // only the zero-initialized part of the data segment reaches into the code segment
+    let path = ElfBuilder::new(0x10008)
+        .segment(0x10008, PF_R | PF_X, words(&[0x00000073, 0x00000073]))
+        .segment(0x10000, PF_R | PF_W, vec![0; 8])
+        .memsz(16)
+        .write(&dir);
+
+    assert!(matches!(
+        load_object_file(path),
+        Err(RiscuError::InvalidRiscu("code and data segments overlap"))
+    ));
+}
+
+#[test]
+fn load_relocated() {
+    let dir = tempdir().unwrap();
+    let path = ElfBuilder::riscu(&[0x00000073], &[0]).write(&dir);
+
+    let program = load_object_file(&path).unwrap().decode().unwrap();
+    let relocated = load_and_decode_object_file_relocated(&path, 0x40000).unwrap();
+
+    assert_eq!(program.entry_address, 0x10000);
+    assert_eq!(relocated.entry_address, 0x50000);
+    assert_eq!(relocated.code_range(), 0x50000..0x50004);
+    assert_eq!(relocated.data.address, program.data.address + 0x40000);
+    assert_eq!(relocated.code.content, program.code.content);
+}
+
+#[test]
+fn load_metadata_without_segments() {
+    let dir = tempdir().unwrap();
+    let path = ElfBuilder::riscu(&[0x00100513, 0x00000073], &[42])
+        .memsz(0x20)
+        .write(&dir);
+
+    let program = load_object_file(&path).unwrap();
+    let metadata = load_metadata(&path).unwrap();
+
+    assert_eq!(
+        metadata,
+        ElfMetadata {
+            entry_address: program.entry_address,
+            code_length: program.code.content.len(),
+            data_length: program.data.content.len(),
+        }
+    );
+    assert_eq!(metadata.data_length, 0x20);
+}
+
+#[test]
+fn producer_comment() {
+    let dir = tempdir().unwrap();
+    let builder = ElfBuilder::riscu(&[0x00000073], &[0]);
+
+    let path = builder.write(&dir);
+    assert_eq!(load_object_file(&path).unwrap().producer_comment(), None);
+
+    let path = builder
+        .extra_section(
+            ".comment",
+            SHT_PROGBITS,
+            b"GCC: (GNU) 10.2.0\0Linker: LLD 12.0.0\0".to_vec(),
+        )
+        .write(&dir);
+    let program = load_object_file(&path).unwrap();
+
+    assert_eq!(
+        program.producer_comment().as_deref(),
+        Some("GCC: (GNU) 10.2.0")
+    );
+    assert_eq!(program.instructions(), &[0x73, 0, 0, 0]);
+}
+
+#[test]
+fn load_symbols_of_functions_and_objects() {
+    const STT_OBJECT: u8 = 1;
+    const STT_FUNC: u8 = 2;
+    const STT_FILE: u8 = 4;
+    const STB_GLOBAL: u8 = 1 << 4;
+
+    let dir = tempdir().unwrap();
+    let builder = ElfBuilder::riscu(&[0x00000073, 0x00008067], &[0]);
+
+    let path = builder.write(&dir);
+    assert!(load_symbols(&path).unwrap().is_empty());
+
+    let path = builder
+        .symbols(&[
+            ("selfie.c", 0, STT_FILE),
+            ("_start", 0x10000, STB_GLOBAL | STT_FUNC),
+            ("exit", 0x10004, STT_FUNC),
+            ("", 0x10008, STT_FUNC),
+            ("bump_pointer", 0x20000, STB_GLOBAL | STT_OBJECT),
+        ])
+        .write(&dir);
+    let symbols = load_symbols(&path).unwrap();
+
+    assert_eq!(
+        symbols.into_iter().collect::<Vec<_>>(),
+        vec![
+            (0x10000, "_start".to_string()),
+            (0x10004, "exit".to_string()),
+            (0x20000, "bump_pointer".to_string()),
+        ]
+    );
+    assert!(load_object_file(&path).is_ok());
+}
+
+#[test]
+fn load_section_headers() {
+    let mut code = words(&[
+        0x00100513, // addi a0,zero,1
+        0x00000073, // ecall
+    ]);
+    code.extend(b"hello\0\0\0");
+
+    let dir = tempdir().unwrap();
+    let path = ElfBuilder::new(0x10000)
+        .segment(0x10000, PF_R | PF_X, code.clone())
+        .segment(0x20000, PF_R | PF_W, vec![42; 8])
+        .section(".text", SHT_PROGBITS, SHF_ALLOC | SHF_EXECINSTR, 0x10000, 8)
+        .section(".rodata", SHT_PROGBITS, SHF_ALLOC, 0x10008, 8)
+        .section(".data", SHT_PROGBITS, SHF_ALLOC | SHF_WRITE, 0x20000, 8)
+        .write(&dir);
+
+    let sections = load_sections(&path).unwrap();
+
+    let names = sections.iter().map(|s| s.name.as_str()).collect::<Vec<_>>();
+    assert_eq!(names, vec![".text", ".rodata", ".data", ".shstrtab"]);
+
+    let layout = sections
+        .iter()
+        .map(|s| (s.addr, s.size, s.flags))
+        .take(3)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        layout,
+        vec![
+            (0x10000, 8, SHF_ALLOC | SHF_EXECINSTR),
+            (0x10008, 8, SHF_ALLOC),
+            (0x20000, 8, SHF_ALLOC | SHF_WRITE),
+        ]
+    );
+
+    // the offsets point to the contents in the file
+    let raw = std::fs::read(&path).unwrap();
+    let content = |s: &SectionInfo| &raw[s.offset as usize..(s.offset + s.size) as usize];
+    assert_eq!(content(&sections[0]), &code[..8]);
+    assert_eq!(content(&sections[1]), b"hello\0\0\0");
+    assert_eq!(content(&sections[2]), [42; 8]);
+
+    // a file without section headers
+    let path = ElfBuilder::riscu(&[0x00000073], &[0]).write(&dir);
+    assert!(load_sections(path).unwrap().is_empty());
+}
+
+#[test]
+fn load_big_endian() {
+    let builder = ElfBuilder::riscu(
+        &[
+            0x00100513, // addi a0,zero,1
+            0x00000073, // ecall
+        ],
+        &[0x1122_3344_5566_7788, 42],
+    )
+    .extra_section(".comment", SHT_PROGBITS, b"GCC: (GNU) 10.2.0\0".to_vec());
+
+    let little = load_object_file_from_bytes(&builder.build()).unwrap();
+    let big = load_object_file_from_bytes(&builder.build_big_endian()).unwrap();
+
+    assert_eq!(big.code.content, little.code.content);
+    assert_eq!(big.data.content, little.data.content);
+    assert_eq!(big.instruction_range, little.instruction_range);
+    assert_eq!(big.entry_address, 0x10000);
+    assert_eq!(big.comment, little.comment);
+
+    let decoded = load_and_decode_object_file_from_bytes(&builder.build_big_endian()).unwrap();
+    assert_eq!(decoded.data.content, vec![0x1122_3344_5566_7788, 42]);
+
+    // only the instructions are reordered, not the read-only data behind them
+    let mut code = words(&[0x00000073, 0x00000073]);
+    code.extend(b"hello\0\0\0");
+    let builder = ElfBuilder::new(0x10000)
+        .segment(0x10000, PF_R | PF_X, code)
+        .segment(0x20000, PF_R | PF_W, vec![0; 8])
+        .section(".text", SHT_PROGBITS, SHF_ALLOC | SHF_EXECINSTR, 0x10000, 8);
+
+    let image = builder.build_big_endian();
+    let big = load_object_file_from_bytes(&image).unwrap();
+    assert_eq!(big.instructions(), &words(&[0x00000073, 0x00000073])[..]);
+    assert_eq!(big.rodata().content, b"hello\0\0\0");
+
+    let view = load_object_file_view(&image).unwrap();
+    assert_same_decoding(&view.decode().unwrap(), &big.decode().unwrap());
+}
+
+#[test]
+fn load_large_binary() {
+    let instructions = 1 << 16;
+
+    let dir = tempdir().unwrap();
+    let path = ElfBuilder::large_riscu(instructions).write(&dir);
+
+    let program = load_object_file(&path).unwrap().decode().unwrap();
+
+    assert_eq!(program.iter_instructions().count(), instructions);
+    assert_eq!(program.data.content.len(), instructions);
+    assert_eq!(
+        program.iter_instructions().nth(INSTRUCTION_MIX.len() + 7),
+        Some(Instruction::new_ecall())
+    );
+}
+
+#[test]
+fn preserve_segment_addresses() {
+    let dir = tempdir().unwrap();
+    let path = ElfBuilder::new(0x400004)
+        .segment(
+            0x400000,
+            PF_R | PF_X,
+            words(&[
+                0x00100513, // addi a0,zero,1
+                0x00000073, // ecall
+            ]),
+        )
+        .segment(0x600000, PF_R | PF_W, vec![0; 8])
+        .write(&dir);
+
+    let program = load_object_file(&path).unwrap();
+    assert_eq!(program.code.address, 0x400000);
+    assert_eq!(program.data.address, 0x600000);
+    assert_eq!(program.entry_address, 0x400004);
+
+    let decoded = program.decode().unwrap();
+    assert_eq!(decoded.code.address, 0x400000);
+    assert_eq!(decoded.data.address, 0x600000);
+    assert_eq!(
+        decoded.iter_locations().collect::<Vec<_>>(),
+        vec![0x400000, 0x400004]
+    );
+}
+
+#[test]
+fn load_from_bytes() {
+    let builder = ElfBuilder::riscu(&[0x00100513, 0x00000073], &[42]);
+
+    let dir = tempdir().unwrap();
+    let from_file = load_object_file(builder.write(&dir)).unwrap();
+    let from_bytes = load_object_file_from_bytes(&builder.build()).unwrap();
+
+    assert_eq!(from_bytes.code.content, from_file.code.content);
+    assert_eq!(from_bytes.data.address, from_file.data.address);
+    assert_eq!(from_bytes.entry_address, from_file.entry_address);
+
+    let decoded = load_and_decode_object_file_from_bytes(&builder.build()).unwrap();
+    assert_eq!(decoded.data.content, vec![42]);
+
+    assert!(matches!(
+        load_object_file_from_bytes(b"not an elf"),
+        Err(RiscuError::InvalidElf(_))
+    ));
+}
+
+#[test]
+fn load_from_parsed_elf() {
+    let image = ElfBuilder::riscu(&[0x00100513, 0x00000073], &[42]).build();
+    let elf = Elf::parse(&image).unwrap();
+
+    let from_elf = load_object_file_from_elf(&image, &elf).unwrap();
+    let from_bytes = load_object_file_from_bytes(&image).unwrap();
+
+    assert_eq!(from_elf.code.content, from_bytes.code.content);
+    assert_eq!(from_elf.data.content, from_bytes.data.content);
+    assert_eq!(from_elf.instruction_range, from_bytes.instruction_range);
+    assert_eq!(from_elf.entry_address, from_bytes.entry_address);
+
+    let decoded = load_and_decode_object_file_from_elf(&image, &elf).unwrap();
+    assert_eq!(decoded.data.content, vec![42]);
+    assert_eq!(decoded.iter_instructions().count(), 2);
+
+    // a parsed file is still checked to be RISC-U
+    let image = ElfBuilder::new(0x10000)
+        .segment(0x10000, PF_R | PF_X, words(&[0x00000073]))
+        .build();
+    let elf = Elf::parse(&image).unwrap();
+    assert!(matches!(
+        load_object_file_from_elf(&image, &elf),
+        Err(RiscuError::InvalidRiscu(_))
+    ));
+}
+
+#[test]
+fn reject_segments_beyond_file() {
+    let image = ElfBuilder::riscu(&[0x00100513, 0x00000073], &[42]).build();
+    let dir = tempdir().unwrap();
+
+    // cut the file off within the data segment, as declared by its program header
+    let data_end = Elf::parse(&image).unwrap().program_headers[1]
+        .file_range()
+        .end;
+    let truncated = &image[..data_end - 4];
+
+    // a code segment at an offset which overflows when adding its size
+    let mut overflowing = image.clone();
+    overflowing[72..80].copy_from_slice(&(u64::MAX - 4).to_le_bytes());
+
+    for raw in [truncated, &overflowing[..]].iter() {
+        let path = dir.path().join("malformed.elf");
+        std::fs::write(&path, raw).unwrap();
+
+        let is_rejected = |result: Result<(), RiscuError>| {
+            matches!(
+                result,
+                Err(RiscuError::InvalidRiscu("segment range exceeds file size"))
+            )
+        };
+
+        assert!(is_rejected(load_object_file_from_bytes(raw).map(drop)));
+        assert!(is_rejected(load_object_file_view(raw).map(drop)));
+        assert!(is_rejected(load_object_file_relaxed(&path).map(drop)));
+        assert!(is_rejected(load_metadata(&path).map(drop)));
+        assert!(is_rejected(
+            load_and_decode_object_file_with_warnings(&path).map(drop)
+        ));
+    }
+
+    assert!(load_object_file_from_bytes(&image).is_ok());
+}
+
+#[test]
+fn reject_segments_beyond_memory() {
+    let dir = tempdir().unwrap();
+
+    // a data segment which ends beyond the address space, and one which would allocate 1 TiB
+    let overflowing = ElfBuilder::new(0x10000)
+        .segment(0x10000, PF_R | PF_X, words(&[0x00000073]))
+        .segment(u64::MAX - 3, PF_R | PF_W, vec![0; 8]);
+    let huge = ElfBuilder::riscu(&[0x00000073], &[0]).memsz(1 << 40);
+
+    for fixture in [overflowing, huge].iter() {
+        let path = fixture.write(&dir);
+        let raw = fixture.build();
+
+        let is_rejected = |result: Result<(), RiscuError>| {
+            matches!(
+                result,
+                Err(RiscuError::InvalidRiscu(
+                    "segment range exceeds memory size"
+                ))
+            )
+        };
+
+        assert!(is_rejected(load_object_file_from_bytes(&raw).map(drop)));
+        assert!(is_rejected(load_object_file_view(&raw).map(drop)));
+        assert!(is_rejected(load_object_file_relaxed(&path).map(drop)));
+        assert!(is_rejected(load_metadata(&path).map(drop)));
+        assert!(is_rejected(
+            load_and_decode_object_file_with_warnings(&path).map(drop)
+        ));
+    }
+
+    // segments which are each small, but would be merged across 8 GiB
+    let path = ElfBuilder::riscu(&[0x00000073], &[0])
+        .segment(0x2_0001_0000, PF_R, b"rodata\0\0".to_vec())
+        .write(&dir);
+    assert!(load_object_file(&path).is_ok());
+    assert!(matches!(
+        load_object_file_relaxed(&path),
+        Err(RiscuError::InvalidRiscu(
+            "segment range exceeds memory size"
+        ))
+    ));
+}
+
+#[test]
+fn reject_instructions_beyond_code_segment() {
+    let dir = tempdir().unwrap();
+    let is_rejected = |result: Result<(), RiscuError>| {
+        matches!(
+            result,
+            Err(RiscuError::InvalidRiscu(
+                "instructions not within code segment"
+            ))
+        )
+    };
+
+    let text = |address, size| {
+        ElfBuilder::riscu(&[0x00100513, 0x00000073], &[0]).section(
+            ".text",
+            SHT_PROGBITS,
+            SHF_ALLOC | SHF_EXECINSTR,
+            address,
+            size,
+        )
+    };
+
+    // a .text which runs past the code segment, and one which ends beyond the address space
+    for fixture in [text(0x10000, 16), text(0x10004, u64::MAX - 3)].iter() {
+        let path = fixture.write(&dir);
+
+        assert!(is_rejected(
+            load_object_file_from_bytes(&fixture.build()).map(drop)
+        ));
+        assert!(is_rejected(
+            load_object_file_view(&fixture.build()).map(drop)
+        ));
+        assert!(is_rejected(load_object_file_relaxed(&path).map(drop)));
+        assert!(is_rejected(load_metadata(&path).map(drop)));
+    }
+    assert!(load_object_file_relaxed(text(0x10000, 8).write(&dir)).is_ok());
+
+    // a .text which runs into the zero-initialized part of a single RWX segment
+    let path = ElfBuilder::new(0x10000)
+        .segment(0x10000, PF_R | PF_W | PF_X, words(&[0x00000073; 4]))
+        .memsz(0x20)
+        .section(
+            ".text",
+            SHT_PROGBITS,
+            SHF_ALLOC | SHF_EXECINSTR,
+            0x10000,
+            0x18,
+        )
+        .write(&dir);
+    assert!(is_rejected(
+        load_object_file_with_strictness(&path, Strictness::Lenient).map(drop)
+    ));
+}
+
+#[test]
+fn zero_fill_bss() {
+    let dir = tempdir().unwrap();
+    // neither the file part nor the memory size of the data segment are whole words, and the
+    // bytes behind it in the file must not leak into its .bss
+    let path = ElfBuilder::new(0x10000)
+        .segment(0x10000, PF_R | PF_X, words(&[0x00000073]))
+        .segment(0x20000, PF_R | PF_W, vec![42; 12])
+        .memsz(0x14)
+        .extra_section(".comment", SHT_PROGBITS, vec![0xff; 0x40])
+        .write(&dir);
+
+    let program = load_object_file(&path).unwrap();
+
+    assert_eq!(program.data_file_size, 12);
+    assert_eq!(program.data.content[..12], [42; 12]);
+    assert_eq!(program.data.content[12..], [0; 8]);
+
+    assert!(matches!(
+        program.decode(),
+        Err(RiscuError::InvalidRiscu(
+            "data segment not a multiple of 8 bytes"
+        ))
+    ));
+}
+
+#[test]
+fn view_without_copying() {
+    let builder = ElfBuilder::riscu(&[0x00100513, 0x00000073], &[42, 43]).memsz(0x40);
+    let image = builder.build();
+
+    let program = load_object_file_from_bytes(&image).unwrap();
+    let view = load_object_file_view(&image).unwrap();
+
+    assert_eq!(view.code.address, program.code.address);
+    assert_eq!(view.instructions(), program.instructions());
+    assert_eq!(view.data.address, program.data.address);
+    assert_eq!(
+        view.data.content,
+        &program.data.content[..program.data_file_size]
+    );
+    assert_eq!(view.data.memory_size, program.data.content.len());
+    assert_eq!(view.instruction_range, program.instruction_range);
+    assert_eq!(view.entry_address, program.entry_address);
+    assert_eq!(view.code.flags, program.code_flags);
+    assert_eq!(view.data.flags, READ_WRITE);
+
+    let image_range = image.as_ptr_range();
+    assert!(image_range.contains(&view.code.content.as_ptr()));
+    assert!(image_range.contains(&view.data.content.as_ptr()));
+
+    assert!(view.little_endian);
+    assert_same_decoding(&view.decode().unwrap(), &program.decode().unwrap());
+
+    // a big-endian file is viewed in its byte order and reordered when decoding
+    let big_endian = builder.build_big_endian();
+    let view = load_object_file_view(&big_endian).unwrap();
+
+    assert!(!view.little_endian);
+    assert_same_decoding(
+        &view.decode().unwrap(),
+        &load_and_decode_object_file_from_bytes(&big_endian).unwrap(),
+    );
+
+    // the last word of the file is completed by the zero-initialized part
+    let image = ElfBuilder::new(0x10000)
+        .segment(0x10000, PF_R | PF_X, words(&[0x00000073]))
+        .segment(0x20000, PF_R | PF_W, vec![1; 12])
+        .memsz(0x18)
+        .build();
+    let decoded = load_object_file_view(&image).unwrap().decode().unwrap();
+
+    assert_same_decoding(
+        &decoded,
+        &load_and_decode_object_file_from_bytes(&image).unwrap(),
+    );
+    assert_eq!(
+        decoded.data.content,
+        [0x0101_0101_0101_0101, 0x0101_0101, 0]
+    );
+}
+
+#[cfg(feature = "mmap")]
+#[test]
+fn load_mapped() {
+    use riscu::load_object_file_mmap;
+
+    let dir = tempdir().unwrap();
+    let path = ElfBuilder::riscu(&[0x00100513, 0x00000073], &[42, 43])
+        .memsz(0x40)
+        .write(&dir);
+
+    let program = load_object_file(&path).unwrap();
+    let mapped = load_object_file_mmap(&path).unwrap();
+    let view = mapped.view();
+
+    assert_eq!(view.instructions(), program.instructions());
+    assert_eq!(view.instruction_range, program.instruction_range);
+    assert_eq!(view.entry_address, program.entry_address);
+    assert_eq!(view.data.memory_size, program.data.content.len());
+    assert_same_decoding(&mapped.decode().unwrap(), &program.decode().unwrap());
+
+    assert!(matches!(
+        load_object_file_mmap(dir.path().join("missing.elf")),
+        Err(RiscuError::CouldNotReadFile(_))
+    ));
+}
+
+fn assert_same_decoding(actual: &DecodedProgram, expected: &DecodedProgram) {
+    assert_eq!(actual.code.address, expected.code.address);
+    assert_eq!(actual.code.content, expected.code.content);
+    assert_eq!(actual.data.address, expected.data.address);
+    assert_eq!(actual.data.content, expected.data.content);
+    assert_eq!(actual.data_file_size, expected.data_file_size);
+    assert_eq!(actual.entry_address, expected.entry_address);
+}
+
+#[test]
+fn load_relaxed() {
+    let text = words(&[
+        0x00100513, // addi a0,zero,1
+        0x00000073, // ecall
+    ]);
+
+    // a GCC-like layout: rodata, text and two data segments
+    let builder = ElfBuilder::new(0x11000)
+        .segment(0x10000, PF_R, b"rodata\0\0".to_vec())
+        .segment(0x11000, PF_R | PF_X, text.clone())
+        .segment(0x12000, PF_R | PF_W, vec![1; 8])
+        .segment(0x12010, PF_R | PF_W, vec![2; 8])
+        .memsz(0x18)
+        .section(".text", SHT_PROGBITS, SHF_ALLOC | SHF_EXECINSTR, 0x11000, 8);
+
+    let dir = tempdir().unwrap();
+    let path = builder.write(&dir);
+
+    // the default keeps only one code and one data segment
+    let selfie = load_object_file(&path).unwrap();
+    assert_eq!(selfie.code.address, 0x11000);
+    assert_eq!(selfie.data.content.len(), 8);
+    assert!(load_object_file_with_strictness(&path, Strictness::Strict).is_err());
+
+    let program = load_object_file_relaxed(&path).unwrap();
+
+    assert_eq!(program.code.address, 0x10000);
+    assert_eq!(program.code.content.len(), 0x1008);
+    assert_eq!(&program.code.content[..6], b"rodata");
+    assert_eq!(program.instructions(), &text[..]);
+    assert_eq!(program.entry_address, 0x11000);
+
+    assert_eq!(program.data.address, 0x12000);
+    assert_eq!(program.data.content.len(), 0x28);
+    assert_eq!(program.data.content[..8], [1; 8]);
+    assert_eq!(program.data.content[8..0x10], [0; 8]);
+    assert_eq!(program.data.content[0x10..0x18], [2; 8]);
+    assert_eq!(program.data_file_size, 0x18);
+
+    // read-only data does not make the code segment writable
+    assert_eq!(program.code_flags, READ_EXECUTE);
+    assert_eq!(program.data_flags, READ_WRITE);
+
+    // the entry has to be executable
+    let path = ElfBuilder {
+        entry: 0x10000,
+        ..builder
+    }
+    .write(&dir);
+    assert!(matches!(
+        load_object_file_relaxed(&path),
+        Err(RiscuError::InvalidRiscu(_))
+    ));
+}
+
+#[test]
+fn index_instructions_by_address() {
+    let mut code = words(&[0x00100513]); // addi a0,zero,1
+    code.extend(&[0xe1, 0x17, 0x01, 0x00]); // c.addi a5,-8; c.nop
+    code.extend(words(&[0x00000073])); // ecall
+
+    let dir = tempdir().unwrap();
+    let path = ElfBuilder::new(0x10000)
+        .segment(0x10000, PF_R | PF_X, code)
+        .segment(0x20000, PF_R | PF_W, vec![0; 8])
+        .write(&dir);
+
+    let index = load_and_index_object_file(path).unwrap();
+
+    assert_eq!(
+        index.keys().copied().collect::<Vec<_>>(),
+        vec![0x10000, 0x10004, 0x10006, 0x10008]
+    );
+    assert_eq!(
+        index[&0x10000],
+        Instruction::new_addi(Register::A0, Register::Zero, 1)
+    );
+    assert_eq!(index[&0x10008], Instruction::new_ecall());
+    assert_eq!(
+        index
+            .range(..=0x10005)
+            .next_back()
+            .map(|(address, _)| *address),
+        Some(0x10004)
+    );
+
+    let path = ElfBuilder::riscu(&[0x00100513, 0xffffffff], &[0]).write(&dir);
+    assert!(matches!(
+        load_and_index_object_file(path),
+        Err(RiscuError::DecodingError {
+            address: 0x10004,
+            ..
+        })
+    ));
+}
+
+#[test]
+fn validate_entry_address() {
+    let dir = tempdir().unwrap();
+    let load = |entry| {
+        let path = ElfBuilder {
+            entry,
+            ..ElfBuilder::riscu(&[0x00100513, 0x00000073], &[0])
+        }
+        .write(&dir);
+
+        load_object_file(&path).map(|program| program.entry_address)
+    };
+
+    assert_eq!(load(0x10004).unwrap(), 0x10004);
+
+    for entry in [0x20000, 0x10008, 0xfffc, 0].iter() {
+        assert!(matches!(
+            load(*entry),
+            Err(RiscuError::InvalidRiscu(
+                "entry address not in code segment"
+            ))
+        ));
+    }
+
+    assert!(matches!(
+        load(0x10002),
+        Err(RiscuError::InvalidRiscu(
+            "entry address not aligned to an instruction"
+        ))
+    ));
+}
+
+#[test]
+fn load_metadata_checks_like_loader() {
+    let dir = tempdir().unwrap();
+
+    let entry_in_data = ElfBuilder {
+        entry: 0x20000,
+        ..ElfBuilder::riscu(&[0x00000073], &[0])
+    };
+    let single_segment = ElfBuilder::new(0x10000).segment(0x10000, PF_R | PF_X, words(&[0x73]));
+
+    for builder in [entry_in_data, single_segment].iter() {
+        let path = builder.write(&dir);
+
+        assert!(load_object_file(&path).is_err());
+        assert!(matches!(
+            load_metadata(&path),
+            Err(RiscuError::InvalidRiscu(_))
+        ));
+    }
+}
+
+#[test]
+fn write_round_trip() {
+    let mut code = words(&[
+        0x00100513, // addi a0,zero,1
+        0x00000073, // ecall
+    ]);
+    code.extend(b"hello\0\0\0");
+
+    let program = Program {
+        code: ProgramSegment {
+            address: 0x10000,
+            content: code,
+        },
+        data: ProgramSegment {
+            address: 0x11000,
+            content: vec![1, 2, 3, 4, 5, 6, 7, 8, 0, 0, 0, 0, 0, 0, 0, 0],
+        },
+        instruction_range: 0x10000..0x10008,
+        data_file_size: 8,
+        entry_address: 0x10004,
+        comment: Some("riscu\0".to_string()),
+        code_flags: READ_EXECUTE,
+        data_flags: READ_WRITE,
+    };
+
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("written.elf");
+    write_object_file(&program, &path).unwrap();
+
+    let loaded = load_object_file_with_strictness(&path, Strictness::Strict).unwrap();
+
+    assert_eq!(loaded.code.address, program.code.address);
+    assert_eq!(loaded.code.content, program.code.content);
+    assert_eq!(loaded.data.address, program.data.address);
+    assert_eq!(loaded.data.content, program.data.content);
+    assert_eq!(loaded.instruction_range, program.instruction_range);
+    assert_eq!(loaded.data_file_size, program.data_file_size);
+    assert_eq!(loaded.entry_address, program.entry_address);
+    assert_eq!(loaded.comment, program.comment);
+    assert_eq!(loaded.code_flags, program.code_flags);
+    assert_eq!(loaded.data_flags, program.data_flags);
+    assert_eq!(loaded.producer_comment().as_deref(), Some("riscu"));
+
+    // writing the loaded program reproduces the file
+    assert_eq!(
+        write_object_file_to_vec(&loaded).unwrap(),
+        std::fs::read(&path).unwrap()
+    );
+
+    let invalid = Program {
+        entry_address: 0x11000,
+        ..program
+    };
+    assert!(matches!(
+        write_object_file(&invalid, &path),
+        Err(RiscuError::InvalidRiscu(
+            "entry address not in code segment"
+        ))
+    ));
+}
+
+#[test]
+fn write_loaded_selfie_binary() {
+    let dir = tempdir().unwrap();
+    let path = ElfBuilder::riscu(&[0x00100513, 0x00000073], &[42])
+        .memsz(0x20)
+        .write(&dir);
+
+    let program = load_object_file(&path).unwrap();
+    let image = write_object_file_to_vec(&program).unwrap();
+    let reloaded = load_object_file_from_bytes(&image).unwrap();
+
+    assert_eq!(reloaded.code.content, program.code.content);
+    assert_eq!(reloaded.data.content, program.data.content);
+    assert_eq!(reloaded.data_file_size, 8);
+    assert_eq!(
+        reloaded.decode().unwrap().iter_instructions().count(),
+        program.decode().unwrap().iter_instructions().count()
+    );
+}
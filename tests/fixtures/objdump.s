@@ -0,0 +1,74 @@
+# Reference program for tests/objdump.rs, covering every RV64IM instruction without compressed
+# encodings. Regenerate objdump.elf and objdump.txt as described in tests/objdump.rs.
+_start:
+    lui a5, 0x1
+    lui t0, 0xfffff
+    auipc gp, 0x10
+    jal ra, main
+    jalr zero, 0(ra)
+    jalr ra, -8(t1)
+main:
+    beq a0, zero, _start
+    bne a0, a1, main
+    blt s0, s1, exit
+    bge t0, t1, main
+    bltu a2, a3, exit
+    bgeu a4, a5, _start
+    lb a0, -1(sp)
+    lh a1, 2(sp)
+    lw a2, -4(s0)
+    ld a3, 8(s0)
+    lbu a4, 0(gp)
+    lhu a5, 2047(tp)
+    lwu a6, -2048(t2)
+    sb a0, -1(sp)
+    sh a1, 2(sp)
+    sw a2, -4(s0)
+    sd a3, 8(s0)
+    addi sp, sp, -32
+    slti a0, a1, 5
+    sltiu a0, a1, -5
+    xori a2, a3, 255
+    ori a4, a5, -1
+    andi s2, s3, 15
+    slli s4, s5, 63
+    srli s6, s7, 1
+    srai s8, s9, 32
+    add s10, s11, t3
+    sub t4, t5, t6
+    sll a0, a1, a2
+    slt a0, a1, a2
+    sltu a0, a1, a2
+    xor a0, a1, a2
+    srl a0, a1, a2
+    sra a0, a1, a2
+    or a0, a1, a2
+    and a0, a1, a2
+    addiw a0, a1, -7
+    slliw a0, a1, 31
+    srliw a0, a1, 3
+    sraiw a0, a1, 4
+    addw a0, a1, a2
+    subw a0, a1, a2
+    sllw a0, a1, a2
+    srlw a0, a1, a2
+    sraw a0, a1, a2
+    mul a0, a1, a2
+    mulh a0, a1, a2
+    mulhsu a0, a1, a2
+    mulhu a0, a1, a2
+    div a0, a1, a2
+    divu a0, a1, a2
+    rem a0, a1, a2
+    remu a0, a1, a2
+    mulw a0, a1, a2
+    divw a0, a1, a2
+    divuw a0, a1, a2
+    remw a0, a1, a2
+    remuw a0, a1, a2
+    fence iorw, iorw
+    fence r, w
+    fence.i
+    ebreak
+exit:
+    ecall
@@ -0,0 +1,182 @@
+//! Compare the disassembly against a reference listing of objdump.
+//!
+//! `fixtures/objdump.elf` wraps the code of `fixtures/objdump.s`, assembled with
+//! `llvm-mc -triple=riscv64 -mattr=+m`, in a RISC-U executable built with `common::ElfBuilder`.
+//! The reference `fixtures/objdump.txt` is the output of
+//! `llvm-objdump -d -M no-aliases --mattr=+m tests/fixtures/objdump.elf`, run from the root of the
+//! crate with LLVM 14.0.6. Both are regenerated by the ignored test `regenerate_fixtures`:
+//!
+//! ```sh
+//! cargo test --test objdump -- --ignored regenerate_fixtures
+//! ```
+
+mod common;
+
+use common::*;
+use riscu::{disassembly::DisasmStyle, load_object_file};
+use std::{fs, path::Path, process::Command};
+use tempfile::tempdir;
+
+/// An instruction of a listing: address, raw encoding and the normalized assembly tokens.
+type Line = (u64, u32, Vec<String>);
+
+/// Split the assembly text into mnemonic and operands, ignoring punctuation. Numbers are compared
+/// by value, since objdump prints upper immediates in decimal, and x8 is called `s0` like objdump
+/// does.
+fn normalize(text: &str) -> Vec<String> {
+    // objdump appends the symbolic branch target, e.g. `0x10000 <.text>`
+    let text = text.split(" <").next().unwrap();
+
+    text.split(|c: char| c.is_whitespace() || ",()".contains(c))
+        .filter(|t| !t.is_empty())
+        .map(|token| {
+            let number = match token.strip_prefix("0x") {
+                Some(hex) => i64::from_str_radix(hex, 16).ok(),
+                None => token.parse::<i64>().ok(),
+            };
+
+            match (token, number) {
+                (_, Some(n)) => n.to_string(),
+                ("fp", None) => String::from("s0"),
+                _ => token.to_string(),
+            }
+        })
+        .collect()
+}
+
+fn parse_objdump(listing: &str) -> Vec<Line> {
+    listing
+        .lines()
+        .filter_map(|line| {
+            let (address, rest) = line.trim_start().split_once(": ")?;
+            let address = u64::from_str_radix(address, 16).ok()?;
+            let (bytes, text) = rest.split_once('\t')?;
+
+            let raw = bytes.split_whitespace().rev().try_fold(0u32, |raw, b| {
+                Some(raw << 8 | u32::from_str_radix(b, 16).ok()?)
+            })?;
+
+            Some((address, raw, normalize(text)))
+        })
+        .collect()
+}
+
+fn parse_disassembly(listing: &str) -> Vec<Line> {
+    listing
+        .lines()
+        .map(|line| {
+            let (address, rest) = line.split_once(": ").unwrap();
+            let (raw, text) = rest.split_once("  ").unwrap();
+
+            (
+                u64::from_str_radix(address.trim_start_matches("0x"), 16).unwrap(),
+                u32::from_str_radix(raw, 16).unwrap(),
+                normalize(text),
+            )
+        })
+        .collect()
+}
+
+/// Run an LLVM tool in the root of the crate and return its output.
+fn llvm(tool: &str, args: &[&str]) -> Vec<u8> {
+    let output = Command::new(tool)
+        .args(args)
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .output()
+        .unwrap();
+
+    assert!(
+        output.status.success(),
+        "{}: {}",
+        tool,
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    output.stdout
+}
+
+#[test]
+#[ignore]
+fn regenerate_fixtures() {
+    let dir = tempdir().unwrap();
+    let object = dir.path().join("objdump.o");
+    let text = dir.path().join("objdump.bin");
+
+    llvm(
+        "llvm-mc",
+        &[
+            "-triple=riscv64",
+            "-mattr=+m",
+            "-filetype=obj",
+            "tests/fixtures/objdump.s",
+            "-o",
+            object.to_str().unwrap(),
+        ],
+    );
+    llvm(
+        "llvm-objcopy",
+        &[
+            "-O",
+            "binary",
+            "--only-section=.text",
+            object.to_str().unwrap(),
+            text.to_str().unwrap(),
+        ],
+    );
+
+    let code = fs::read(text).unwrap();
+    let size = code.len() as u64;
+
+    let elf = ElfBuilder::new(0x10000)
+        .segment(0x10000, PF_R | PF_X, code)
+        .segment(0x11000, PF_R | PF_W, vec![0; 8])
+        .section(
+            ".text",
+            SHT_PROGBITS,
+            SHF_ALLOC | SHF_EXECINSTR,
+            0x10000,
+            size,
+        )
+        .section(".data", SHT_PROGBITS, SHF_ALLOC | SHF_WRITE, 0x11000, 8)
+        .build();
+
+    let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+    fs::write(fixtures.join("objdump.elf"), elf).unwrap();
+
+    let listing = llvm(
+        "llvm-objdump",
+        &[
+            "-d",
+            "-M",
+            "no-aliases",
+            "--mattr=+m",
+            "tests/fixtures/objdump.elf",
+        ],
+    );
+    fs::write(fixtures.join("objdump.txt"), listing).unwrap();
+}
+
+#[test]
+fn disassembly_matches_objdump() {
+    let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+
+    let program = load_object_file(fixtures.join("objdump.elf"))
+        .unwrap()
+        .decode()
+        .unwrap();
+
+    let mut listing = Vec::new();
+    program
+        .write_disassembly(&mut listing, DisasmStyle::Compact)
+        .unwrap();
+    let actual = parse_disassembly(&String::from_utf8(listing).unwrap());
+
+    let expected = parse_objdump(&fs::read_to_string(fixtures.join("objdump.txt")).unwrap());
+
+    assert!(!expected.is_empty());
+    assert_eq!(actual.len(), expected.len());
+
+    for (actual, expected) in actual.iter().zip(&expected) {
+        assert_eq!(actual, expected, "at {:#x}", expected.0);
+    }
+}